@@ -2,22 +2,60 @@ mod ffi {
     use std::ffi::{c_void, CStr, CString};
     use std::os::raw::c_char;
 
+    /// Returns the number of values visited, or -1 for NULL and -2 for non-UTF-8 `csv`, the
+    /// negated codes chap7 uses, in which case `c_callback` is never called.
     #[no_mangle]
     pub extern "C" fn csv_for_each_value(
         csv: *const c_char,
         c_callback: unsafe extern "C" fn(*const c_char, *const c_void),
         context: *const c_void,
-    ) {
-        let csv = unsafe { CStr::from_ptr(csv) }.to_str().unwrap();
-        super::for_each_value(csv, |value| {
+    ) -> i64 {
+        if csv.is_null() {
+            return -1;
+        }
+        let csv = match unsafe { CStr::from_ptr(csv) }.to_str() {
+            Ok(csv) => csv,
+            Err(_) => return -2,
+        };
+        let count = super::for_each_value(csv, |value| {
             let value = CString::new(value).unwrap();
             unsafe { c_callback(value.as_ptr(), context) };
+            true
         });
+        count as i64
+    }
+
+    /// Like `csv_for_each_value`, but stops after the first value `c_callback` returns false for.
+    /// The count includes that value.
+    #[no_mangle]
+    pub extern "C" fn csv_for_each_value_until(
+        csv: *const c_char,
+        c_callback: unsafe extern "C" fn(*const c_char, *const c_void) -> bool,
+        context: *const c_void,
+    ) -> i64 {
+        if csv.is_null() {
+            return -1;
+        }
+        let csv = match unsafe { CStr::from_ptr(csv) }.to_str() {
+            Ok(csv) => csv,
+            Err(_) => return -2,
+        };
+        let count = super::for_each_value(csv, |value| {
+            let value = CString::new(value).unwrap();
+            unsafe { c_callback(value.as_ptr(), context) }
+        });
+        count as i64
     }
 }
 
-fn for_each_value(csv: &str, callback: impl Fn(&str)) {
+/// Stops after the first value `callback` returns false for. Returns the number delivered.
+fn for_each_value(csv: &str, mut callback: impl FnMut(&str) -> bool) -> u64 {
+    let mut count = 0;
     for value in csv.split(",") {
-        callback(value.trim());
+        count += 1;
+        if !callback(value.trim()) {
+            break;
+        }
     }
+    count
 }
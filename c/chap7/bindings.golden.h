@@ -0,0 +1,1072 @@
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define CHUNK_SIZE (64 * 1024)
+
+typedef enum Command {
+  Command_Version,
+  Command_Bytes,
+  Command_Characters,
+  Command_Words,
+  Command_Check,
+} Command;
+
+typedef enum CountErrorCode {
+  CountErrorCode_Ok,
+  CountErrorCode_NullPointer,
+  CountErrorCode_InvalidUtf8,
+  CountErrorCode_CallbackPanicked,
+  CountErrorCode_FileNotFound,
+  CountErrorCode_PathOutsideRoot,
+  CountErrorCode_Io,
+  CountErrorCode_InvalidArgument,
+  CountErrorCode_BufferTooSmall,
+  CountErrorCode_Cancelled,
+  CountErrorCode_ListCycle,
+  CountErrorCode_ListTooDeep,
+  CountErrorCode_FileTooLarge,
+  CountErrorCode_UnknownMetric,
+  CountErrorCode_LimitExceeded,
+  CountErrorCode_UnknownKey,
+  CountErrorCode_TimedOut,
+  CountErrorCode_SelfTestFailed,
+} CountErrorCode;
+
+/**
+ * `Human` abbreviates with SI suffixes to one decimal place, e.g. `1.2M`; values below 1000 are
+ * written in full.
+ */
+typedef enum CountFormat {
+  CountFormat_Plain,
+  CountFormat_ThousandsSeparated,
+  CountFormat_Human,
+} CountFormat;
+
+typedef enum FileMode {
+  FileMode_Normal,
+  FileMode_CsvList,
+  FileMode_CsvMerged,
+} FileMode;
+
+typedef enum OutputMode {
+  OutputMode_Stdout,
+  OutputMode_Callback,
+  OutputMode_Both,
+  OutputMode_Discard,
+} OutputMode;
+
+typedef enum ParseError {
+  ParseError_Ok,
+  ParseError_NullPointer,
+  ParseError_InvalidUtf8,
+  ParseError_MalformedPair,
+  ParseError_InvalidEscape,
+  ParseError_MissingCommand,
+  ParseError_UnknownCommand,
+  ParseError_MissingFilename,
+  ParseError_UnknownFileMode,
+  ParseError_UnknownWordRule,
+  ParseError_UnknownFormat,
+  ParseError_UnknownKey,
+  ParseError_UnknownEncoding,
+  ParseError_UnknownFlag,
+} ParseError;
+
+typedef enum SelfTestStage {
+  SelfTestStage_Passed,
+  SelfTestStage_Count,
+  SelfTestStage_CsvSplit,
+  SelfTestStage_Merge,
+  SelfTestStage_StringRoundTrip,
+} SelfTestStage;
+
+/**
+ * How input bytes become characters. `Latin1` maps each byte to the code point of the same
+ * value, so decoding cannot fail.
+ *
+ */
+typedef enum TextEncoding {
+  TextEncoding_Utf8,
+  TextEncoding_Latin1,
+} TextEncoding;
+
+/**
+ * `Whitespace` is the default and splits on Unicode whitespace. `UnicodeWords` follows the
+ * UAX #29 word boundaries closely enough for prose: ideographs and hiragana count individually,
+ * katakana runs form one word, apostrophes and decimal points stay inside a word, and hyphens
+ * separate words.
+ *
+ */
+typedef enum WordRule {
+  WordRule_Whitespace,
+  WordRule_UnicodeWords,
+} WordRule;
+
+/**
+ * Contents of a file behind a `file_open` handle, with each metric filled in on first query.
+ */
+typedef struct LoadedFile LoadedFile;
+
+typedef struct MergeJob MergeJob;
+
+typedef struct MergeStream MergeStream;
+
+/**
+ * A borrowed, read-only string owned by the library. It must not be freed, and `ptr` is not
+ * guaranteed to be NUL-terminated; read exactly `len` bytes.
+ */
+typedef struct StrView {
+  const char *ptr;
+  uintptr_t len;
+} StrView;
+
+typedef struct Arguments {
+  enum Command command;
+  const char *filename;
+  enum FileMode file_mode;
+  enum WordRule word_rule;
+  bool newline_fix;
+  enum CountFormat format;
+  bool strict;
+  enum TextEncoding encoding;
+} Arguments;
+
+/**
+ * A library-allocated list of strings, released with `string_array_free`.
+ */
+typedef struct StringArray {
+  char **items;
+  uintptr_t len;
+} StringArray;
+
+typedef struct FileCount {
+  char *filename;
+  enum CountErrorCode status;
+  uint64_t chars;
+  uint64_t bytes;
+} FileCount;
+
+typedef struct BatchOptions {
+  uintptr_t threads;
+} BatchOptions;
+
+typedef struct TextStats {
+  uint64_t bytes;
+  uint64_t chars;
+  uint64_t words;
+  uint64_t lines;
+} TextStats;
+
+/**
+ * With `recursive` set, list entries ending in `.csv` are read as nested lists and merged in
+ * place. A `max_depth` of 0 selects the default limit.
+ *
+ * A nonzero `max_file_bytes` caps how much any one file contributes: larger files fail with
+ * `CountErrorCode_FileTooLarge`, or are cut at the last whole character within the cap when
+ * `truncate_oversized` is set.
+ *
+ * `ensure_trailing_newline` appends `\n` to each non-empty file that does not already end with
+ * one, so files never run together.
+ *
+ * `with_manifest` records where each file landed in the merged text; see
+ * `csv_merge_take_manifest`.
+ *
+ * `transform`, when set, sees each file before it is merged, together with `transform_context`.
+ * `content` holds `content_len` bytes followed by a NUL. Returning NULL keeps the content;
+ * anything else replaces it and must be a NUL-terminated UTF-8 string allocated by the library
+ * (`count_string_dup` or any result freed with `count_string_free`), which the merge then frees.
+ * Size caps, limits and the manifest all apply to the replaced content.
+ *
+ * A nonzero `timeout_ms` fails the merge with `CountErrorCode_TimedOut` once it passes. It is
+ * checked before each list or file is read, so one slow read can still overrun it.
+ */
+typedef struct MergeOptions {
+  bool recursive;
+  uint32_t max_depth;
+  uint64_t max_file_bytes;
+  bool truncate_oversized;
+  bool ensure_trailing_newline;
+  bool with_manifest;
+  char *(*transform)(const char *filename, const char *content, uintptr_t content_len, void *context);
+  void *transform_context;
+  uint64_t timeout_ms;
+} MergeOptions;
+
+/**
+ * The bytes `merged[start..start + len]` came from `filename`, after any truncation. Inserted
+ * newlines fall between spans, never inside one. Nested lists contribute their leaf files.
+ */
+typedef struct MergeSpan {
+  char *filename;
+  uint64_t start;
+  uint64_t len;
+} MergeSpan;
+
+/**
+ * A nonzero `timeout_ms` limits counts that read files, which fail with
+ * `CountErrorCode_TimedOut` once it passes; counts of in-memory text ignore it.
+ *
+ * `with_digest` adds the SHA-256 of the input bytes, as lowercase hex, to JSON output such as
+ * `count_summary_json_with_options`. Other counts ignore it.
+ */
+typedef struct CountOptions {
+  enum WordRule word_rule;
+  enum TextEncoding encoding;
+  uint64_t timeout_ms;
+  bool with_digest;
+} CountOptions;
+
+void print_version(void);
+
+struct StrView version_string(void);
+
+int32_t version_string_buf(char *buf, uintptr_t buf_len, uintptr_t *needed);
+
+/**
+ * JSON object describing this build: crate version, enabled features, target triple, profile
+ * and rustc version. Free with `count_string_free`.
+ */
+char *count_build_info(void);
+
+uint64_t count_characters(const char *text);
+
+/**
+ * Every byte before the terminator is one ISO-8859-1 character.
+ */
+uint64_t count_characters_latin1(const char *text);
+
+/**
+ * Copies `string` into memory owned by the library, for callbacks that hand a string back to it.
+ * Returns NULL for NULL. Free with `count_string_free` if it is not handed over.
+ */
+char *count_string_dup(const char *string);
+
+void count_string_free(char *string);
+
+struct Arguments parse_args(uintptr_t argc, const char *const *argv);
+
+char *arguments_serialize(const struct Arguments *args);
+
+/**
+ * On success `out->filename` is allocated by the library (or NULL when absent) and is
+ * released with `count_string_free`. Unknown keys are reported to the warning callback and
+ * otherwise ignored, or fail with `ParseError_UnknownKey` in strict mode.
+ */
+enum ParseError arguments_deserialize(const char *s, struct Arguments *out);
+
+/**
+ * Value of the command spelled `name`, aliases included, or -1 when there is none.
+ */
+int32_t command_from_str(const char *name);
+
+/**
+ * Canonical name of the command with value `command`, or NULL for a value outside the enum.
+ * The string is static.
+ */
+const char *command_name(int command);
+
+/**
+ * Value of the file mode spelled `name`, as in `mode=<name>` of a serialized argument list,
+ * or -1 when there is none.
+ */
+int32_t file_mode_from_str(const char *name);
+
+/**
+ * Name of the file mode with value `mode`, or NULL for a value outside the enum. The string
+ * is static.
+ */
+const char *file_mode_name(int mode);
+
+/**
+ * Help text listing every command and flag. Free with `count_string_free`.
+ */
+char *usage_string(void);
+
+/**
+ * Candidates for the last entry of `argv` (an empty token when `argv` holds only the program
+ * name): command names in the command position, flags after the filename, and nothing for
+ * the filename itself. Release `out` with `string_array_free`.
+ */
+int32_t complete_args(uintptr_t argc, const char *const *argv, struct StringArray *out);
+
+int32_t count_files_batch(const char *csv, struct FileCount **out, uintptr_t *out_len);
+
+/**
+ * Counts every file named in `csv` on up to `options->threads` workers (NULL or 0 picks the
+ * available parallelism). Results keep the input order, and a file that fails only sets its
+ * own `status`. Release the array with `file_count_array_free`.
+ */
+int32_t count_files_batch_with_options(const char *csv,
+                                       const struct BatchOptions *options,
+                                       struct FileCount **out,
+                                       uintptr_t *out_len);
+
+void file_count_array_free(struct FileCount *counts, uintptr_t len);
+
+/**
+ * Frees every string and the array itself, then resets `*array` to empty. NULL is ignored.
+ */
+void string_array_free(struct StringArray *array);
+
+/**
+ * Turns on memoization of `count_all` results, keeping the `max_entries` most recently used
+ * inputs. 0 disables the cache again. Re-enabling keeps existing entries that still fit.
+ */
+void count_cache_enable(uintptr_t max_entries);
+
+/**
+ * Drops every cached result; the cache stays enabled with the same bound.
+ */
+void count_cache_clear(void);
+
+/**
+ * Writes how many results the cache holds and how many `count_all` calls it has answered
+ * since it was last enabled from disabled. Both are 0 while it is disabled.
+ */
+int32_t count_cache_stats(uintptr_t *out_entries, uint64_t *out_hits);
+
+/**
+ * Returns the number of values visited, or a negated `CountErrorCode` for NULL or non-UTF-8
+ * `csv` or one over the value limit, in which case `c_callback` is never called.
+ */
+int64_t csv_for_each_value(const char *csv,
+                           void (*c_callback)(const char*, const void*),
+                           const void *context);
+
+/**
+ * Like `csv_for_each_value`, but stops after the first value `c_callback` returns false for.
+ * The count includes that value.
+ */
+int64_t csv_for_each_value_until(const char *csv,
+                                 bool (*c_callback)(const char*, const void*),
+                                 const void *context);
+
+/**
+ * `stats` is only valid for the duration of each callback. Returns what `csv_for_each_value`
+ * returns.
+ */
+int64_t csv_for_each_value_stats(const char *csv, void (*callback)(const char *value,
+                                                                   const struct TextStats *stats,
+                                                                   void *context), void *context);
+
+/**
+ * Takes ownership of `csv` and releases it with `free_csv`. Returns NULL on failure, including
+ * NULL or non-UTF-8 `csv`, with the cause in `last_error_code`.
+ */
+char *csv_merge_files(char *csv, void (*free_csv)(char*));
+
+/**
+ * Takes ownership of `csv` as `csv_merge_files` does.
+ */
+char *csv_merge_files_dedup_lines(char *csv, void (*free_csv)(char*));
+
+/**
+ * Deduplicated merge as in `csv_merge_files_dedup_lines`, plus how many bytes it saved over
+ * plain concatenation. Free `*out_merged` with `csv_free_merged_file`.
+ */
+int32_t csv_merge_dedup_stats(const char *csv, char **out_merged, uint64_t *out_bytes_saved);
+
+int32_t csv_merge_files_buf(const char *csv, char *buf, uintptr_t buf_len, uintptr_t *needed);
+
+/**
+ * `options` may be NULL for the defaults. On success `*merged` receives a string freed with
+ * `csv_free_merged_file`. With `options->with_manifest` set, the spans of the merge are kept
+ * for `csv_merge_take_manifest` on this thread; any other call discards earlier spans.
+ */
+int32_t csv_merge_files_checked(const char *csv, const struct MergeOptions *options, char **merged);
+
+/**
+ * Runs `metric` over each file named in `csv`, in list order, and reports it with the
+ * filename. Files are read through the content root. The first file that cannot be read
+ * stops the run with its error code, after the files before it have been reported.
+ */
+int32_t csv_count_files(const char *csv,
+                        enum Command metric,
+                        void (*cb)(const char *filename, uint64_t count, const void *context),
+                        const void *context);
+
+/**
+ * Calls `cb` for each file named in `csv`, in order, with whether it exists as a regular file
+ * under the content root; only metadata is read. Files outside the root count as missing.
+ * Without a root, each value is checked as a path of its own, and the built-in fixtures
+ * exist too. Returns the number of missing files, or a negated `CountErrorCode` when `csv`
+ * is invalid or `cb` panics.
+ */
+int32_t csv_validate_manifest(const char *csv,
+                              void (*cb)(const char *filename, bool exists, const void *context),
+                              const void *context);
+
+/**
+ * Hands over the spans recorded by the last `csv_merge_files_checked` on this thread, one per
+ * merged file in merge order, and forgets them. Without a recorded manifest the array is
+ * empty. Release it with `merge_span_array_free`.
+ */
+int32_t csv_merge_take_manifest(struct MergeSpan **out, uintptr_t *out_len);
+
+void merge_span_array_free(struct MergeSpan *spans, uintptr_t len);
+
+void csv_free_merged_file(char *merged);
+
+/**
+ * Returns NULL for NULL or non-UTF-8 `csv` or a non-ASCII `delimiter`.
+ */
+char *csv_common_prefix(const char *csv, char delimiter);
+
+/**
+ * Returns 1 when the last field of the first line of `csv` is empty after trimming, as in
+ * `"a,b,"`, 0 when it is not, and -1 for NULL or non-UTF-8 input or a non-ASCII `delimiter`.
+ * Quotes are not interpreted.
+ */
+int32_t csv_has_trailing_empty(const char *csv, char delimiter);
+
+/**
+ * Returns 1 when both CSVs hold the same trimmed values with the same multiplicities, in any
+ * order, 0 otherwise, and a negated `CountErrorCode` when either is NULL or not UTF-8 or
+ * `delimiter` is not ASCII.
+ */
+int32_t csv_equal_unordered(const char *a, const char *b, char delimiter);
+
+/**
+ * Calls `callback` for every value that repeats an earlier one, with the zero-based positions
+ * of the first occurrence and the repeat. Returns the number of repeats, or a negated
+ * `CountErrorCode` for NULL or non-UTF-8 `csv` or one over the value limit.
+ */
+int64_t csv_find_duplicates(const char *csv, void (*callback)(const char *value,
+                                                              uintptr_t first_index,
+                                                              uintptr_t duplicate_index,
+                                                              void *context), void *context);
+
+/**
+ * Picks whichever of `,` `;` `\t` `|` occurs on the first line and splits every non-empty line
+ * into the same number of fields, preferring the most fields. Fails with
+ * `CountErrorCode_InvalidArgument` when no candidate is consistent.
+ */
+int32_t detect_delimiter(const char *text, char *out);
+
+/**
+ * Splits with quoting: a field that starts with `quote` runs to the matching closing `quote`
+ * and may contain the delimiter, and a doubled `quote` inside it stands for one quote
+ * character. Unquoted fields are trimmed. An unterminated quote, text after a closing quote,
+ * or a `delimiter` or `quote` outside ASCII fails with `CountErrorCode_InvalidArgument`.
+ */
+int32_t csv_for_each_field_quoted(const char *csv,
+                                  char delimiter,
+                                  char quote,
+                                  void (*callback)(const char *value, void *context),
+                                  void *context);
+
+/**
+ * Field count under the same rules as `csv_for_each_field_quoted`.
+ */
+int32_t csv_count_fields_quoted(const char *csv, char delimiter, char quote, uint64_t *out);
+
+/**
+ * Bytes spent on double-quote syntax: the quotes around quoted fields plus one byte per
+ * doubled `""` escape. Parsing follows `csv_for_each_field_quoted` with `"` as the quote;
+ * quotes inside unquoted fields are content, not overhead.
+ */
+int32_t csv_quoting_overhead(const char *csv, char delimiter, uint64_t *out);
+
+/**
+ * Treats the first non-empty line as a header: `out_columns` gets its field count, parsed
+ * like `csv_for_each_field_quoted` with `"` as the quote, and `out_data_rows` the number of
+ * non-empty lines after it. Empty input has no header and reports 0 for both.
+ */
+int32_t csv_header_and_row_count(const char *csv,
+                                 char delimiter,
+                                 uint64_t *out_columns,
+                                 uint64_t *out_data_rows);
+
+/**
+ * Number of `delimiter`-separated values and the width in characters of the widest one after
+ * trimming. Quotes are not interpreted.
+ */
+int32_t csv_max_field_width(const char *csv,
+                            char delimiter,
+                            uint64_t *out_count,
+                            uint64_t *out_max_width);
+
+/**
+ * Trimmed `delimiter`-separated values of `csv` that are not among the comma-separated,
+ * trimmed `stop_values`. An empty entry in `stop_values`, as in `"NA,,null"`, excludes empty
+ * fields.
+ */
+int32_t csv_count_excluding(const char *csv,
+                            char delimiter,
+                            const char *stop_values,
+                            uint64_t *out);
+
+/**
+ * Keeps the named columns of a comma-separated, double-quoted table, in the requested order
+ * and starting with the header row. Values are re-quoted where needed. A column missing from
+ * the header returns NULL with the name in `last_error_message`. A NULL `columns` with a
+ * nonzero `n_columns` returns NULL with `CountErrorCode_NullPointer` in `last_error_code`.
+ * Free the result with `count_string_free`.
+ */
+char *csv_select_columns(const char *csv, const char *const *columns, uintptr_t n_columns);
+
+/**
+ * Lays out each non-empty line of `csv` as a row of columns padded to the widest value in
+ * characters, two spaces apart, with no trailing whitespace. Values are parsed like
+ * `csv_for_each_field_quoted` with `"` as the quote, so quoted values are shown without
+ * their quotes. Short rows are padded with empty cells. Returns NULL on invalid input; free
+ * the result with `count_string_free`.
+ */
+char *csv_align(const char *csv, char delimiter);
+
+/**
+ * Reads the file at `path` one line at a time and calls `record_cb` for each non-empty line
+ * with its fields, parsed like `csv_for_each_field_quoted` with `"` as the quote. The field
+ * strings are only valid during the call. A trailing `\r` is dropped, so CRLF files work.
+ * Quoted values may not span lines, and only one line is held in memory at a time.
+ */
+int32_t csv_stream_from_path(const char *path,
+                             char delimiter,
+                             void (*record_cb)(const char *const *fields,
+                                               uintptr_t field_count,
+                                               const void *context),
+                             const void *context);
+
+struct MergeStream *merge_stream_new(const char *csv);
+
+/**
+ * Returns 0 after writing the next bytes into `buf`, 1 once the merged output is exhausted
+ * (with nothing written), or a negated `CountErrorCode` when a file fails to load. Bytes
+ * delivered before an error are a partial merge; the error is reported by the call following
+ * the last successful one and repeats on every later call.
+ */
+int32_t merge_stream_next(struct MergeStream *stream,
+                          uint8_t *buf,
+                          uintptr_t buf_len,
+                          uintptr_t *written);
+
+void merge_stream_free(struct MergeStream *stream);
+
+int32_t count_file_digest(const char *path, uint8_t (*out)[32]);
+
+/**
+ * SHA-256 of the bytes before the NUL terminator, whatever their encoding, so it matches
+ * `count_file_digest` of a file holding the same bytes.
+ */
+int32_t count_text_digest(const char *text, uint8_t (*out)[32]);
+
+/**
+ * In strict mode every warning also fails the operation that raised it, with the error code
+ * for its class. The warning callback still fires first.
+ */
+void set_strict_mode(bool enabled);
+
+/**
+ * Passing NULL removes the callback. Warnings are dropped while no callback is set.
+ */
+void set_warning_callback(void (*callback)(const char *message, void *context), void *context);
+
+int32_t last_error_code(void);
+
+/**
+ * The returned message is owned by the library and stays valid until the next failing call on
+ * this thread. Inside a callback, a message from a nested call also ends when the call that
+ * invoked the callback returns.
+ */
+const char *last_error_message(void);
+
+/**
+ * Same lifetime as `last_error_message`; empty when no error has been recorded.
+ */
+struct StrView last_error_message_view(void);
+
+/**
+ * Static description of a `CountErrorCode` value, valid for the lifetime of the program.
+ */
+struct StrView count_error_message(int32_t code);
+
+/**
+ * NUL-terminated form of `count_error_message`. Takes a plain integer because C may pass any
+ * value; unknown codes get a generic description.
+ */
+const char *error_code_message(int32_t code);
+
+int32_t set_content_root(const char *path);
+
+/**
+ * Counting stops with `CountErrorCode_Cancelled` once the byte behind `cancel` becomes
+ * non-zero; it is checked before every chunk. A NULL `cancel` never cancels.
+ */
+int32_t count_file_bytes(const char *path, const uint8_t *cancel, uint64_t *out);
+
+/**
+ * Line count of the file at `path`, read in chunks so it never has to fit in memory, or -1
+ * on failure with the cause in `last_error_code` and `last_error_message`. A non-NULL
+ * `progress` is called every 16 MiB and once at the end with `bytes_done == bytes_total`.
+ */
+int64_t count_lines_in_file(const char *path, void (*progress)(uint64_t bytes_done,
+                                                               uint64_t bytes_total,
+                                                               void *context), void *context);
+
+/**
+ * `count_all` over the file at `path`, decoded as `options->encoding` (NULL options count
+ * with the defaults). `options->timeout_ms` is checked after every chunk read.
+ */
+int32_t count_file_stats(const char *path,
+                         const struct CountOptions *options,
+                         struct TextStats *out);
+
+/**
+ * Non-overlapping occurrences of `needle` in the file at `path`, searched in a read-only
+ * memory mapping of the whole file instead of through a read buffer. An empty `needle`
+ * fails with `CountErrorCode_InvalidArgument`. The file must not shrink while it is being
+ * searched.
+ */
+int32_t count_substring_mmap(const char *path, const char *needle, uint64_t *out);
+
+/**
+ * Reads the UTF-8 file at `path` once for repeated `file_chars`, `file_words` and
+ * `file_lines` queries, each of which is counted on first use only. Returns NULL on failure
+ * with the cause in `last_error_message`. A handle must not be used from two threads at
+ * once. Close it with `file_close`.
+ */
+struct LoadedFile *file_open(const char *path);
+
+/**
+ * Characters in the file. Each metric returns 0 for a NULL handle.
+ */
+uint64_t file_chars(struct LoadedFile *file);
+
+/**
+ * Whitespace-separated words.
+ */
+uint64_t file_words(struct LoadedFile *file);
+
+uint64_t file_lines(struct LoadedFile *file);
+
+void file_close(struct LoadedFile *file);
+
+/**
+ * Starts `csv_merge_files_checked` for `csv` on a background thread and returns a handle to
+ * it, or NULL with the cause in `last_error_message` when `csv` is invalid. `options` may be
+ * NULL for the defaults; a `transform` in it is called on the job's thread. Wait with
+ * `csv_job_wait_timeout` and release the handle with `csv_job_free`.
+ */
+struct MergeJob *csv_job_start(const char *csv, const struct MergeOptions *options);
+
+/**
+ * Waits up to `ms` milliseconds for the job to finish; 0 waits for as long as it takes.
+ * Returns `CountErrorCode_TimedOut` while the job is still running, which keeps running and
+ * can be waited on again. Once it has finished, every call returns the merge's own status,
+ * with its message in `last_error_message`.
+ */
+int32_t csv_job_wait_timeout(struct MergeJob *job, uint64_t ms);
+
+/**
+ * Hands over the merged text of a job that finished successfully, freed with
+ * `csv_free_merged_file`. Fails with the job's status if it failed, with
+ * `CountErrorCode_TimedOut` if it is still running, and with `CountErrorCode_InvalidArgument`
+ * once the text has been taken.
+ */
+int32_t csv_job_take_result(struct MergeJob *job, char **merged);
+
+/**
+ * Releases the handle without waiting. A job that is still running finishes in the
+ * background, and its result is discarded.
+ */
+void csv_job_free(struct MergeJob *job);
+
+/**
+ * Writes `value` with `group_separator` between groups of three digits, e.g. `1,234,567`.
+ * A NUL separator disables grouping; a non-ASCII one returns NULL. Free the result with
+ * `count_string_free`.
+ */
+char *format_count(uint64_t value, char group_separator);
+
+/**
+ * Locale-independent formatting of `value` in the given style. Free the result with
+ * `count_string_free`.
+ */
+char *format_count_styled(uint64_t value, enum CountFormat style);
+
+/**
+ * 0 leaves a limit unset, which is the default for all three. Exceeding one fails with
+ * `CountErrorCode_LimitExceeded`, and `last_error_message` names the limit.
+ */
+void set_limits(uint64_t max_text_bytes, uint64_t max_merge_total_bytes, uint64_t max_csv_values);
+
+/**
+ * Adds a metric named `name` after the built-ins. Fails with
+ * `CountErrorCode_InvalidArgument` when the name is already taken.
+ */
+int32_t register_metric(const char *name,
+                        uint64_t (*metric)(const char *text, void *context),
+                        void *context);
+
+/**
+ * Built-ins come first (bytes, characters, words, lines), then registered metrics in
+ * registration order. Release `out` with `string_array_free`.
+ */
+int32_t list_metrics(struct StringArray *out);
+
+/**
+ * Unknown names fail with `CountErrorCode_UnknownMetric`.
+ */
+int32_t run_metric(const char *name, const char *text, uint64_t *out);
+
+/**
+ * Receives each line of library output, without its newline, while the mode is `Callback` or
+ * `Both`. Passing NULL removes the callback.
+ */
+void set_output_callback(void (*callback)(const char *line, void *context), void *context);
+
+/**
+ * Chooses where library-printed text such as `print_version` goes; the default is
+ * `OutputMode_Stdout`. Functions that return strings are unaffected.
+ */
+void set_output_mode(enum OutputMode mode);
+
+/**
+ * Runs the core counting, CSV, merge and string-ownership paths on built-in fixtures and
+ * returns the first `SelfTestStage` that failed, or `SelfTestStage_Passed`. The details of a
+ * failure are in `last_error_message`. Nothing is read from disk, and the content root and
+ * registered callbacks are neither used nor changed.
+ */
+int32_t count_self_test(void);
+
+/**
+ * A NULL `options` counts with the defaults. With `TextEncoding_Latin1` every byte is one
+ * character and the text is never rejected as invalid.
+ */
+int32_t count_all(const char *text, const struct CountOptions *options, struct TextStats *out);
+
+/**
+ * Counts data pulled from `read`, which fills up to `buf_len` bytes of `buf` and returns how
+ * many it wrote, 0 at the end of the stream, or a negative value on failure. A failure ends
+ * the count with `CountErrorCode_Io`, and the returned value appears in `last_error_message`.
+ * Words follow the whitespace rule; characters may be split across reads.
+ */
+int32_t count_stream(intptr_t (*read)(uint8_t *buf, uintptr_t buf_len, void *context),
+                     void *context,
+                     struct TextStats *out);
+
+/**
+ * `count_stream` with the word rule and encoding of `options`, giving the same counts as
+ * `count_all` over the concatenated reads. A NULL `options` counts with the defaults, and
+ * `options->timeout_ms` is ignored.
+ */
+int32_t count_stream_with_options(intptr_t (*read)(uint8_t *buf, uintptr_t buf_len, void *context),
+                                  void *context,
+                                  const struct CountOptions *options,
+                                  struct TextStats *out);
+
+/**
+ * Markdown table of the default counts, one row per metric with the counts right-aligned.
+ * Free the result with `count_string_free`; NULL or non-UTF-8 text returns NULL.
+ */
+char *count_report_markdown(const char *text);
+
+/**
+ * `{"chars":N,"bytes":N,"words":N,"lines":N,"graphemes":N}` for `text`, with the keys always
+ * in that order. The first four match `count_all` with the defaults and come from one pass;
+ * graphemes are approximated as `unicode::count_graphemes` describes. Free the result with
+ * `count_string_free`; NULL or non-UTF-8 text returns NULL.
+ */
+char *count_summary_json(const char *text);
+
+/**
+ * `count_summary_json` counted with `options`, which may be NULL for the defaults. With
+ * `options->with_digest` set the object ends with `"sha256":"<64 hex digits>"` over the
+ * bytes of `text`. Under `TextEncoding_Latin1` no text is rejected.
+ */
+char *count_summary_json_with_options(const char *text, const struct CountOptions *options);
+
+int32_t weighted_char_count(const char *text,
+                            uint64_t (*weight_cb)(uint32_t codepoint, const void *context),
+                            const void *context,
+                            uint64_t *out);
+
+char *first_distinct_chars(const char *text, uint64_t n);
+
+void window_char_counts(const char *text,
+                        uint64_t window,
+                        void (*cb)(uint64_t window_index, uint64_t count, const void *context),
+                        const void *context);
+
+/**
+ * Groups are reported in order of first appearance. Blocks come from a built-in table of the
+ * common ones: the Latin, Greek, Cyrillic, Armenian, Hebrew, Arabic, Syriac, Devanagari,
+ * Bengali, Thai, Georgian and Hangul blocks, general punctuation and symbols, kana and CJK
+ * ideographs (including Extension B), and the main emoji blocks. Characters in any other
+ * block are counted under "Unknown".
+ */
+void for_each_block(const char *text, void (*cb)(const char *block_name,
+                                                 uint64_t count,
+                                                 const void *context), const void *context);
+
+/**
+ * Groups are reported in order of first appearance. Letters are assigned a script from a
+ * built-in table covering Latin, Greek, Cyrillic, Armenian, Hebrew, Arabic, Syriac,
+ * Devanagari, Bengali, Thai, Georgian, Hangul, Hiragana, Katakana and Han; other letters are
+ * "Unknown". Combining marks are "Inherited" and everything else, such as digits,
+ * punctuation and spaces, is "Common".
+ */
+void for_each_script(const char *text, void (*cb)(const char *script_name,
+                                                  uint64_t count,
+                                                  const void *context), const void *context);
+
+/**
+ * Words are whitespace-separated and measured in characters; lengths are reported in
+ * ascending order.
+ */
+void for_each_word_length(const char *text, void (*cb)(uint64_t length,
+                                                       uint64_t count,
+                                                       const void *context), const void *context);
+
+int32_t count_characters_capped(const char *text,
+                                uintptr_t max_bytes,
+                                uint64_t *out_chars,
+                                bool *out_truncated);
+
+/**
+ * Characters in the Mark categories (Mn, Mc, Me) as `general_category` classifies them, so
+ * a decomposed `"e\u{301}"` has 1. Marks are only recognized in the ranges its tables list:
+ * the common combining and enclosing diacritics, and the Devanagari and Bengali vowel signs,
+ * including spacing (Mc) ones such as U+093E.
+ */
+int32_t count_combining_marks(const char *text, uint64_t *out);
+
+int32_t count_excluding_categories(const char *text, const char *categories, uint64_t *out);
+
+/**
+ * Returns 1 when a NUL byte appears before the last of the `len` bytes, meaning the data would
+ * be cut short (or rejected) when passed as a C string; a single trailing terminator is fine.
+ * Returns 0 otherwise, including for a NULL `text` with `len` 0, and -1 for NULL with `len`
+ * above 0.
+ */
+int32_t contains_interior_nul(const char *text, uintptr_t len);
+
+/**
+ * Counts `len` bytes the way Python's `surrogateescape` decodes them: valid UTF-8 sequences
+ * count as one character each, and every byte that is not part of one counts as one unit of
+ * its own. Strict counting rejects such input instead, and a truncated multi-byte sequence
+ * here counts one unit per byte rather than one character. NUL bytes are ordinary
+ * characters. A NULL `bytes` is only accepted with `len` 0.
+ */
+int32_t count_characters_os(const char *bytes, uintptr_t len, uint64_t *out);
+
+/**
+ * The Unicode scalar values of `text`, one `u32` each, with their number in `out_len`.
+ * Returns NULL when `text` is NULL or not UTF-8, with the cause in `last_error_message`.
+ * Empty text gives a non-NULL array of length 0. Free with `decode_codepoints_free`.
+ */
+uint32_t *decode_codepoints(const char *text, uintptr_t *out_len);
+
+/**
+ * `len` must be the length `decode_codepoints` reported.
+ */
+void decode_codepoints_free(uint32_t *codepoints, uintptr_t len);
+
+/**
+ * Guesses which supported encoding the `len` bytes are in and writes its `--encoding` name,
+ * `"utf8"` or `"latin1"`, to `out_name` (free with `count_string_free`). Non-ASCII input
+ * that decodes as UTF-8 scores 100; pure ASCII decodes the same either way and scores 50.
+ * Anything else is Latin-1, scored by the share of its high bytes outside the C1 control
+ * range 0x80-0x9F, which real Latin-1 text rarely uses. A NULL `bytes` is only accepted with
+ * `len` 0.
+ */
+int32_t detect_encoding(const char *bytes,
+                        uintptr_t len,
+                        char **out_name,
+                        uint64_t *out_confidence_x100);
+
+/**
+ * Per-mille of the `len` bytes that are ASCII control bytes other than tab, line feed, form
+ * feed and carriage return. Bytes from 0x80 up count as text, so UTF-8 and Latin-1 both
+ * score low. Empty input scores 0. A NULL `text` is only accepted with `len` 0.
+ */
+int32_t binary_ratio_x1000(const char *text, uintptr_t len, uint64_t *out);
+
+/**
+ * A NULL `error_offset` skips reporting where the first invalid sequence starts.
+ */
+bool validate_utf8(const uint8_t *data, uintptr_t len, uintptr_t *error_offset);
+
+/**
+ * Counts maximal runs of ASCII digits, so `1.5` is two integers. Signs are ignored: `-4`
+ * counts the same as `4`, and `1-2` is two integers.
+ */
+int32_t count_integers(const char *text, uint64_t *out);
+
+int32_t count_sections(const char *text,
+                       const char *separator,
+                       enum Command metric,
+                       void (*cb)(uint64_t section_index, uint64_t count, const void *context),
+                       const void *context);
+
+int32_t count_chars_in_set(const char *text, const char *set, uint64_t *out);
+
+/**
+ * Ties go to the shorter length; text without lines reports 0.
+ */
+int32_t modal_line_length(const char *text, uint64_t *out);
+
+/**
+ * Understands `\n`, `\t`, `\\` and `\xNN` (taken as the code point U+00NN); any other
+ * escape fails with `CountErrorCode_InvalidArgument`.
+ */
+int32_t count_characters_unescaped(const char *text, uint64_t *out);
+
+/**
+ * Spaces and tabs at the end of each line; line terminators are not counted.
+ */
+int32_t count_trailing_whitespace_bytes(const char *text, uint64_t *out);
+
+/**
+ * Consecutive `\n` bytes ending the text; a `\r` before one stops the run.
+ */
+int32_t count_trailing_newlines(const char *text, uint64_t *out);
+
+/**
+ * Lines longer than `max_len` characters; terminators are not counted.
+ */
+int32_t count_long_lines(const char *text, uint64_t max_len, uint64_t *out);
+
+/**
+ * Same count as `count_long_lines`, also calling `callback` with the 1-based number and the
+ * length of each offending line. A NULL `out` skips the total.
+ */
+int32_t for_each_long_line(const char *text,
+                           uint64_t max_len,
+                           void (*callback)(uint64_t line_number, uint64_t length, void *context),
+                           void *context,
+                           uint64_t *out);
+
+/**
+ * A sentence is text ending in one or more of `.` `!` `?`, or trailing text without one.
+ * Words are whitespace-separated; the average is words per sentence times 100, or 0 for no
+ * sentences. NULL outputs are skipped.
+ */
+int32_t readability_stats(const char *text,
+                          uint64_t *out_sentences,
+                          uint64_t *out_words,
+                          uint64_t *out_avg_words_x100);
+
+/**
+ * A URL starts at the first `http://` or `https://` in a whitespace-separated token and runs
+ * to the end of that token, punctuation included; a bare scheme is not counted.
+ */
+int32_t count_urls(const char *text, uint64_t *out);
+
+/**
+ * Same matching as `count_urls`, calling `callback` with each URL. A NULL `out` skips the
+ * total.
+ */
+int32_t for_each_url(const char *text,
+                     void (*callback)(const char *url, void *context),
+                     void *context,
+                     uint64_t *out);
+
+/**
+ * Lines identical to the line right before them, as `uniq -d` sees runs: `a a b b b` has
+ * three repeats.
+ */
+int32_t count_consecutive_duplicate_lines(const char *text, uint64_t *out);
+
+/**
+ * Calls `cb` once per non-empty bucket in ascending order, with the bucket's lower bound. Line
+ * lengths are in characters without terminators; a `bucket_size` of 0 fails with
+ * `CountErrorCode_InvalidArgument`.
+ */
+int32_t line_length_histogram(const char *text,
+                              uint64_t bucket_size,
+                              void (*cb)(uint64_t bucket_start, uint64_t count, const void *context),
+                              const void *context);
+
+/**
+ * Compares the sets of distinct lines: `added` counts lines of `b` missing from `a`, and
+ * `removed` the reverse. Order and repetition are ignored.
+ */
+int32_t count_line_diff(const char *a, const char *b, uint64_t *out_added, uint64_t *out_removed);
+
+/**
+ * The character at the 1-based `rank` when distinct characters are ordered by descending
+ * count, then ascending code point. A `rank` of 0 or past the last distinct character fails
+ * with `CountErrorCode_InvalidArgument`.
+ */
+int32_t nth_frequent_char(const char *text,
+                          uint64_t rank,
+                          uint32_t *out_codepoint,
+                          uint64_t *out_count);
+
+/**
+ * Replaces every run of Unicode whitespace with one space, leading and trailing runs
+ * included. `out_removed` gets how many whitespace characters were dropped, so a lone tab
+ * turned into a space removes none. Free `*out` with `count_string_free`.
+ */
+int32_t collapse_whitespace(const char *text, char **out, uint64_t *out_removed);
+
+/**
+ * Rewrites every line ending (`\r\n`, a lone `\r` or a lone `\n`) as LF for `style` 0,
+ * CRLF for 1 or CR for 2, counting in `out_changed` the endings that were in another form.
+ * Other styles fail with `CountErrorCode_InvalidArgument`. Free `*out` with
+ * `count_string_free`.
+ */
+int32_t normalize_newlines(const char *text, uint32_t style, char **out, uint64_t *out_changed);
+
+/**
+ * Character-level Levenshtein distance between `a` and `b`. Either string being longer than
+ * 4096 characters fails with `CountErrorCode_InvalidArgument`, since the cost grows with the
+ * product of the lengths.
+ */
+int32_t edit_distance(const char *a, const char *b, uint64_t *out);
+
+/**
+ * Whitespace-separated words of `text`, reading it in 4 KiB steps and stopping at the first
+ * step that passes `limit`. Then `out` gets `limit` and `out_exceeded` true; otherwise the
+ * full count and false. Input after the stopping point is never read, so invalid UTF-8 or
+ * the configured text limit there are not reported.
+ */
+int32_t count_words_capped(const char *text, uint64_t limit, uint64_t *out, bool *out_exceeded);
+
+/**
+ * Whitespace-separated tokens matching `pattern`, where `*` matches any run of characters and
+ * `?` exactly one; everything else matches itself. The whole token must match.
+ */
+int32_t count_glob_matches(const char *text, const char *pattern, uint64_t *out);
+
+/**
+ * Characters in `transform(text)` minus characters in `text`. The output is released with
+ * `free_output`, so it may come from any allocator; a NULL output fails with
+ * `CountErrorCode_InvalidArgument`.
+ */
+int32_t count_transform_delta(const char *text,
+                              char *(*transform)(const char *input, const void *context),
+                              void (*free_output)(char*),
+                              const void *context,
+                              int64_t *out_delta);
+
+/**
+ * Blocks of non-blank lines separated by one or more blank lines, where a blank line is
+ * empty or only whitespace. Blank lines at either end do not start a paragraph.
+ */
+int32_t count_paragraphs(const char *text, uint64_t *out);
+
+/**
+ * Line breaks before the first and after the last non-whitespace character, so
+ * `"\n\na\n\n\n"` has 2 leading and 3 trailing blank lines and `"a\n"` has 1 trailing.
+ * Text that is all whitespace counts every line break as leading.
+ */
+int32_t count_edge_blank_lines(const char *text, uint64_t *out_leading, uint64_t *out_trailing);
+
+/**
+ * Overlapping runs of `n` characters: the character count minus `n - 1`, or 0 for text
+ * shorter than `n`. An `n` of 0 fails with `CountErrorCode_InvalidArgument`.
+ */
+int32_t count_char_ngrams(const char *text, uint64_t n, uint64_t *out);
+
+/**
+ * Calls `cb` once per distinct n-gram counted by `count_char_ngrams`, in order of first
+ * appearance, with how often it occurs.
+ */
+int32_t char_ngram_frequencies(const char *text,
+                               uint64_t n,
+                               void (*cb)(const char *ngram, uint64_t count, const void *context),
+                               const void *context);
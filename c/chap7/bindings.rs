@@ -0,0 +1,10 @@
+// Shared by build.rs and tests/bindings.rs, so the golden header is generated exactly as the
+// build generates target/bridge/bindings.h.
+
+fn bindings_builder(crate_dir: &str) -> cbindgen::Builder {
+    cbindgen::Builder::new()
+        .with_crate(crate_dir)
+        .with_language(cbindgen::Language::C)
+        .include_item("CountErrorCode")
+        .include_item("SelfTestStage")
+}
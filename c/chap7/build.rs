@@ -1,17 +1,81 @@
-use cbindgen::Language;
 use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+include!("bindings.rs");
 
 fn main() {
+    println!("cargo:rerun-if-changed=bindings.rs");
     println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=src/modules/api.rs");
+    println!("cargo:rerun-if-changed=src/modules/args.rs");
+    println!("cargo:rerun-if-changed=src/modules/batch.rs");
+    println!("cargo:rerun-if-changed=src/modules/buffer.rs");
+    println!("cargo:rerun-if-changed=src/modules/cache.rs");
     println!("cargo:rerun-if-changed=src/modules/csv.rs");
-    println!("cargo:rerun-if-changed=src/modules/files/mod.rs");
+    println!("cargo:rerun-if-changed=src/modules/digest.rs");
+    println!("cargo:rerun-if-changed=src/modules/error.rs");
+    println!("cargo:rerun-if-changed=src/modules/file/mod.rs");
+    println!("cargo:rerun-if-changed=src/modules/format.rs");
+    println!("cargo:rerun-if-changed=src/modules/job.rs");
+    println!("cargo:rerun-if-changed=src/modules/limits.rs");
+    println!("cargo:rerun-if-changed=src/modules/metrics.rs");
+    println!("cargo:rerun-if-changed=src/modules/output.rs");
+    println!("cargo:rerun-if-changed=src/modules/selftest.rs");
+    println!("cargo:rerun-if-changed=src/modules/stats.rs");
+    println!("cargo:rerun-if-changed=src/modules/text.rs");
+    println!("cargo:rerun-if-changed=src/modules/unicode.rs");
 
     let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
 
-    cbindgen::Builder::new()
-        .with_crate(manifest_dir)
-        .with_language(Language::C)
+    bindings_builder(&manifest_dir)
         .generate()
         .expect("Unable to generate C bindings")
         .write_to_file("target/bridge/bindings.h");
+
+    write_build_info();
+}
+
+fn write_build_info() {
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(str::to_owned))
+        .map(|name| name.to_lowercase().replace('_', "-"))
+        .collect();
+    features.sort();
+    let features: Vec<String> = features.iter().map(|feature| json_string(feature)).collect();
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+    let rustc_version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map_or_else(|| "unknown".to_owned(), |version| version.trim().to_owned());
+
+    let json = format!(
+        "{{\"version\":{},\"features\":[{}],\"target\":{},\"profile\":{},\"rustc\":{}}}",
+        json_string(&env::var("CARGO_PKG_VERSION").unwrap()),
+        features.join(","),
+        json_string(&env::var("TARGET").unwrap()),
+        json_string(&env::var("PROFILE").unwrap()),
+        json_string(&rustc_version),
+    );
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("build_info.rs"), format!("const BUILD_INFO: &str = {json:?};\n"))
+        .expect("Unable to write build info");
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
 }
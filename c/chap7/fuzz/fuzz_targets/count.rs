@@ -0,0 +1,46 @@
+#![no_main]
+
+extern crate count;
+
+use libfuzzer_sys::fuzz_target;
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+
+extern "C" {
+    fn count_characters(text: *const c_char) -> u64;
+    fn weighted_char_count(
+        text: *const c_char,
+        weight_cb: unsafe extern "C" fn(codepoint: u32, context: *const c_void) -> u64,
+        context: *const c_void,
+        out: *mut u64,
+    ) -> i32;
+    fn first_distinct_chars(text: *const c_char, n: u64) -> *mut c_char;
+    fn count_string_free(string: *mut c_char);
+}
+
+unsafe extern "C" fn unit_weight(_codepoint: u32, _context: *const c_void) -> u64 {
+    1
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = CString::new(data) else { return };
+
+    let mut weighted = 0;
+    let status = unsafe { weighted_char_count(text.as_ptr(), unit_weight, std::ptr::null(), &mut weighted) };
+
+    match std::str::from_utf8(data) {
+        Ok(valid) => {
+            assert_eq!(status, 0);
+            let chars = unsafe { count_characters(text.as_ptr()) };
+            assert_eq!(chars, weighted);
+            assert!(chars as usize <= valid.len());
+
+            let distinct = unsafe { first_distinct_chars(text.as_ptr(), u64::MAX) };
+            assert!(!distinct.is_null());
+            let distinct_len = unsafe { CStr::from_ptr(distinct) }.to_bytes().len();
+            unsafe { count_string_free(distinct) };
+            assert!(distinct_len <= valid.len());
+        }
+        Err(_) => assert_ne!(status, 0),
+    }
+});
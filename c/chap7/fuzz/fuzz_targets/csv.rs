@@ -0,0 +1,45 @@
+#![no_main]
+
+extern crate count;
+
+use libfuzzer_sys::fuzz_target;
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+
+extern "C" {
+    fn csv_for_each_value(
+        csv: *const c_char,
+        c_callback: unsafe extern "C" fn(*const c_char, *const c_void),
+        context: *const c_void,
+    ) -> i64;
+}
+
+unsafe extern "C" fn collect(value: *const c_char, context: *const c_void) {
+    let values = &mut *(context as *mut Vec<String>);
+    values.push(CStr::from_ptr(value).to_str().unwrap().to_owned());
+}
+
+/// The values `csv_for_each_value` delivers for `csv`, after checking that its return value
+/// counts them.
+fn split(csv: &str) -> Vec<String> {
+    let csv = CString::new(csv).unwrap();
+    let mut values: Vec<String> = Vec::new();
+    let count = unsafe { csv_for_each_value(csv.as_ptr(), collect, &mut values as *mut Vec<String> as *const c_void) };
+    assert_eq!(count, values.len() as i64);
+    values
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else { return };
+    if text.contains('\0') {
+        return;
+    }
+
+    let values = split(text);
+    let expected: Vec<&str> = text.split(',').map(str::trim).collect();
+    assert_eq!(values, expected);
+
+    // Values are already trimmed and hold no commas, so joining and splitting them again is
+    // lossless.
+    assert_eq!(split(&values.join(",")), values);
+});
@@ -0,0 +1,22 @@
+#![no_main]
+
+use count::api;
+use libfuzzer_sys::fuzz_target;
+
+/// The built-in files a merge reads while no content root is set.
+const FIXTURES: [(&str, &str); 2] = [("chapter1.md", "# Getting started\n"), ("chapter2.md", "# Wrapping up\n")];
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(csv) = std::str::from_utf8(data) else { return };
+
+    // Without a content root every value is either a fixture or missing, and neither may panic.
+    let expected: Option<String> = api::csv_values(csv)
+        .into_iter()
+        .map(|value| FIXTURES.iter().find(|(name, _)| *name == value).map(|(_, contents)| *contents))
+        .collect();
+    match (api::merge_files(csv), expected) {
+        (Ok(merged), Some(expected)) => assert_eq!(merged, expected),
+        (Err(error), None) => assert_eq!(error.code, api::CountErrorCode::FileNotFound),
+        (merged, expected) => panic!("merge of {csv:?} gave {merged:?}, expected {expected:?}"),
+    }
+});
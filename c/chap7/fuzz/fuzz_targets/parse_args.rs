@@ -0,0 +1,47 @@
+#![no_main]
+
+extern crate count;
+
+use count::api::{self, Command};
+use libfuzzer_sys::fuzz_target;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+#[repr(C)]
+struct Arguments {
+    command: Command,
+    filename: *const c_char,
+    file_mode: api::FileMode,
+    word_rule: api::WordRule,
+    newline_fix: bool,
+    format: api::CountFormat,
+    strict: bool,
+    encoding: api::TextEncoding,
+}
+
+extern "C" {
+    fn parse_args(argc: usize, argv: *const *const c_char) -> Arguments;
+}
+
+// NUL bytes separate the arguments, as they do in memory behind a real argv.
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else { return };
+    let arguments: Vec<&str> = text.split('\0').collect();
+
+    let Ok(parsed) = api::parse_args(&arguments) else { return };
+    assert_eq!(parsed.filename, arguments.get(2).copied());
+
+    // The C export panics on errors, so it only sees input the Rust parser accepted.
+    let owned: Vec<CString> = arguments.iter().map(|&argument| CString::new(argument).unwrap()).collect();
+    let argv: Vec<*const c_char> = owned.iter().map(|argument| argument.as_ptr()).collect();
+    let exported = unsafe { parse_args(argv.len(), argv.as_ptr()) };
+    assert_eq!(exported.command, parsed.command);
+    assert_eq!(exported.file_mode, parsed.file_mode);
+    assert_eq!(exported.word_rule, parsed.word_rule);
+    assert_eq!(exported.newline_fix, parsed.newline_fix);
+    assert_eq!(exported.format, parsed.format);
+    assert_eq!(exported.strict, parsed.strict);
+    assert_eq!(exported.encoding, parsed.encoding);
+    let filename = (!exported.filename.is_null()).then(|| unsafe { CStr::from_ptr(exported.filename) }.to_str().unwrap());
+    assert_eq!(filename, parsed.filename);
+});
@@ -1,77 +1,359 @@
 mod modules {
+    pub mod api;
+    pub mod args;
+    mod batch;
+    pub mod buffer;
+    mod cache;
     mod csv;
+    mod digest;
+    pub mod error;
     mod file;
+    mod job;
+    pub mod format;
+    mod limits;
+    mod metrics;
+    pub mod output;
+    mod selftest;
+    pub mod stats;
+    mod text;
+    mod unicode;
 }
 
-use std::ffi::CStr;
+#[cfg(test)]
+mod testutil;
+
+use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 use std::{slice, ptr};
 
+pub use modules::api;
+use modules::args;
+use modules::format::CountFormat;
+use modules::stats::{TextEncoding, WordRule};
+use modules::buffer::{self, StrView};
+use modules::error;
+use modules::output;
+
+const VERSION_STRING: &str = "count version 1.0.0";
+
+include!(concat!(env!("OUT_DIR"), "/build_info.rs"));
+
 #[no_mangle]
 pub extern "C" fn print_version() {
-    println!("count version 1.0.0");
+    output::write_line(VERSION_STRING);
 }
 
+#[no_mangle]
+pub extern "C" fn version_string() -> StrView {
+    StrView::new(VERSION_STRING)
+}
+
+// `buf` and `needed` are only touched by `copy_to_buffer`, which checks both for NULL and never
+// writes past `buf_len`.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[no_mangle]
+pub extern "C" fn version_string_buf(buf: *mut c_char, buf_len: usize, needed: *mut usize) -> i32 {
+    error::status(|| unsafe { buffer::copy_to_buffer(VERSION_STRING, buf, buf_len, needed) })
+}
+
+/// JSON object describing this build: crate version, enabled features, target triple, profile
+/// and rustc version. Free with `count_string_free`.
+#[no_mangle]
+pub extern "C" fn count_build_info() -> *mut c_char {
+    CString::new(BUILD_INFO).unwrap().into_raw()
+}
+
+// NULL is rejected up front; anything else must be a NUL-terminated string, as for every text export.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 #[no_mangle]
 pub extern "C" fn count_characters(text: *const c_char) -> u64 {
+    assert!(!text.is_null(), "Missing text.");
     let text = unsafe { CStr::from_ptr(text) };
     let text = text.to_str().expect("Unicode conversion failed.");
     text.chars().count().try_into().unwrap()
 }
 
+/// Every byte before the terminator is one ISO-8859-1 character.
+// Same contract as `count_characters`; no encoding check is needed.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[no_mangle]
+pub extern "C" fn count_characters_latin1(text: *const c_char) -> u64 {
+    assert!(!text.is_null(), "Missing text.");
+    let text = unsafe { CStr::from_ptr(text) };
+    text.to_bytes().len() as u64
+}
+
+/// Copies `string` into memory owned by the library, for callbacks that hand a string back to it.
+/// Returns NULL for NULL. Free with `count_string_free` if it is not handed over.
+// NULL is passed through; a non-NULL `string` must be NUL-terminated.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[no_mangle]
+pub extern "C" fn count_string_dup(string: *const c_char) -> *mut c_char {
+    if string.is_null() {
+        return ptr::null_mut();
+    }
+    unsafe { CStr::from_ptr(string) }.to_owned().into_raw()
+}
+
+// Only strings this library handed out may be passed here, as every allocating export documents.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[no_mangle]
+pub extern "C" fn count_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(unsafe { CString::from_raw(string) });
+    }
+}
+
 #[repr(C)]
 pub struct Arguments {
     command: Command,
     filename: *const c_char,
     file_mode: FileMode,
+    word_rule: WordRule,
+    newline_fix: bool,
+    format: CountFormat,
+    strict: bool,
+    encoding: TextEncoding,
 }
 
 /// cbindgen:prefix-with-name
 #[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum FileMode {
     Normal,
     CsvList,
     CsvMerged
 }
 
+impl FileMode {
+    const NAMES: [(FileMode, &'static str); 3] = [
+        (FileMode::Normal, "normal"),
+        (FileMode::CsvList, "csv-list"),
+        (FileMode::CsvMerged, "csv-merged"),
+    ];
+
+    fn from_name(name: &str) -> Option<FileMode> {
+        FileMode::NAMES.iter().find(|(_, n)| *n == name).map(|(mode, _)| *mode)
+    }
+
+    fn name(self) -> &'static str {
+        FileMode::NAMES.iter().find(|(mode, _)| *mode == self).unwrap().1
+    }
+}
+
 /// cbindgen:prefix-with-name
 #[repr(C)]
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Command {
     Version,
     Bytes,
     Characters,
+    Words,
+    Check,
 }
 
+impl Command {
+    fn from_name(name: &str) -> Option<Command> {
+        args::command_from_name(name)
+    }
+
+    fn name(self) -> &'static str {
+        args::command_name(self)
+    }
+}
+
+// `argv` holds `argc` NUL-terminated strings, exactly as `main` receives them.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 #[no_mangle]
 pub extern "C" fn parse_args(argc: usize, argv: *const *const c_char) -> Arguments {
-    let arguments = unsafe { slice::from_raw_parts(argv, argc) };
-
-    let command = arguments.get(1).copied().expect("Missing command.");
-    let command = unsafe { CStr::from_ptr(command) }.to_str().unwrap();
-    let command = match command {
-        "version" => Command::Version,
-        "bytes" => Command::Bytes,
-        "characters" => Command::Characters,
-        _ => panic!("Command not recognized: {command}")
-    };
-
-    let filename = arguments.get(2).copied();
-    if command != Command::Version && filename.is_none() {
-        panic!("Missing filename.");
-    }
-    let filename = filename.unwrap_or(ptr::null());
-
-    let file_mode = if let Some(csv_flag) = arguments.get(3).copied() {
-        let csv_flag = unsafe { CStr::from_ptr(csv_flag) }.to_str().unwrap();
-        match csv_flag {
-            "--csv-list" => FileMode::CsvList,
-            "--csv-merged" => FileMode::CsvMerged,
-            _ => panic!("CSV flag not recognized: {csv_flag}")
-        }
+    let arguments = unsafe { arguments_from_ptr(argc, argv) };
+    let arguments: Vec<&str> = arguments.iter().map(|&argument| unsafe { argument_to_str(argument) }).collect();
+    let parsed = api::parse_args_with_message(&arguments).unwrap_or_else(|(_, message)| panic!("{message}"));
+
+    Arguments {
+        command: parsed.command,
+        // The filename borrows argv, so its pointer is still the NUL-terminated original.
+        filename: parsed.filename.map_or(ptr::null(), |filename| filename.as_ptr() as *const c_char),
+        file_mode: parsed.file_mode,
+        word_rule: parsed.word_rule,
+        newline_fix: parsed.newline_fix,
+        format: parsed.format,
+        strict: parsed.strict,
+        encoding: parsed.encoding,
+    }
+}
+
+unsafe fn arguments_from_ptr<'a>(argc: usize, argv: *const *const c_char) -> &'a [*const c_char] {
+    if argv.is_null() || argc == 0 {
+        &[]
     } else {
-        FileMode::Normal
-    };
+        slice::from_raw_parts(argv, argc)
+    }
+}
+
+unsafe fn argument_to_str<'a>(argument: *const c_char) -> &'a str {
+    assert!(!argument.is_null(), "Missing argument.");
+    CStr::from_ptr(argument).to_str().expect("Unicode conversion failed.")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::*;
+    use crate::api::ParseError;
+    use crate::modules::args::COMMANDS;
+    use crate::testutil::ArgvBuilder;
+
+    /// The parsed arguments with the filename copied out, since it points into the builder's
+    /// argv; `filename` is left NULL.
+    fn parse(arguments: &[&str]) -> (Arguments, Option<String>) {
+        let mut builder = ArgvBuilder::new(arguments);
+        let (argc, argv) = builder.build();
+        let mut args = parse_args(argc, argv);
+        let filename = (!args.filename.is_null())
+            .then(|| unsafe { CStr::from_ptr(args.filename) }.to_str().unwrap().to_owned());
+        args.filename = ptr::null();
+        (args, filename)
+    }
+
+    /// The error path of `parse_args`, which panics instead of returning it: the same argv
+    /// conversion followed by the parser the export wraps.
+    fn parse_error(argc: usize, argv: *const *const c_char) -> (ParseError, String) {
+        let arguments = unsafe { arguments_from_ptr(argc, argv) };
+        let arguments: Vec<&str> = arguments.iter().map(|&argument| unsafe { argument_to_str(argument) }).collect();
+        api::parse_args_with_message(&arguments).unwrap_err()
+    }
+
+    fn parse_error_of(arguments: &[&str]) -> (ParseError, String) {
+        let mut builder = ArgvBuilder::new(arguments);
+        let (argc, argv) = builder.build();
+        parse_error(argc, argv)
+    }
 
-    Arguments { command, filename, file_mode }
+    #[test]
+    fn parse_args_accepts_every_command_and_alias() {
+        for spec in &COMMANDS {
+            for name in std::iter::once(spec.name).chain(spec.aliases.iter().copied()) {
+                let (args, filename) = parse(&["count", name, "notes.md"]);
+                assert_eq!(args.command, spec.command, "for `{name}`");
+                assert_eq!(filename.as_deref(), Some("notes.md"));
+            }
+        }
+        let (args, filename) = parse(&["count", "version"]);
+        assert_eq!((args.command, filename), (Command::Version, None));
+    }
+
+    #[test]
+    fn parse_args_defaults_without_flags() {
+        let (args, _) = parse(&["count", "words", "notes.md"]);
+        assert_eq!(args.file_mode, FileMode::Normal);
+        assert_eq!(args.word_rule, WordRule::Whitespace);
+        assert!(!args.newline_fix);
+        assert_eq!(args.format, CountFormat::Plain);
+        assert!(!args.strict);
+        assert_eq!(args.encoding, TextEncoding::Utf8);
+    }
+
+    #[test]
+    fn parse_args_filename_borrows_argv() {
+        let mut builder = ArgvBuilder::new(&["count", "bytes", "notes.md"]);
+        let (argc, argv) = builder.build();
+        let args = parse_args(argc, argv);
+        assert_eq!(args.filename, unsafe { *argv.add(2) });
+    }
+
+    #[test]
+    fn parse_args_accepts_every_flag() {
+        assert_eq!(parse(&["count", "words", "list.csv", "--csv-list"]).0.file_mode, FileMode::CsvList);
+        assert_eq!(parse(&["count", "words", "list.csv", "--csv-merged"]).0.file_mode, FileMode::CsvMerged);
+        for (rule, name) in WordRule::NAMES {
+            assert_eq!(parse(&["count", "words", "a.md", &format!("--word-rule={name}")]).0.word_rule, rule);
+        }
+        for (format, name) in CountFormat::NAMES {
+            assert_eq!(parse(&["count", "words", "a.md", &format!("--format={name}")]).0.format, format);
+        }
+        for (encoding, name) in TextEncoding::NAMES {
+            assert_eq!(parse(&["count", "words", "a.md", &format!("--encoding={name}")]).0.encoding, encoding);
+        }
+        assert!(parse(&["count", "words", "a.md", "--newline-fix"]).0.newline_fix);
+        assert!(parse(&["count", "words", "a.md", "--strict"]).0.strict);
+    }
+
+    #[test]
+    fn parse_args_combines_flags_and_lets_later_ones_win() {
+        let (args, _) = parse(&["count", "chars", "list.csv", "--csv-list", "--strict", "--format=human", "--csv-merged"]);
+        assert_eq!(args.command, Command::Characters);
+        assert_eq!(args.file_mode, FileMode::CsvMerged);
+        assert_eq!(args.format, CountFormat::Human);
+        assert!(args.strict);
+        assert!(!args.newline_fix);
+    }
+
+    #[test]
+    fn parse_args_reports_a_missing_command() {
+        assert_eq!(parse_error(0, ptr::null()).0, ParseError::MissingCommand);
+        let mut builder = ArgvBuilder::default();
+        let (argc, argv) = builder.build();
+        assert_eq!(parse_error(argc, argv), (ParseError::MissingCommand, "Missing command.".to_owned()));
+        assert_eq!(parse_error_of(&["count"]).0, ParseError::MissingCommand);
+    }
+
+    #[test]
+    fn parse_args_reports_an_unknown_command_with_a_suggestion() {
+        assert_eq!(
+            parse_error_of(&["count", "wrods", "a.md"]),
+            (ParseError::UnknownCommand, "Command not recognized: wrods (did you mean `words`?)".to_owned())
+        );
+        assert_eq!(
+            parse_error_of(&["count", "frobnicate", "a.md"]),
+            (ParseError::UnknownCommand, "Command not recognized: frobnicate".to_owned())
+        );
+    }
+
+    #[test]
+    fn parse_args_reports_a_missing_filename() {
+        for spec in COMMANDS.iter().filter(|spec| spec.command != Command::Version) {
+            assert_eq!(parse_error_of(&["count", spec.name]).0, ParseError::MissingFilename, "for `{}`", spec.name);
+        }
+    }
+
+    #[test]
+    fn parse_args_reports_unknown_flags() {
+        for flag in ["--verbose", "strict", "--format", "--format=loud", "--word-rule=", "--strict=yes", "--encoding=ebcdic"] {
+            let (error, message) = parse_error_of(&["count", "words", "a.md", flag]);
+            assert_eq!(error, ParseError::UnknownFlag, "for `{flag}`");
+            assert!(message.starts_with(&format!("Flag not recognized: {flag}")), "{message}");
+        }
+        assert_eq!(
+            parse_error_of(&["count", "words", "a.md", "--strct"]).1,
+            "Flag not recognized: --strct (did you mean `--strict`?)"
+        );
+    }
+
+    #[test]
+    fn parse_args_reports_the_first_bad_argument() {
+        assert_eq!(parse_error_of(&["count", "wrods", "a.md", "--verbose"]).0, ParseError::UnknownCommand);
+        assert_eq!(parse_error_of(&["count", "words", "a.md", "--verbose", "--strict=1"]).1, "Flag not recognized: --verbose");
+    }
+
+    #[test]
+    fn version_string_is_readable_through_its_view() {
+        let view = version_string();
+        let bytes = unsafe { std::slice::from_raw_parts(view.ptr as *const u8, view.len) };
+        assert_eq!(bytes, VERSION_STRING.as_bytes());
+    }
+
+    #[test]
+    fn build_info_names_the_crate_version() {
+        let info = count_build_info();
+        let json = unsafe { CStr::from_ptr(info) }.to_str().unwrap().to_owned();
+        count_string_free(info);
+        assert!(json.starts_with('{') && json.ends_with('}'), "{json}");
+        let version = format!("\"version\":\"{}\"", env!("CARGO_PKG_VERSION"));
+        assert!(json.contains(&version), "{json}");
+        for key in ["\"features\":[", "\"target\":\"", "\"profile\":\"", "\"rustc\":\""] {
+            assert!(json.contains(key), "{key} missing from {json}");
+        }
+    }
 }
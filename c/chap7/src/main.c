@@ -6,42 +6,66 @@
 
 typedef struct CommandContext {
     Command command;
+    WordRule word_rule;
     bool print_filename;
+    CountFormat format;
+    TextEncoding encoding;
 } CommandContext;
 
+int run_check(const char* filename);
+void print_duplicate(const char* value, size_t first_index, size_t duplicate_index, void* context);
 void run_command_for_file(const char* filename, const void* ctx_ptr);
-uint64_t do_calculation(Command command, const char* data);
+uint64_t do_calculation(const CommandContext* ctx, const char* data);
 uint64_t count_bytes(const char* data);
-void print_result(uint64_t result);
-void print_result_with_filename(uint64_t result, const char* filename);
+uint64_t count_words(const char* data, WordRule word_rule, TextEncoding encoding);
+void print_result(uint64_t result, CountFormat format);
+void print_result_with_filename(uint64_t result, const char* filename, CountFormat format);
 
 int main(const int argc, const char *argv[]) {
+    if (argc < 2) {
+        char* usage = usage_string();
+        fprintf(stderr, "%s\n", usage);
+        count_string_free(usage);
+        return 1;
+    }
     const Arguments args = parse_args(argc, argv);
+    set_strict_mode(args.strict);
 
     if (args.command == Command_Version) {
         print_version();
         return 0;
     }
+    if (args.command == Command_Check) {
+        return run_check(args.filename);
+    }
 
     switch (args.file_mode) {
         case FileMode_Normal: {
-            CommandContext ctx = { .command = args.command, .print_filename = false };
+            CommandContext ctx = { .command = args.command, .word_rule = args.word_rule, .print_filename = false, .format = args.format, .encoding = args.encoding };
             run_command_for_file(args.filename, &ctx);
             break;
         }
         case FileMode_CsvList: {
             char* csv = file_to_string(file_read(args.filename));
-            CommandContext ctx = { .command = args.command, .print_filename = true };
+            CommandContext ctx = { .command = args.command, .word_rule = args.word_rule, .print_filename = true, .format = args.format, .encoding = args.encoding };
             csv_for_each_value(csv, run_command_for_file, &ctx);
             file_free_string(csv);
             break;
         }
         case FileMode_CsvMerged: {
             char* csv = file_to_string(file_read(args.filename));
-            char* content = csv_merge_files(csv, file_free_string);
-            const size_t result = do_calculation(args.command, content);
+            const MergeOptions options = { .ensure_trailing_newline = args.newline_fix };
+            char* content = NULL;
+            const int32_t status = csv_merge_files_checked(csv, &options, &content);
+            file_free_string(csv);
+            if (status != CountErrorCode_Ok) {
+                fprintf(stderr, "%s\n", last_error_message());
+                return 1;
+            }
+            CommandContext ctx = { .command = args.command, .word_rule = args.word_rule, .print_filename = false, .format = args.format, .encoding = args.encoding };
+            const size_t result = do_calculation(&ctx, content);
             csv_free_merged_file(content);
-            print_result(result);
+            print_result(result, ctx.format);
             break;
         }
     }
@@ -49,42 +73,70 @@ int main(const int argc, const char *argv[]) {
     return 0;
 }
 
+int run_check(const char* filename) {
+    char* csv = file_to_string(file_read(filename));
+    const int64_t duplicates = csv_find_duplicates(csv, print_duplicate, NULL);
+    file_free_string(csv);
+    if (duplicates < 0) {
+        fprintf(stderr, "%s\n", last_error_message());
+        return 1;
+    }
+    return duplicates > 0 ? 1 : 0;
+}
+
+void print_duplicate(const char* value, const size_t first_index, const size_t duplicate_index, void* context) {
+    fprintf(stderr, "Duplicate entry %s at positions %zu and %zu\n", value, first_index + 1, duplicate_index + 1);
+}
+
 void run_command_for_file(const char* filename, const void* ctx_ptr) {
     const CommandContext* ctx = (CommandContext*) ctx_ptr;
     File file = file_read(filename);
     char* str = file_to_string(file);
 
-    const uint64_t result = do_calculation(ctx->command, str);
+    const uint64_t result = do_calculation(ctx, str);
     if (ctx->print_filename) {
-        print_result_with_filename(result, filename);
+        print_result_with_filename(result, filename, ctx->format);
     } else {
-        print_result(result);
+        print_result(result, ctx->format);
     }
 
     free(str);
     file_free(file);
 }
 
-uint64_t do_calculation(const Command command, const char* data) {
-    switch (command) {
+uint64_t do_calculation(const CommandContext* ctx, const char* data) {
+    switch (ctx->command) {
         case Command_Bytes:
             return count_bytes(data);
         case Command_Characters:
-            return count_characters(data);
+            return ctx->encoding == TextEncoding_Latin1 ? count_characters_latin1(data) : count_characters(data);
+        case Command_Words:
+            return count_words(data, ctx->word_rule, ctx->encoding);
         default:
-            fprintf(stderr, "Unrecognized command: %i\n", command);
+            fprintf(stderr, "Unrecognized command: %i\n", ctx->command);
             exit(1);
     }
 }
 
-void print_result(const uint64_t result) {
-    printf("%llu\n", result);
+void print_result(const uint64_t result, const CountFormat format) {
+    char* formatted = format_count_styled(result, format);
+    printf("%s\n", formatted);
+    count_string_free(formatted);
 }
 
-void print_result_with_filename(const uint64_t result, const char* filename) {
-    printf("%lli %s\n", result, filename);
+void print_result_with_filename(const uint64_t result, const char* filename, const CountFormat format) {
+    char* formatted = format_count_styled(result, format);
+    printf("%s %s\n", formatted, filename);
+    count_string_free(formatted);
 }
 
 uint64_t count_bytes(const char* data) {
     return strlen(data);
 }
+
+uint64_t count_words(const char* data, const WordRule word_rule, const TextEncoding encoding) {
+    const CountOptions options = { .word_rule = word_rule, .encoding = encoding };
+    TextStats stats;
+    count_all(data, &options, &stats);
+    return stats.words;
+}
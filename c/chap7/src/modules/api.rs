@@ -0,0 +1,185 @@
+//! Safe Rust entry points for crates that link against `count` directly instead of going through
+//! the C ABI. The C exports are built on these functions.
+//!
+//! ```
+//! use count::api;
+//!
+//! let stats = api::count_all("one two\nthree\n");
+//! assert_eq!((stats.words, stats.lines), (3, 2));
+//!
+//! assert_eq!(api::csv_values("a.md, b.md"), ["a.md", "b.md"]);
+//!
+//! let args = api::parse_args(&["count", "words", "notes.md", "--format=human"]).unwrap();
+//! assert_eq!(args.filename, Some("notes.md"));
+//! ```
+//!
+//! cbindgen:ignore
+
+use std::fmt;
+
+use crate::modules::csv;
+use crate::modules::error;
+use crate::modules::stats;
+
+pub use crate::modules::args::ParseError;
+pub use crate::modules::error::CountErrorCode;
+pub use crate::modules::format::CountFormat;
+pub use crate::modules::stats::{CountOptions, TextEncoding, TextStats, WordRule};
+pub use crate::{Command, FileMode};
+
+/// Bytes, characters, whitespace-separated words and lines of `text`.
+pub fn count_all(text: &str) -> TextStats {
+    stats::count_all(text, &CountOptions::default())
+}
+
+/// The comma-separated values of `csv`, trimmed, in order. Quotes are not interpreted.
+pub fn csv_values(csv: &str) -> Vec<&str> {
+    csv.split(',').map(str::trim).collect()
+}
+
+/// Concatenates the files named in `csv`, read through the configured content root.
+///
+/// ```
+/// use count::api::{self, CountErrorCode};
+///
+/// // With no content root set, only the built-in chapter fixtures can be read.
+/// assert_eq!(api::merge_files("chapter1.md, chapter2.md").unwrap(), "# Getting started\n# Wrapping up\n");
+/// assert_eq!(api::merge_files("missing.md").unwrap_err().code, CountErrorCode::FileNotFound);
+/// ```
+pub fn merge_files(csv: &str) -> Result<String, MergeError> {
+    csv::merge_files(csv).map_err(MergeError::from_code)
+}
+
+/// Parses a command line the way the `count` binary does; `args[0]` is the program name.
+///
+/// ```
+/// use count::api::{self, ParseError};
+///
+/// assert_eq!(api::parse_args(&["count", "wrods", "a.md"]), Err(ParseError::UnknownCommand));
+/// assert_eq!(api::parse_args(&["count", "bytes"]), Err(ParseError::MissingFilename));
+/// ```
+pub fn parse_args<'a>(args: &[&'a str]) -> Result<Arguments<'a>, ParseError> {
+    parse_args_with_message(args).map_err(|(error, _)| error)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Arguments<'a> {
+    pub command: Command,
+    pub filename: Option<&'a str>,
+    pub file_mode: FileMode,
+    pub word_rule: WordRule,
+    pub newline_fix: bool,
+    pub format: CountFormat,
+    pub strict: bool,
+    pub encoding: TextEncoding,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MergeError {
+    pub code: CountErrorCode,
+    pub message: String,
+}
+
+impl MergeError {
+    fn from_code(code: CountErrorCode) -> MergeError {
+        MergeError { code, message: error::message_for(code) }
+    }
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+/// Also returns the message the CLI reports, naming the offending argument.
+pub(crate) fn parse_args_with_message<'a>(args: &[&'a str]) -> Result<Arguments<'a>, (ParseError, String)> {
+    use crate::modules::args::{self, Flag, Position};
+
+    let mut parsed = Arguments {
+        command: Command::Version,
+        filename: None,
+        file_mode: FileMode::Normal,
+        word_rule: WordRule::Whitespace,
+        newline_fix: false,
+        format: CountFormat::Plain,
+        strict: false,
+        encoding: TextEncoding::Utf8,
+    };
+    let mut command = None;
+    for (index, &argument) in args.iter().enumerate().skip(1) {
+        match Position::of(index) {
+            Position::Command => match Command::from_name(argument) {
+                Some(parsed) => command = Some(parsed),
+                None => {
+                    let message = format!("Command not recognized: {argument}");
+                    return Err((ParseError::UnknownCommand, args::with_suggestion(message, argument, Position::Command)));
+                }
+            },
+            Position::Filename => parsed.filename = Some(argument),
+            Position::Flag => match args::parse_flag(argument) {
+                Some(Flag::FileMode(mode)) => parsed.file_mode = mode,
+                Some(Flag::WordRule(rule)) => parsed.word_rule = rule,
+                Some(Flag::Format(style)) => parsed.format = style,
+                Some(Flag::NewlineFix) => parsed.newline_fix = true,
+                Some(Flag::Strict) => parsed.strict = true,
+                Some(Flag::Encoding(encoding)) => parsed.encoding = encoding,
+                None => {
+                    let message = format!("Flag not recognized: {argument}");
+                    return Err((ParseError::UnknownFlag, args::with_suggestion(message, argument, Position::Flag)));
+                }
+            },
+        }
+    }
+
+    parsed.command = command.ok_or((ParseError::MissingCommand, "Missing command.".to_owned()))?;
+    if parsed.command != Command::Version && parsed.filename.is_none() {
+        return Err((ParseError::MissingFilename, "Missing filename.".to_owned()));
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Arguments, Command, CountErrorCode, CountFormat, FileMode, ParseError, TextEncoding, WordRule};
+
+    #[test]
+    fn count_all_uses_the_default_options() {
+        let stats = super::count_all("naïve café\n");
+        assert_eq!((stats.bytes, stats.chars, stats.words, stats.lines), (13, 11, 2, 1));
+    }
+
+    #[test]
+    fn csv_values_trims_without_unquoting() {
+        assert_eq!(super::csv_values(" a.md ,\"b, c\""), ["a.md", "\"b", "c\""]);
+        assert_eq!(super::csv_values(""), [""]);
+    }
+
+    #[test]
+    fn merge_errors_carry_the_recorded_message() {
+        let error = super::merge_files("chapter1.md, missing.md").unwrap_err();
+        assert_eq!(error.code, CountErrorCode::FileNotFound);
+        assert!(error.message.contains("missing.md"), "{}", error.message);
+        assert_eq!(error.to_string(), error.message);
+    }
+
+    #[test]
+    fn parse_args_fills_every_field() {
+        let parsed = super::parse_args(&["count", "chars", "a.md", "--csv-list", "--format=human", "--strict"]);
+        let expected = Arguments {
+            command: Command::Characters,
+            filename: Some("a.md"),
+            file_mode: FileMode::CsvList,
+            word_rule: WordRule::Whitespace,
+            newline_fix: false,
+            format: CountFormat::Human,
+            strict: true,
+            encoding: TextEncoding::Utf8,
+        };
+        assert_eq!(parsed, Ok(expected));
+        assert_eq!(super::parse_args(&["count"]), Err(ParseError::MissingCommand));
+        assert_eq!(super::parse_args(&["count", "version", "a.md", "--verbose"]), Err(ParseError::UnknownFlag));
+    }
+}
@@ -0,0 +1,626 @@
+mod ffi {
+    use std::ffi::{CStr, CString};
+    use std::os::raw::{c_char, c_int};
+    use std::ptr;
+    use std::sync::OnceLock;
+
+    use super::ParseError;
+    use crate::modules::buffer::StringArray;
+    use crate::modules::error::{self, CountErrorCode};
+    use crate::{Arguments, FileMode};
+
+    #[no_mangle]
+    pub extern "C" fn arguments_serialize(args: *const Arguments) -> *mut c_char {
+        match unsafe { args.as_ref() } {
+            Some(args) => {
+                let filename = (!args.filename.is_null())
+                    .then(|| unsafe { CStr::from_ptr(args.filename) }.to_string_lossy());
+                CString::new(super::serialize(args, filename.as_deref())).unwrap().into_raw()
+            }
+            None => ptr::null_mut(),
+        }
+    }
+
+    /// On success `out->filename` is allocated by the library (or NULL when absent) and is
+    /// released with `count_string_free`. Unknown keys are reported to the warning callback and
+    /// otherwise ignored, or fail with `ParseError_UnknownKey` in strict mode.
+    #[no_mangle]
+    pub extern "C" fn arguments_deserialize(s: *const c_char, out: *mut Arguments) -> ParseError {
+        if s.is_null() || out.is_null() {
+            return ParseError::NullPointer;
+        }
+        let s = match unsafe { CStr::from_ptr(s) }.to_str() {
+            Ok(s) => s,
+            Err(_) => return ParseError::InvalidUtf8,
+        };
+        match super::deserialize(s) {
+            Ok((mut args, filename)) => {
+                if let Some(filename) = filename {
+                    args.filename = CString::new(filename).unwrap().into_raw();
+                }
+                unsafe { out.write(args) };
+                ParseError::Ok
+            }
+            Err(err) => err,
+        }
+    }
+
+    /// Value of the command spelled `name`, aliases included, or -1 when there is none.
+    #[no_mangle]
+    pub extern "C" fn command_from_str(name: *const c_char) -> i32 {
+        match unsafe { error::str_from_ptr(name) } {
+            Ok(name) => super::command_from_name(name).map_or(-1, |command| command as i32),
+            Err(_) => -1,
+        }
+    }
+
+    /// Canonical name of the command with value `command`, or NULL for a value outside the enum.
+    /// The string is static.
+    #[no_mangle]
+    pub extern "C" fn command_name(command: c_int) -> *const c_char {
+        let index = super::COMMANDS.iter().position(|spec| spec.command as c_int == command);
+        let names = COMMAND_NAMES.get_or_init(|| super::COMMANDS.iter().map(|spec| CString::new(spec.name).unwrap()).collect());
+        index.map_or(ptr::null(), |index| names[index].as_ptr())
+    }
+
+    /// Value of the file mode spelled `name`, as in `mode=<name>` of a serialized argument list,
+    /// or -1 when there is none.
+    #[no_mangle]
+    pub extern "C" fn file_mode_from_str(name: *const c_char) -> i32 {
+        match unsafe { error::str_from_ptr(name) } {
+            Ok(name) => FileMode::from_name(name).map_or(-1, |mode| mode as i32),
+            Err(_) => -1,
+        }
+    }
+
+    /// Name of the file mode with value `mode`, or NULL for a value outside the enum. The string
+    /// is static.
+    #[no_mangle]
+    pub extern "C" fn file_mode_name(mode: c_int) -> *const c_char {
+        let index = FileMode::NAMES.iter().position(|(known, _)| *known as c_int == mode);
+        let names = FILE_MODE_NAMES.get_or_init(|| FileMode::NAMES.iter().map(|(_, name)| CString::new(*name).unwrap()).collect());
+        index.map_or(ptr::null(), |index| names[index].as_ptr())
+    }
+
+    static COMMAND_NAMES: OnceLock<Vec<CString>> = OnceLock::new();
+    static FILE_MODE_NAMES: OnceLock<Vec<CString>> = OnceLock::new();
+
+    /// Help text listing every command and flag. Free with `count_string_free`.
+    #[no_mangle]
+    pub extern "C" fn usage_string() -> *mut c_char {
+        CString::new(super::usage_string()).unwrap().into_raw()
+    }
+
+    /// Candidates for the last entry of `argv` (an empty token when `argv` holds only the program
+    /// name): command names in the command position, flags after the filename, and nothing for
+    /// the filename itself. Release `out` with `string_array_free`.
+    #[no_mangle]
+    pub extern "C" fn complete_args(argc: usize, argv: *const *const c_char, out: *mut StringArray) -> i32 {
+        error::status(|| {
+            let arguments = unsafe { crate::arguments_from_ptr(argc, argv) };
+            let arguments = arguments
+                .iter()
+                .map(|&argument| unsafe { error::str_from_ptr(argument) })
+                .collect::<Result<Vec<_>, CountErrorCode>>()?;
+            let (index, partial) = match arguments.len() {
+                0 | 1 => (1, ""),
+                len => (len - 1, arguments[len - 1]),
+            };
+            unsafe { error::write_out(out, StringArray::new(super::completions(index, partial))) }
+        })
+    }
+}
+
+use crate::modules::csv;
+use crate::modules::error::{self, CountErrorCode};
+use crate::modules::format::CountFormat;
+use crate::modules::stats::{TextEncoding, WordRule};
+use crate::{Arguments, Command, FileMode};
+use std::{fmt, ptr};
+
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParseError {
+    Ok,
+    NullPointer,
+    InvalidUtf8,
+    MalformedPair,
+    InvalidEscape,
+    MissingCommand,
+    UnknownCommand,
+    MissingFilename,
+    UnknownFileMode,
+    UnknownWordRule,
+    UnknownFormat,
+    UnknownKey,
+    UnknownEncoding,
+    UnknownFlag,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParseError::Ok => "No error.",
+            ParseError::NullPointer => "A required pointer argument was NULL.",
+            ParseError::InvalidUtf8 => "Input is not valid UTF-8.",
+            ParseError::MalformedPair => "Expected a key=value pair.",
+            ParseError::InvalidEscape => "Invalid percent escape.",
+            ParseError::MissingCommand => "Missing command.",
+            ParseError::UnknownCommand => "Command not recognized.",
+            ParseError::MissingFilename => "Missing filename.",
+            ParseError::UnknownFileMode => "File mode not recognized.",
+            ParseError::UnknownWordRule => "Word rule not recognized.",
+            ParseError::UnknownFormat => "Format not recognized.",
+            ParseError::UnknownKey => "Argument key not recognized.",
+            ParseError::UnknownEncoding => "Encoding not recognized.",
+            ParseError::UnknownFlag => "Flag not recognized.",
+        })
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub enum Flag {
+    FileMode(FileMode),
+    WordRule(WordRule),
+    Format(CountFormat),
+    Encoding(TextEncoding),
+    NewlineFix,
+    Strict,
+}
+
+#[derive(Clone, Copy)]
+pub enum Position {
+    Command,
+    Filename,
+    Flag,
+}
+
+impl Position {
+    pub fn of(index: usize) -> Position {
+        match index {
+            0 | 1 => Position::Command,
+            2 => Position::Filename,
+            _ => Position::Flag,
+        }
+    }
+}
+
+pub struct CommandSpec {
+    pub command: Command,
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub help: &'static str,
+}
+
+/// Every command the CLI accepts. Parsing, usage text, completion and suggestions all read this
+/// table, so a new command needs an entry here and a case in the C dispatch.
+pub const COMMANDS: [CommandSpec; 5] = [
+    CommandSpec { command: Command::Version, name: "version", aliases: &[], help: "Print the version." },
+    CommandSpec { command: Command::Bytes, name: "bytes", aliases: &[], help: "Count bytes." },
+    CommandSpec { command: Command::Characters, name: "characters", aliases: &["chars"], help: "Count characters." },
+    CommandSpec { command: Command::Words, name: "words", aliases: &[], help: "Count words." },
+    CommandSpec {
+        command: Command::Check,
+        name: "check",
+        aliases: &[],
+        help: "Report duplicate entries in a CSV file list.",
+    },
+];
+
+struct FlagSpec {
+    /// Spelled without the leading `--`, as are the aliases.
+    name: &'static str,
+    aliases: &'static [&'static str],
+    /// Accepted values of a `name=value` flag; `None` for a flag that takes no argument.
+    values: Option<fn() -> Vec<&'static str>>,
+    help: &'static str,
+    /// Receives the value, or an empty string for a flag without one.
+    parse: fn(&str) -> Option<Flag>,
+}
+
+const FLAGS: [FlagSpec; 7 + cfg!(test) as usize] = [
+    // File modes other than the default are flags spelled like the mode itself.
+    FlagSpec {
+        name: FileMode::NAMES[1].1,
+        aliases: &[],
+        values: None,
+        help: "Read the file as a CSV list of files and count each one.",
+        parse: |_| Some(Flag::FileMode(FileMode::NAMES[1].0)),
+    },
+    FlagSpec {
+        name: FileMode::NAMES[2].1,
+        aliases: &[],
+        values: None,
+        help: "Read the file as a CSV list of files and count them as one text.",
+        parse: |_| Some(Flag::FileMode(FileMode::NAMES[2].0)),
+    },
+    FlagSpec {
+        name: "word-rule",
+        aliases: &[],
+        values: Some(|| WordRule::NAMES.iter().map(|(_, name)| *name).collect()),
+        help: "How words are split.",
+        parse: |value| WordRule::from_name(value).map(Flag::WordRule),
+    },
+    FlagSpec {
+        name: "format",
+        aliases: &[],
+        values: Some(|| CountFormat::NAMES.iter().map(|(_, name)| *name).collect()),
+        help: "How counts are printed.",
+        parse: |value| CountFormat::from_name(value).map(Flag::Format),
+    },
+    FlagSpec {
+        name: "encoding",
+        aliases: &[],
+        values: Some(|| TextEncoding::NAMES.iter().map(|(_, name)| *name).collect()),
+        help: "How file bytes are decoded.",
+        parse: |value| TextEncoding::from_name(value).map(Flag::Encoding),
+    },
+    FlagSpec {
+        name: "newline-fix",
+        aliases: &[],
+        values: None,
+        help: "End each merged file with a newline.",
+        parse: |_| Some(Flag::NewlineFix),
+    },
+    FlagSpec {
+        name: "strict",
+        aliases: &[],
+        values: None,
+        help: "Treat warnings as errors.",
+        parse: |_| Some(Flag::Strict),
+    },
+    // Lets tests check that usage, completion and suggestions all follow this table.
+    #[cfg(test)]
+    FlagSpec {
+        name: "test-only",
+        aliases: &[],
+        values: Some(|| vec!["on", "off"]),
+        help: "Exists only in tests.",
+        parse: |value| (value == "on").then_some(Flag::Strict),
+    },
+];
+
+impl CommandSpec {
+    fn spellings(&self) -> impl Iterator<Item = &'static str> {
+        std::iter::once(self.name).chain(self.aliases.iter().copied())
+    }
+}
+
+impl FlagSpec {
+    fn spellings(&self) -> impl Iterator<Item = &'static str> {
+        std::iter::once(self.name).chain(self.aliases.iter().copied())
+    }
+}
+
+pub fn command_from_name(name: &str) -> Option<Command> {
+    COMMANDS.iter().find(|spec| spec.spellings().any(|n| n == name)).map(|spec| spec.command)
+}
+
+pub fn command_name(command: Command) -> &'static str {
+    COMMANDS.iter().find(|spec| spec.command == command).unwrap().name
+}
+
+pub fn parse_flag(flag: &str) -> Option<Flag> {
+    let flag = flag.strip_prefix("--")?;
+    let (name, value) = match flag.split_once('=') {
+        Some((name, value)) => (name, Some(value)),
+        None => (flag, None),
+    };
+    let spec = FLAGS.iter().find(|spec| spec.spellings().any(|n| n == name))?;
+    match (spec.values, value) {
+        (Some(_), Some(value)) => (spec.parse)(value),
+        (None, None) => (spec.parse)(""),
+        _ => None,
+    }
+}
+
+fn flag_spellings() -> Vec<String> {
+    let mut flags = Vec::new();
+    for spec in &FLAGS {
+        match spec.values {
+            Some(values) => flags.extend(values().into_iter().map(|value| format!("--{}={value}", spec.name))),
+            None => flags.push(format!("--{}", spec.name)),
+        }
+    }
+    flags
+}
+
+fn completions(index: usize, partial: &str) -> Vec<String> {
+    let candidates = match Position::of(index) {
+        Position::Command => COMMANDS.iter().map(|spec| spec.name.to_owned()).collect(),
+        Position::Filename => Vec::new(),
+        Position::Flag => flag_spellings(),
+    };
+    candidates.into_iter().filter(|candidate| candidate.starts_with(partial)).collect()
+}
+
+pub fn usage_string() -> String {
+    let mut rows = vec!["Usage: count <command> [filename] [flags]".to_owned(), String::new(), "Commands:".to_owned()];
+    let commands: Vec<(String, &str)> = COMMANDS
+        .iter()
+        .map(|spec| (spec.spellings().collect::<Vec<_>>().join(", "), spec.help))
+        .collect();
+    let flags: Vec<(String, String)> = FLAGS
+        .iter()
+        .map(|spec| match spec.values {
+            Some(values) => (format!("--{}=<value>", spec.name), format!("{} One of: {}.", spec.help, values().join(", "))),
+            None => (format!("--{}", spec.name), spec.help.to_owned()),
+        })
+        .collect();
+    let width = commands.iter().map(|(name, _)| name.len()).chain(flags.iter().map(|(name, _)| name.len())).max().unwrap_or(0);
+    rows.extend(commands.iter().map(|(name, help)| format!("  {name:width$}  {help}")));
+    rows.push(String::new());
+    rows.push("Flags:".to_owned());
+    rows.extend(flags.iter().map(|(name, help)| format!("  {name:width$}  {help}")));
+    rows.join("\n")
+}
+
+/// Appends a "did you mean" hint to a parse error when a known spelling is a couple of edits
+/// away from `input`.
+pub fn with_suggestion(message: String, input: &str, position: Position) -> String {
+    let candidates: Vec<String> = match position {
+        Position::Command => COMMANDS.iter().flat_map(CommandSpec::spellings).map(str::to_owned).collect(),
+        Position::Filename => Vec::new(),
+        Position::Flag => flag_spellings(),
+    };
+    let closest = candidates
+        .iter()
+        .map(|candidate| (edit_distance(input, candidate), candidate))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .min_by_key(|(distance, _)| *distance);
+    match closest {
+        Some((_, candidate)) => format!("{message} (did you mean `{candidate}`?)"),
+        None => message,
+    }
+}
+
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Levenshtein distance in characters, in O(len(a) * len(b)) time.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn serialize(args: &Arguments, filename: Option<&str>) -> String {
+    let mut pairs = vec![format!("command={}", args.command.name())];
+    if let Some(filename) = filename {
+        pairs.push(format!("filename={}", escape(filename)));
+    }
+    pairs.push(format!("mode={}", args.file_mode.name()));
+    pairs.push(format!("word_rule={}", args.word_rule.name()));
+    if args.newline_fix {
+        pairs.push("newline_fix=true".to_owned());
+    }
+    pairs.push(format!("format={}", args.format.name()));
+    pairs.push(format!("encoding={}", args.encoding.name()));
+    if args.strict {
+        pairs.push("strict=true".to_owned());
+    }
+    pairs.join(",")
+}
+
+fn deserialize(s: &str) -> Result<(Arguments, Option<String>), ParseError> {
+    let mut command = None;
+    let mut filename = None;
+    let mut file_mode = FileMode::Normal;
+    let mut word_rule = WordRule::Whitespace;
+    let mut newline_fix = false;
+    let mut format = CountFormat::Plain;
+    let mut strict = false;
+    let mut encoding = TextEncoding::Utf8;
+    for pair in csv::pairs(s) {
+        let (key, value) = pair.map_err(|_| ParseError::MalformedPair)?;
+        match key {
+            "command" => command = Some(Command::from_name(value).ok_or(ParseError::UnknownCommand)?),
+            "filename" => filename = Some(unescape(value).ok_or(ParseError::InvalidEscape)?),
+            "mode" => file_mode = FileMode::from_name(value).ok_or(ParseError::UnknownFileMode)?,
+            "word_rule" => word_rule = WordRule::from_name(value).ok_or(ParseError::UnknownWordRule)?,
+            "newline_fix" => newline_fix = value.parse().map_err(|_| ParseError::MalformedPair)?,
+            "format" => format = CountFormat::from_name(value).ok_or(ParseError::UnknownFormat)?,
+            "encoding" => encoding = TextEncoding::from_name(value).ok_or(ParseError::UnknownEncoding)?,
+            "strict" => strict = value.parse().map_err(|_| ParseError::MalformedPair)?,
+            _ => {
+                let warning = format!("Ignoring unknown argument key: {key}");
+                error::warn(CountErrorCode::UnknownKey, &warning).map_err(|_| ParseError::UnknownKey)?;
+            }
+        }
+    }
+    let command = command.ok_or(ParseError::MissingCommand)?;
+    if command != Command::Version && filename.is_none() {
+        return Err(ParseError::MissingFilename);
+    }
+    let args = Arguments { command, filename: ptr::null(), file_mode, word_rule, newline_fix, format, strict, encoding };
+    Ok((args, filename))
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '%' | ',' | '=' | ' ' => escaped.push_str(&format!("%{:02X}", c as u32)),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+    use std::ptr;
+
+    use super::{ffi, Flag, ParseError, Position, COMMANDS};
+    use crate::modules::buffer::StringArray;
+    use crate::modules::error::CountErrorCode;
+    use crate::modules::format::CountFormat;
+    use crate::modules::stats::{TextEncoding, WordRule};
+    use crate::testutil::{ArgvBuilder, Rng};
+    use crate::{Arguments, Command, FileMode};
+
+    /// Serializes through the C export and parses the result back, returning what came out.
+    fn round_trip(args: &Arguments) -> (Arguments, Option<String>) {
+        let serialized = ffi::arguments_serialize(args);
+        assert!(!serialized.is_null());
+        let mut parsed = Arguments { filename: ptr::null(), ..*args };
+        assert_eq!(ffi::arguments_deserialize(serialized, &mut parsed), ParseError::Ok);
+        drop(unsafe { CString::from_raw(serialized) });
+        let filename = (!parsed.filename.is_null()).then(|| {
+            let filename = unsafe { CString::from_raw(parsed.filename as *mut _) };
+            filename.into_string().unwrap()
+        });
+        (Arguments { filename: ptr::null(), ..parsed }, filename)
+    }
+
+    const FILENAMES: [&str; 6] = ["a.md", "with space.md", "100%,=.csv", "ünï cödé.txt", "%41", "="];
+
+    #[test]
+    fn random_argument_sets_survive_a_round_trip() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..500 {
+            let command = rng.pick(&COMMANDS).command;
+            let filename = if command == Command::Version && rng.below(2) == 0 {
+                None
+            } else {
+                Some(CString::new(*rng.pick(&FILENAMES)).unwrap())
+            };
+            let args = Arguments {
+                command,
+                filename: filename.as_ref().map_or(ptr::null(), |filename| filename.as_ptr()),
+                file_mode: rng.pick(&FileMode::NAMES).0,
+                word_rule: rng.pick(&WordRule::NAMES).0,
+                newline_fix: rng.below(2) == 0,
+                format: rng.pick(&CountFormat::NAMES).0,
+                strict: rng.below(2) == 0,
+                encoding: rng.pick(&TextEncoding::NAMES).0,
+            };
+            let (parsed, parsed_filename) = round_trip(&args);
+            let expected = filename.as_deref().map(|filename| filename.to_str().unwrap().to_owned());
+            assert_eq!(parsed_filename, expected);
+            assert_eq!(parsed.command, args.command);
+            assert_eq!(parsed.file_mode, args.file_mode);
+            assert_eq!(parsed.word_rule, args.word_rule);
+            assert_eq!(parsed.newline_fix, args.newline_fix);
+            assert_eq!(parsed.format, args.format);
+            assert_eq!(parsed.strict, args.strict);
+            assert_eq!(parsed.encoding, args.encoding);
+        }
+    }
+
+    fn deserialize(s: &str) -> ParseError {
+        let s = CString::new(s).unwrap();
+        let mut out = std::mem::MaybeUninit::<Arguments>::uninit();
+        let result = ffi::arguments_deserialize(s.as_ptr(), out.as_mut_ptr());
+        if result == ParseError::Ok {
+            let out = unsafe { out.assume_init() };
+            if !out.filename.is_null() {
+                drop(unsafe { CString::from_raw(out.filename as *mut _) });
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn deserialize_reports_malformed_input() {
+        assert_eq!(deserialize("command=version"), ParseError::Ok);
+        assert_eq!(deserialize("command"), ParseError::MalformedPair);
+        assert_eq!(deserialize("command=bytes"), ParseError::MissingFilename);
+        assert_eq!(deserialize("filename=a.md"), ParseError::MissingCommand);
+        assert_eq!(deserialize("command=bytes,filename=%4"), ParseError::InvalidEscape);
+        assert_eq!(deserialize("command=bytes,filename=a,mode=zip"), ParseError::UnknownFileMode);
+        assert_eq!(deserialize("command=bytes,filename=a,strict=yes"), ParseError::MalformedPair);
+        assert_eq!(ffi::arguments_deserialize(ptr::null(), ptr::null_mut()), ParseError::NullPointer);
+        assert!(ffi::arguments_serialize(ptr::null()).is_null());
+    }
+
+    fn complete(arguments: &[&str]) -> Vec<String> {
+        let mut builder = ArgvBuilder::new(arguments);
+        let (argc, argv) = builder.build();
+        let mut out = StringArray { items: ptr::null_mut(), len: 0 };
+        assert_eq!(ffi::complete_args(argc, argv, &mut out), CountErrorCode::Ok as i32);
+        out.take_strings()
+    }
+
+    #[test]
+    fn completes_commands_and_flags() {
+        assert_eq!(complete(&[]), ["version", "bytes", "characters", "words", "check"]);
+        assert_eq!(complete(&["count"]), complete(&[]));
+        assert_eq!(complete(&["count", "by"]), ["bytes"]);
+        assert_eq!(complete(&["count", "bytes", "a"]), Vec::<String>::new());
+        assert_eq!(complete(&["count", "bytes", "a.md", "--csv-"]), ["--csv-list", "--csv-merged"]);
+        assert_eq!(complete(&["count", "bytes", "a.md", "--format=h"]), ["--format=human"]);
+    }
+
+    #[test]
+    fn a_new_table_entry_reaches_usage_completion_and_suggestions() {
+        let usage = super::usage_string();
+        assert!(usage.contains("--test-only=<value>  Exists only in tests. One of: on, off."), "{usage}");
+        assert_eq!(complete(&["count", "bytes", "a.md", "--test"]), ["--test-only=on", "--test-only=off"]);
+        let message = super::with_suggestion("Flag not recognized: --test-onyl=on".to_owned(), "--test-onyl=on", Position::Flag);
+        assert_eq!(message, "Flag not recognized: --test-onyl=on (did you mean `--test-only=on`?)");
+        assert!(matches!(super::parse_flag("--test-only=on"), Some(Flag::Strict)));
+        assert!(super::parse_flag("--test-only=maybe").is_none());
+    }
+
+    fn name_of(name: *const std::os::raw::c_char) -> Option<&'static str> {
+        (!name.is_null()).then(|| unsafe { std::ffi::CStr::from_ptr(name) }.to_str().unwrap())
+    }
+
+    fn from_str(parse: extern "C" fn(*const std::os::raw::c_char) -> i32, name: &str) -> i32 {
+        let name = CString::new(name).unwrap();
+        parse(name.as_ptr())
+    }
+
+    #[test]
+    fn commands_round_trip_through_their_names() {
+        for spec in &COMMANDS {
+            let name = name_of(ffi::command_name(spec.command as i32)).unwrap();
+            assert_eq!(name, spec.name);
+            assert_eq!(from_str(ffi::command_from_str, name), spec.command as i32);
+        }
+        assert_eq!(from_str(ffi::command_from_str, "chars"), Command::Characters as i32);
+        assert_eq!(from_str(ffi::command_from_str, "Bytes"), -1);
+        assert_eq!(ffi::command_from_str(ptr::null()), -1);
+        assert_eq!(name_of(ffi::command_name(-1)), None);
+        assert_eq!(name_of(ffi::command_name(COMMANDS.len() as i32)), None);
+    }
+
+    #[test]
+    fn file_modes_round_trip_through_their_names() {
+        for (mode, name) in FileMode::NAMES {
+            assert_eq!(name_of(ffi::file_mode_name(mode as i32)), Some(name));
+            assert_eq!(from_str(ffi::file_mode_from_str, name), mode as i32);
+        }
+        assert_eq!(from_str(ffi::file_mode_from_str, "csv"), -1);
+        assert_eq!(ffi::file_mode_from_str(ptr::null()), -1);
+        assert_eq!(name_of(ffi::file_mode_name(FileMode::NAMES.len() as i32)), None);
+    }
+}
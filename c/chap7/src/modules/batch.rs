@@ -0,0 +1,145 @@
+mod ffi {
+    use std::ffi::CString;
+    use std::os::raw::c_char;
+    use std::ptr;
+
+    use super::{BatchOptions, FileCount};
+    use crate::modules::error::{self, CountErrorCode};
+
+    #[no_mangle]
+    pub extern "C" fn count_files_batch(
+        csv: *const c_char,
+        out: *mut *mut FileCount,
+        out_len: *mut usize,
+    ) -> i32 {
+        count_files_batch_with_options(csv, ptr::null(), out, out_len)
+    }
+
+    /// Counts every file named in `csv` on up to `options->threads` workers (NULL or 0 picks the
+    /// available parallelism). Results keep the input order, and a file that fails only sets its
+    /// own `status`. Release the array with `file_count_array_free`.
+    #[no_mangle]
+    pub extern "C" fn count_files_batch_with_options(
+        csv: *const c_char,
+        options: *const BatchOptions,
+        out: *mut *mut FileCount,
+        out_len: *mut usize,
+    ) -> i32 {
+        error::status(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            let options = unsafe { options.as_ref() }.copied().unwrap_or_default();
+            if out.is_null() || out_len.is_null() {
+                return Err(CountErrorCode::NullPointer);
+            }
+            let counts: Box<[FileCount]> = super::count_files(csv, &options)?
+                .into_iter()
+                .map(|(filename, result)| {
+                    let (status, chars, bytes) = match result {
+                        Ok((chars, bytes)) => (CountErrorCode::Ok, chars, bytes),
+                        Err(code) => (code, 0, 0),
+                    };
+                    let filename = CString::new(filename).unwrap().into_raw();
+                    FileCount { filename, status, chars, bytes }
+                })
+                .collect();
+            unsafe {
+                out_len.write(counts.len());
+                out.write(Box::into_raw(counts) as *mut FileCount);
+            }
+            Ok(())
+        })
+    }
+
+    #[no_mangle]
+    pub extern "C" fn file_count_array_free(counts: *mut FileCount, len: usize) {
+        if counts.is_null() {
+            return;
+        }
+        let counts = unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(counts, len)) };
+        for count in counts.iter() {
+            if !count.filename.is_null() {
+                drop(unsafe { CString::from_raw(count.filename) });
+            }
+        }
+    }
+}
+
+use crate::modules::error::{self, CountErrorCode};
+use crate::modules::file;
+use crate::modules::limits;
+use std::os::raw::c_char;
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+#[repr(C)]
+pub struct FileCount {
+    pub filename: *mut c_char,
+    pub status: CountErrorCode,
+    pub chars: u64,
+    pub bytes: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct BatchOptions {
+    pub threads: usize,
+}
+
+type CountResult = Result<(u64, u64), CountErrorCode>;
+
+fn count_files(csv: &str, options: &BatchOptions) -> Result<Vec<(String, CountResult)>, CountErrorCode> {
+    limits::check_csv_values(csv, ',')?;
+    let filenames: Vec<&str> = csv.split(",").map(str::trim).collect();
+    let threads = match options.threads {
+        0 => thread::available_parallelism().map_or(1, usize::from),
+        threads => threads,
+    };
+    let threads = threads.min(filenames.len()).max(1);
+
+    let next = AtomicUsize::new(0);
+    let finished: Vec<Vec<(usize, CountResult)>> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        match filenames.get(index) {
+                            Some(filename) => results.push((index, count_file_guarded(filename))),
+                            None => return results,
+                        }
+                    }
+                })
+            })
+            .collect();
+        workers.into_iter().filter_map(|worker| worker.join().ok()).collect()
+    });
+    let mut results: Vec<Option<CountResult>> = vec![None; filenames.len()];
+    for (index, result) in finished.into_iter().flatten() {
+        results[index] = Some(result);
+    }
+    Ok(filenames
+        .iter()
+        .zip(results)
+        .map(|(filename, result)| {
+            // Only a worker that died outside `count_file_guarded` leaves an entry without a result.
+            let result = result.unwrap_or_else(|| worker_failed(filename));
+            (filename.to_string(), result)
+        })
+        .collect())
+}
+
+fn count_file_guarded(filename: &str) -> CountResult {
+    panic::catch_unwind(|| count_file(filename)).unwrap_or_else(|_| worker_failed(filename))
+}
+
+fn worker_failed(filename: &str) -> CountResult {
+    error::fail(CountErrorCode::Io, format!("Counting {filename} failed unexpectedly"))
+}
+
+fn count_file(filename: &str) -> CountResult {
+    let file = file::read_file_checked(filename)?;
+    let text = file.to_str();
+    Ok((text.chars().count() as u64, text.len() as u64))
+}
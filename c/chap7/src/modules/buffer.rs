@@ -0,0 +1,133 @@
+mod ffi {
+    use std::ffi::CString;
+    use std::ptr;
+
+    use super::StringArray;
+
+    /// Frees every string and the array itself, then resets `*array` to empty. NULL is ignored.
+    #[no_mangle]
+    pub extern "C" fn string_array_free(array: *mut StringArray) {
+        let array = match unsafe { array.as_mut() } {
+            Some(array) => array,
+            None => return,
+        };
+        if !array.items.is_null() {
+            let items = unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(array.items, array.len)) };
+            for item in items.iter() {
+                drop(unsafe { CString::from_raw(*item) });
+            }
+        }
+        *array = StringArray { items: ptr::null_mut(), len: 0 };
+    }
+}
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::modules::error::CountErrorCode;
+
+/// A borrowed, read-only string owned by the library. It must not be freed, and `ptr` is not
+/// guaranteed to be NUL-terminated; read exactly `len` bytes.
+#[repr(C)]
+pub struct StrView {
+    pub ptr: *const c_char,
+    pub len: usize,
+}
+
+impl StrView {
+    pub fn new(value: &str) -> StrView {
+        StrView { ptr: value.as_ptr() as *const c_char, len: value.len() }
+    }
+}
+
+/// A library-allocated list of strings, released with `string_array_free`.
+#[repr(C)]
+pub struct StringArray {
+    pub items: *mut *mut c_char,
+    pub len: usize,
+}
+
+impl StringArray {
+    pub fn new(strings: Vec<String>) -> StringArray {
+        let items: Box<[*mut c_char]> =
+            strings.into_iter().map(|string| CString::new(string).unwrap().into_raw()).collect();
+        let len = items.len();
+        StringArray { items: Box::into_raw(items) as *mut *mut c_char, len }
+    }
+
+    /// Copies the strings out and frees the array with `string_array_free`.
+    #[cfg(test)]
+    pub fn take_strings(mut self) -> Vec<String> {
+        let items = unsafe { std::slice::from_raw_parts(self.items, self.len) };
+        let strings = items
+            .iter()
+            .map(|&item| unsafe { std::ffi::CStr::from_ptr(item) }.to_str().unwrap().to_owned())
+            .collect();
+        ffi::string_array_free(&mut self);
+        strings
+    }
+}
+
+// When `buf` is NULL or too small, only `needed` is written, so callers can probe for the size
+// first and call again with a large enough buffer.
+pub unsafe fn copy_to_buffer(
+    value: &str,
+    buf: *mut c_char,
+    buf_len: usize,
+    needed: *mut usize,
+) -> Result<(), CountErrorCode> {
+    let required = value.len() + 1;
+    if !needed.is_null() {
+        needed.write(required);
+    }
+    if buf.is_null() || buf_len < required {
+        return Err(CountErrorCode::BufferTooSmall);
+    }
+    ptr::copy_nonoverlapping(value.as_ptr(), buf as *mut u8, value.len());
+    buf.add(value.len()).write(0);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::raw::c_char;
+    use std::ptr;
+
+    use super::copy_to_buffer;
+    use crate::modules::error::CountErrorCode;
+
+    /// Copies "abc" into a `buf_len`-byte buffer, returning the result, what was reported as
+    /// needed and the buffer afterwards.
+    fn copy(buf_len: usize) -> (Result<(), CountErrorCode>, usize, Vec<u8>) {
+        let mut buf = vec![b'x'; buf_len];
+        let mut needed = 0;
+        let result = unsafe { copy_to_buffer("abc", buf.as_mut_ptr() as *mut c_char, buf_len, &mut needed) };
+        (result, needed, buf)
+    }
+
+    #[test]
+    fn an_exact_fit_copies_the_terminator() {
+        assert_eq!(copy(4), (Ok(()), 4, b"abc\0".to_vec()));
+        assert_eq!(copy(6), (Ok(()), 4, b"abc\0xx".to_vec()));
+    }
+
+    #[test]
+    fn one_byte_short_writes_nothing() {
+        assert_eq!(copy(3), (Err(CountErrorCode::BufferTooSmall), 4, b"xxx".to_vec()));
+    }
+
+    #[test]
+    fn a_zero_length_buffer_only_reports_the_size() {
+        assert_eq!(copy(0), (Err(CountErrorCode::BufferTooSmall), 4, vec![]));
+    }
+
+    #[test]
+    fn a_null_probe_reports_the_size() {
+        let mut needed = 0;
+        let result = unsafe { copy_to_buffer("abc", ptr::null_mut(), 100, &mut needed) };
+        assert_eq!((result, needed), (Err(CountErrorCode::BufferTooSmall), 4));
+        let mut buf = [0 as c_char; 4];
+        assert_eq!(unsafe { copy_to_buffer("abc", buf.as_mut_ptr(), 4, ptr::null_mut()) }, Ok(()));
+    }
+}
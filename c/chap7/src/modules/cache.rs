@@ -0,0 +1,199 @@
+mod ffi {
+    use crate::modules::error::{self, CountErrorCode};
+
+    /// Turns on memoization of `count_all` results, keeping the `max_entries` most recently used
+    /// inputs. 0 disables the cache again. Re-enabling keeps existing entries that still fit.
+    #[no_mangle]
+    pub extern "C" fn count_cache_enable(max_entries: usize) {
+        let mut cache = super::CACHE.lock().unwrap();
+        if max_entries == 0 {
+            *cache = None;
+            return;
+        }
+        let cache = cache.get_or_insert_with(|| super::Cache { max_entries, entries: Vec::new(), hits: 0 });
+        cache.max_entries = max_entries;
+        cache.evict();
+    }
+
+    /// Drops every cached result; the cache stays enabled with the same bound.
+    #[no_mangle]
+    pub extern "C" fn count_cache_clear() {
+        if let Some(cache) = super::CACHE.lock().unwrap().as_mut() {
+            cache.entries.clear();
+        }
+    }
+
+    /// Writes how many results the cache holds and how many `count_all` calls it has answered
+    /// since it was last enabled from disabled. Both are 0 while it is disabled.
+    #[no_mangle]
+    pub extern "C" fn count_cache_stats(out_entries: *mut usize, out_hits: *mut u64) -> i32 {
+        error::status(|| {
+            if out_entries.is_null() || out_hits.is_null() {
+                return Err(CountErrorCode::NullPointer);
+            }
+            let (entries, hits) = super::CACHE.lock().unwrap().as_ref().map_or((0, 0), |cache| (cache.entries.len(), cache.hits));
+            unsafe { error::write_out(out_entries, entries) }?;
+            unsafe { error::write_out(out_hits, hits) }
+        })
+    }
+}
+
+use crate::modules::error::CountErrorCode;
+use crate::modules::stats::{CountOptions, TextEncoding, TextStats, WordRule};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::sync::Mutex;
+
+/// Inputs up to this size are kept in full and compared byte for byte on a hit. Longer inputs
+/// are identified by length plus two unrelated 64-bit hashes (FNV-1a and SipHash), which keeps
+/// the memory per entry fixed; a false hit needs both hashes to collide at the same length.
+const SHORT_INPUT_BYTES: usize = 4096;
+
+/// `None` until `count_cache_enable`, so callers who never opt in pay for a lock and nothing else.
+static CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+
+struct Cache {
+    max_entries: usize,
+    /// Least recently used first. Bounds are meant to be small, so a linear scan beats the
+    /// bookkeeping of a linked map.
+    entries: Vec<Entry>,
+    hits: u64,
+}
+
+struct Entry {
+    key: Key,
+    stats: TextStats,
+}
+
+#[derive(PartialEq)]
+struct Key {
+    word_rule: WordRule,
+    encoding: TextEncoding,
+    len: usize,
+    fnv: u64,
+    sip: u64,
+    short: Option<Vec<u8>>,
+}
+
+impl Key {
+    fn new(bytes: &[u8], options: &CountOptions) -> Key {
+        let mut sip = DefaultHasher::new();
+        sip.write(bytes);
+        Key {
+            word_rule: options.word_rule,
+            encoding: options.encoding,
+            len: bytes.len(),
+            fnv: fnv1a(bytes),
+            sip: sip.finish(),
+            short: (bytes.len() <= SHORT_INPUT_BYTES).then(|| bytes.to_vec()),
+        }
+    }
+}
+
+impl Cache {
+    fn get(&mut self, key: &Key) -> Option<TextStats> {
+        let index = self.entries.iter().position(|entry| entry.key == *key)?;
+        let entry = self.entries.remove(index);
+        let stats = entry.stats;
+        self.entries.push(entry);
+        self.hits += 1;
+        Some(stats)
+    }
+
+    fn insert(&mut self, key: Key, stats: TextStats) {
+        // Two threads that missed on the same input both come back with its stats.
+        self.entries.retain(|entry| entry.key != key);
+        self.entries.push(Entry { key, stats });
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        let excess = self.entries.len().saturating_sub(self.max_entries);
+        self.entries.drain(..excess);
+    }
+}
+
+/// Returns the cached stats for `bytes` counted with `options`, or runs `count` and remembers a
+/// successful result. Without an enabled cache this is just `count()`.
+pub fn count_cached(
+    bytes: &[u8],
+    options: &CountOptions,
+    count: impl FnOnce() -> Result<TextStats, CountErrorCode>,
+) -> Result<TextStats, CountErrorCode> {
+    if CACHE.lock().unwrap().is_none() {
+        return count();
+    }
+    let key = Key::new(bytes, options);
+    if let Some(stats) = CACHE.lock().unwrap().as_mut().and_then(|cache| cache.get(&key)) {
+        return Ok(stats);
+    }
+    // Counted without the lock held so concurrent misses don't serialize on the scan.
+    let stats = count()?;
+    if let Some(cache) = CACHE.lock().unwrap().as_mut() {
+        cache.insert(key, stats);
+    }
+    Ok(stats)
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cache, Key, SHORT_INPUT_BYTES};
+    use crate::modules::stats::{CountOptions, TextStats, WordRule};
+
+    fn key(text: &str) -> Key {
+        Key::new(text.as_bytes(), &CountOptions::default())
+    }
+
+    fn stats(bytes: u64) -> TextStats {
+        TextStats { bytes, ..TextStats::default() }
+    }
+
+    #[test]
+    fn hits_return_what_was_inserted_and_misses_return_none() {
+        let mut cache = Cache { max_entries: 2, entries: Vec::new(), hits: 0 };
+        assert_eq!(cache.get(&key("a")), None);
+        cache.insert(key("a"), stats(1));
+        assert_eq!(cache.get(&key("a")), Some(stats(1)));
+        assert_eq!(cache.get(&key("b")), None);
+        // Reinserting replaces rather than duplicates.
+        cache.insert(key("a"), stats(2));
+        assert_eq!((cache.entries.len(), cache.get(&key("a"))), (1, Some(stats(2))));
+    }
+
+    #[test]
+    fn the_least_recently_used_entry_is_evicted() {
+        let mut cache = Cache { max_entries: 2, entries: Vec::new(), hits: 0 };
+        cache.insert(key("a"), stats(1));
+        cache.insert(key("b"), stats(2));
+        // Touching "a" leaves "b" as the oldest.
+        cache.get(&key("a"));
+        cache.insert(key("c"), stats(3));
+        assert_eq!(cache.get(&key("b")), None);
+        assert_eq!(cache.get(&key("a")), Some(stats(1)));
+        assert_eq!(cache.get(&key("c")), Some(stats(3)));
+
+        cache.max_entries = 1;
+        cache.evict();
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.get(&key("c")), Some(stats(3)));
+    }
+
+    #[test]
+    fn keys_compare_text_and_options() {
+        assert!(key("same") == key("same"));
+        assert!(key("same") != key("Same"));
+        let unicode = CountOptions { word_rule: WordRule::UnicodeWords, ..CountOptions::default() };
+        assert!(key("same") != Key::new(b"same", &unicode));
+
+        let long = "x".repeat(SHORT_INPUT_BYTES + 1);
+        let mut other = long.clone().into_bytes();
+        other[0] = b'y';
+        assert!(key(&long).short.is_none());
+        assert!(key(&long) == key(&long));
+        assert!(key(&long) != Key::new(&other, &CountOptions::default()));
+    }
+}
@@ -1,50 +1,1856 @@
 mod ffi {
-    use std::ffi::{c_void, CStr, CString};
+    use std::ffi::{c_void, CString};
     use std::os::raw::c_char;
+    use std::path::Path;
+    use std::{ptr, slice};
+
+    use super::{MergeOptions, MergeSpan, MergeStream};
+    use crate::api;
+    use crate::modules::buffer;
+    use crate::Command;
+    use crate::modules::error::{self, CountErrorCode};
+    use crate::modules::stats::{self, CountOptions, TextStats};
 
+    /// Returns the number of values visited, or a negated `CountErrorCode` for NULL or non-UTF-8
+    /// `csv` or one over the value limit, in which case `c_callback` is never called.
     #[no_mangle]
     pub extern "C" fn csv_for_each_value(
         csv: *const c_char,
         c_callback: unsafe extern "C" fn(*const c_char, *const c_void),
         context: *const c_void,
-    ) {
-        let csv = unsafe { CStr::from_ptr(csv) }.to_str().unwrap();
-        super::for_each_value(csv, |value| {
-            let value = CString::new(value).unwrap();
-            unsafe { c_callback(value.as_ptr(), context) };
-        });
+    ) -> i64 {
+        error::count_or_code(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            super::for_each_value(csv, |value| {
+                let value = CString::new(value).unwrap();
+                unsafe { c_callback(value.as_ptr(), context) };
+                true
+            })
+        })
+    }
+
+    /// Like `csv_for_each_value`, but stops after the first value `c_callback` returns false for.
+    /// The count includes that value.
+    #[no_mangle]
+    pub extern "C" fn csv_for_each_value_until(
+        csv: *const c_char,
+        c_callback: unsafe extern "C" fn(*const c_char, *const c_void) -> bool,
+        context: *const c_void,
+    ) -> i64 {
+        error::count_or_code(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            super::for_each_value(csv, |value| {
+                let value = CString::new(value).unwrap();
+                unsafe { c_callback(value.as_ptr(), context) }
+            })
+        })
+    }
+
+    /// `stats` is only valid for the duration of each callback. Returns what `csv_for_each_value`
+    /// returns.
+    #[no_mangle]
+    pub extern "C" fn csv_for_each_value_stats(
+        csv: *const c_char,
+        callback: unsafe extern "C" fn(value: *const c_char, stats: *const TextStats, context: *mut c_void),
+        context: *mut c_void,
+    ) -> i64 {
+        error::count_or_code(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            super::for_each_value(csv, |value| {
+                let stats = stats::count_all(value, &CountOptions::default());
+                let value = CString::new(value).unwrap();
+                unsafe { callback(value.as_ptr(), &stats, context) };
+                true
+            })
+        })
     }
 
+    /// Takes ownership of `csv` and releases it with `free_csv`. Returns NULL on failure, including
+    /// NULL or non-UTF-8 `csv`, with the cause in `last_error_code`.
     #[no_mangle]
     pub extern "C" fn csv_merge_files(
         csv: *mut c_char,
         free_csv: unsafe extern "C" fn(*mut c_char),
     ) -> *mut c_char {
-        let csv_str = unsafe { CStr::from_ptr(csv) }.to_str().unwrap();
-        let merged = super::merge_files(&csv_str);
-        unsafe { free_csv(csv); }
-        CString::new(merged).unwrap().into_raw()
+        merge_and_free(csv, free_csv, |csv| api::merge_files(csv).map_err(|error| error.code))
+    }
+
+    /// Takes ownership of `csv` as `csv_merge_files` does.
+    #[no_mangle]
+    pub extern "C" fn csv_merge_files_dedup_lines(
+        csv: *mut c_char,
+        free_csv: unsafe extern "C" fn(*mut c_char),
+    ) -> *mut c_char {
+        merge_and_free(csv, free_csv, |csv| super::merge_files_dedup_lines(csv).map(|(merged, _)| merged))
+    }
+
+    /// Deduplicated merge as in `csv_merge_files_dedup_lines`, plus how many bytes it saved over
+    /// plain concatenation. Free `*out_merged` with `csv_free_merged_file`.
+    #[no_mangle]
+    pub extern "C" fn csv_merge_dedup_stats(
+        csv: *const c_char,
+        out_merged: *mut *mut c_char,
+        out_bytes_saved: *mut u64,
+    ) -> i32 {
+        error::status(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            if out_merged.is_null() || out_bytes_saved.is_null() {
+                return Err(CountErrorCode::NullPointer);
+            }
+            let (merged, concatenated_len) = super::merge_files_dedup_lines(csv)?;
+            unsafe {
+                out_bytes_saved.write((concatenated_len - merged.len()) as u64);
+                out_merged.write(CString::new(merged).unwrap().into_raw());
+            }
+            Ok(())
+        })
+    }
+
+    #[no_mangle]
+    pub extern "C" fn csv_merge_files_buf(
+        csv: *const c_char,
+        buf: *mut c_char,
+        buf_len: usize,
+        needed: *mut usize,
+    ) -> i32 {
+        error::status(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            let merged = super::merge_files(csv)?;
+            unsafe { buffer::copy_to_buffer(&merged, buf, buf_len, needed) }
+        })
+    }
+
+    /// `options` may be NULL for the defaults. On success `*merged` receives a string freed with
+    /// `csv_free_merged_file`. With `options->with_manifest` set, the spans of the merge are kept
+    /// for `csv_merge_take_manifest` on this thread; any other call discards earlier spans.
+    #[no_mangle]
+    pub extern "C" fn csv_merge_files_checked(
+        csv: *const c_char,
+        options: *const MergeOptions,
+        merged: *mut *mut c_char,
+    ) -> i32 {
+        error::status(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            let options = unsafe { options.as_ref() }.copied().unwrap_or_default();
+            if merged.is_null() {
+                return Err(CountErrorCode::NullPointer);
+            }
+            super::LAST_MANIFEST.with(|manifest| manifest.borrow_mut().clear());
+            let (result, spans) = super::merge_files_with_options(csv, &options)?;
+            if options.with_manifest {
+                super::LAST_MANIFEST.with(|manifest| *manifest.borrow_mut() = spans);
+            }
+            unsafe { error::write_out(merged, CString::new(result).unwrap().into_raw()) }
+        })
+    }
+
+    /// Runs `metric` over each file named in `csv`, in list order, and reports it with the
+    /// filename. Files are read through the content root. The first file that cannot be read
+    /// stops the run with its error code, after the files before it have been reported.
+    #[no_mangle]
+    pub extern "C" fn csv_count_files(
+        csv: *const c_char,
+        metric: Command,
+        cb: unsafe extern "C" fn(filename: *const c_char, count: u64, context: *const c_void),
+        context: *const c_void,
+    ) -> i32 {
+        error::status(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            super::count_files(csv, metric, |filename, count| {
+                let filename = CString::new(filename).unwrap();
+                error::guard_callback(|| unsafe { cb(filename.as_ptr(), count, context) })
+            })
+        })
+    }
+
+    /// Calls `cb` for each file named in `csv`, in order, with whether it exists as a regular file
+    /// under the content root; only metadata is read. Files outside the root count as missing.
+    /// Without a root, each value is checked as a path of its own, and the built-in fixtures
+    /// exist too. Returns the number of missing files, or a negated `CountErrorCode` when `csv`
+    /// is invalid or `cb` panics.
+    #[no_mangle]
+    pub extern "C" fn csv_validate_manifest(
+        csv: *const c_char,
+        cb: unsafe extern "C" fn(filename: *const c_char, exists: bool, context: *const c_void),
+        context: *const c_void,
+    ) -> i32 {
+        let mut missing = 0;
+        let status = error::status(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            missing = super::validate_manifest(csv, |filename, exists| {
+                let filename = CString::new(filename).unwrap();
+                error::guard_callback(|| unsafe { cb(filename.as_ptr(), exists, context) })
+            })?;
+            Ok(())
+        });
+        if status == CountErrorCode::Ok as i32 {
+            i32::try_from(missing).unwrap_or(i32::MAX)
+        } else {
+            -status
+        }
+    }
+
+    /// Hands over the spans recorded by the last `csv_merge_files_checked` on this thread, one per
+    /// merged file in merge order, and forgets them. Without a recorded manifest the array is
+    /// empty. Release it with `merge_span_array_free`.
+    #[no_mangle]
+    pub extern "C" fn csv_merge_take_manifest(out: *mut *mut MergeSpan, out_len: *mut usize) -> i32 {
+        error::status(|| {
+            if out.is_null() || out_len.is_null() {
+                return Err(CountErrorCode::NullPointer);
+            }
+            let spans: Box<[MergeSpan]> = super::LAST_MANIFEST
+                .with(|manifest| std::mem::take(&mut *manifest.borrow_mut()))
+                .into_iter()
+                .map(|span| MergeSpan {
+                    filename: CString::new(span.filename).unwrap().into_raw(),
+                    start: span.start as u64,
+                    len: span.len as u64,
+                })
+                .collect();
+            unsafe {
+                out_len.write(spans.len());
+                out.write(Box::into_raw(spans) as *mut MergeSpan);
+            }
+            Ok(())
+        })
+    }
+
+    #[no_mangle]
+    pub extern "C" fn merge_span_array_free(spans: *mut MergeSpan, len: usize) {
+        if spans.is_null() {
+            return;
+        }
+        let spans = unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(spans, len)) };
+        for span in spans.iter() {
+            if !span.filename.is_null() {
+                drop(unsafe { CString::from_raw(span.filename) });
+            }
+        }
     }
 
     #[no_mangle]
     pub extern "C" fn csv_free_merged_file(merged: *mut c_char) {
-        unsafe { CString::from_raw(merged) };
+        if !merged.is_null() {
+            drop(unsafe { CString::from_raw(merged) });
+        }
+    }
+
+    /// Returns NULL for NULL or non-UTF-8 `csv` or a non-ASCII `delimiter`.
+    #[no_mangle]
+    pub extern "C" fn csv_common_prefix(csv: *const c_char, delimiter: c_char) -> *mut c_char {
+        let mut prefix = ptr::null_mut();
+        error::status(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            let delimiter = super::ascii_char(delimiter, "delimiter")?;
+            prefix = CString::new(super::common_prefix(csv, delimiter)).unwrap().into_raw();
+            Ok(())
+        });
+        prefix
+    }
+
+    /// Returns 1 when the last field of the first line of `csv` is empty after trimming, as in
+    /// `"a,b,"`, 0 when it is not, and -1 for NULL or non-UTF-8 input or a non-ASCII `delimiter`.
+    /// Quotes are not interpreted.
+    #[no_mangle]
+    pub extern "C" fn csv_has_trailing_empty(csv: *const c_char, delimiter: c_char) -> i32 {
+        let csv = unsafe { error::str_from_ptr(csv) };
+        match csv.and_then(|csv| Ok(super::has_trailing_empty(csv, super::ascii_char(delimiter, "delimiter")?))) {
+            Ok(trailing_empty) => trailing_empty as i32,
+            Err(_) => -1,
+        }
+    }
+
+    /// Returns 1 when both CSVs hold the same trimmed values with the same multiplicities, in any
+    /// order, 0 otherwise, and a negated `CountErrorCode` when either is NULL or not UTF-8 or
+    /// `delimiter` is not ASCII.
+    #[no_mangle]
+    pub extern "C" fn csv_equal_unordered(a: *const c_char, b: *const c_char, delimiter: c_char) -> i32 {
+        let mut equal = false;
+        let code = error::status(|| {
+            let (a, b) = unsafe { (error::str_from_ptr(a)?, error::str_from_ptr(b)?) };
+            equal = super::equal_unordered(a, b, super::ascii_char(delimiter, "delimiter")?);
+            Ok(())
+        });
+        if code == CountErrorCode::Ok as i32 {
+            equal as i32
+        } else {
+            -code
+        }
+    }
+
+    /// Calls `callback` for every value that repeats an earlier one, with the zero-based positions
+    /// of the first occurrence and the repeat. Returns the number of repeats, or a negated
+    /// `CountErrorCode` for NULL or non-UTF-8 `csv` or one over the value limit.
+    #[no_mangle]
+    pub extern "C" fn csv_find_duplicates(
+        csv: *const c_char,
+        callback: unsafe extern "C" fn(
+            value: *const c_char,
+            first_index: usize,
+            duplicate_index: usize,
+            context: *mut c_void,
+        ),
+        context: *mut c_void,
+    ) -> i64 {
+        error::count_or_code(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            super::find_duplicates(csv, |value, first_index, duplicate_index| {
+                let value = CString::new(value).unwrap();
+                unsafe { callback(value.as_ptr(), first_index, duplicate_index, context) };
+            })
+        })
+    }
+
+    /// Picks whichever of `,` `;` `\t` `|` occurs on the first line and splits every non-empty line
+    /// into the same number of fields, preferring the most fields. Fails with
+    /// `CountErrorCode_InvalidArgument` when no candidate is consistent.
+    #[no_mangle]
+    pub extern "C" fn detect_delimiter(text: *const c_char, out: *mut c_char) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            let delimiter = super::detect_delimiter(text)?;
+            unsafe { error::write_out(out, delimiter as u8 as c_char) }
+        })
+    }
+
+    /// Splits with quoting: a field that starts with `quote` runs to the matching closing `quote`
+    /// and may contain the delimiter, and a doubled `quote` inside it stands for one quote
+    /// character. Unquoted fields are trimmed. An unterminated quote, text after a closing quote,
+    /// or a `delimiter` or `quote` outside ASCII fails with `CountErrorCode_InvalidArgument`.
+    #[no_mangle]
+    pub extern "C" fn csv_for_each_field_quoted(
+        csv: *const c_char,
+        delimiter: c_char,
+        quote: c_char,
+        callback: unsafe extern "C" fn(value: *const c_char, context: *mut c_void),
+        context: *mut c_void,
+    ) -> i32 {
+        error::status(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            for field in super::split_quoted(csv, super::ascii_char(delimiter, "delimiter")?, super::ascii_char(quote, "quote")?)? {
+                let field = CString::new(field).unwrap();
+                error::guard_callback(|| unsafe { callback(field.as_ptr(), context) })?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Field count under the same rules as `csv_for_each_field_quoted`.
+    #[no_mangle]
+    pub extern "C" fn csv_count_fields_quoted(
+        csv: *const c_char,
+        delimiter: c_char,
+        quote: c_char,
+        out: *mut u64,
+    ) -> i32 {
+        error::status(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            let fields = super::split_quoted(csv, super::ascii_char(delimiter, "delimiter")?, super::ascii_char(quote, "quote")?)?;
+            unsafe { error::write_out(out, fields.len() as u64) }
+        })
+    }
+
+    /// Bytes spent on double-quote syntax: the quotes around quoted fields plus one byte per
+    /// doubled `""` escape. Parsing follows `csv_for_each_field_quoted` with `"` as the quote;
+    /// quotes inside unquoted fields are content, not overhead.
+    #[no_mangle]
+    pub extern "C" fn csv_quoting_overhead(csv: *const c_char, delimiter: c_char, out: *mut u64) -> i32 {
+        error::status(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            let overhead = super::quoting_overhead(csv, super::ascii_char(delimiter, "delimiter")?)?;
+            unsafe { error::write_out(out, overhead) }
+        })
+    }
+
+    /// Treats the first non-empty line as a header: `out_columns` gets its field count, parsed
+    /// like `csv_for_each_field_quoted` with `"` as the quote, and `out_data_rows` the number of
+    /// non-empty lines after it. Empty input has no header and reports 0 for both.
+    #[no_mangle]
+    pub extern "C" fn csv_header_and_row_count(
+        csv: *const c_char,
+        delimiter: c_char,
+        out_columns: *mut u64,
+        out_data_rows: *mut u64,
+    ) -> i32 {
+        error::status(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            let (columns, data_rows) = super::header_and_row_count(csv, super::ascii_char(delimiter, "delimiter")?)?;
+            unsafe { error::write_out(out_columns, columns) }?;
+            unsafe { error::write_out(out_data_rows, data_rows) }
+        })
+    }
+
+    /// Number of `delimiter`-separated values and the width in characters of the widest one after
+    /// trimming. Quotes are not interpreted.
+    #[no_mangle]
+    pub extern "C" fn csv_max_field_width(
+        csv: *const c_char,
+        delimiter: c_char,
+        out_count: *mut u64,
+        out_max_width: *mut u64,
+    ) -> i32 {
+        error::status(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            let (count, max_width) = super::max_field_width(csv, super::ascii_char(delimiter, "delimiter")?)?;
+            unsafe { error::write_out(out_count, count) }?;
+            unsafe { error::write_out(out_max_width, max_width) }
+        })
+    }
+
+    /// Trimmed `delimiter`-separated values of `csv` that are not among the comma-separated,
+    /// trimmed `stop_values`. An empty entry in `stop_values`, as in `"NA,,null"`, excludes empty
+    /// fields.
+    #[no_mangle]
+    pub extern "C" fn csv_count_excluding(
+        csv: *const c_char,
+        delimiter: c_char,
+        stop_values: *const c_char,
+        out: *mut u64,
+    ) -> i32 {
+        error::status(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            let stop_values = unsafe { error::str_from_ptr(stop_values) }?;
+            let count = super::count_excluding(csv, super::ascii_char(delimiter, "delimiter")?, stop_values)?;
+            unsafe { error::write_out(out, count) }
+        })
+    }
+
+    /// Keeps the named columns of a comma-separated, double-quoted table, in the requested order
+    /// and starting with the header row. Values are re-quoted where needed. A column missing from
+    /// the header returns NULL with the name in `last_error_message`. A NULL `columns` with a
+    /// nonzero `n_columns` returns NULL with `CountErrorCode_NullPointer` in `last_error_code`.
+    /// Free the result with `count_string_free`.
+    #[no_mangle]
+    pub extern "C" fn csv_select_columns(
+        csv: *const c_char,
+        columns: *const *const c_char,
+        n_columns: usize,
+    ) -> *mut c_char {
+        let selected = (|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            if columns.is_null() && n_columns > 0 {
+                return error::fail(CountErrorCode::NullPointer, "columns is NULL but n_columns is not 0.");
+            }
+            let columns = unsafe { crate::arguments_from_ptr(n_columns, columns) };
+            let columns = columns
+                .iter()
+                .map(|&column| unsafe { error::str_from_ptr(column) })
+                .collect::<Result<Vec<_>, CountErrorCode>>()?;
+            super::select_columns(csv, &columns)
+        })();
+        match selected {
+            Ok(selected) => CString::new(selected).unwrap().into_raw(),
+            Err(_) => ptr::null_mut(),
+        }
+    }
+
+    /// Lays out each non-empty line of `csv` as a row of columns padded to the widest value in
+    /// characters, two spaces apart, with no trailing whitespace. Values are parsed like
+    /// `csv_for_each_field_quoted` with `"` as the quote, so quoted values are shown without
+    /// their quotes. Short rows are padded with empty cells. Returns NULL on invalid input; free
+    /// the result with `count_string_free`.
+    #[no_mangle]
+    pub extern "C" fn csv_align(csv: *const c_char, delimiter: c_char) -> *mut c_char {
+        let aligned = unsafe { error::str_from_ptr(csv) }
+            .and_then(|csv| super::align(csv, super::ascii_char(delimiter, "delimiter")?));
+        match aligned {
+            Ok(aligned) => CString::new(aligned).unwrap().into_raw(),
+            Err(_) => ptr::null_mut(),
+        }
+    }
+
+    /// Reads the file at `path` one line at a time and calls `record_cb` for each non-empty line
+    /// with its fields, parsed like `csv_for_each_field_quoted` with `"` as the quote. The field
+    /// strings are only valid during the call. A trailing `\r` is dropped, so CRLF files work.
+    /// Quoted values may not span lines, and only one line is held in memory at a time.
+    #[no_mangle]
+    pub extern "C" fn csv_stream_from_path(
+        path: *const c_char,
+        delimiter: c_char,
+        record_cb: unsafe extern "C" fn(fields: *const *const c_char, field_count: usize, context: *const c_void),
+        context: *const c_void,
+    ) -> i32 {
+        error::status(|| {
+            let path = unsafe { error::str_from_ptr(path) }?;
+            super::stream_records(Path::new(path), super::ascii_char(delimiter, "delimiter")?, |fields| {
+                let fields = fields.iter().map(|field| CString::new(field.as_str())).collect::<Result<Vec<_>, _>>();
+                let fields = match fields {
+                    Ok(fields) => fields,
+                    Err(_) => return error::fail(CountErrorCode::InvalidArgument, "A field contains a NUL byte."),
+                };
+                let pointers: Vec<*const c_char> = fields.iter().map(|field| field.as_ptr()).collect();
+                error::guard_callback(|| unsafe { record_cb(pointers.as_ptr(), pointers.len(), context) })
+            })
+        })
+    }
+
+    #[no_mangle]
+    pub extern "C" fn merge_stream_new(csv: *const c_char) -> *mut MergeStream {
+        match unsafe { error::str_from_ptr(csv) } {
+            Ok(csv) => match MergeStream::new(csv) {
+                Ok(stream) => Box::into_raw(Box::new(stream)),
+                Err(_) => ptr::null_mut(),
+            },
+            Err(_) => ptr::null_mut(),
+        }
+    }
+
+    /// Returns 0 after writing the next bytes into `buf`, 1 once the merged output is exhausted
+    /// (with nothing written), or a negated `CountErrorCode` when a file fails to load. Bytes
+    /// delivered before an error are a partial merge; the error is reported by the call following
+    /// the last successful one and repeats on every later call.
+    #[no_mangle]
+    pub extern "C" fn merge_stream_next(
+        stream: *mut MergeStream,
+        buf: *mut u8,
+        buf_len: usize,
+        written: *mut usize,
+    ) -> i32 {
+        let stream = match unsafe { stream.as_mut() } {
+            Some(stream) => stream,
+            None => return -(CountErrorCode::NullPointer as i32),
+        };
+        if buf.is_null() || written.is_null() {
+            return -(CountErrorCode::NullPointer as i32);
+        }
+        let buf = unsafe { slice::from_raw_parts_mut(buf, buf_len) };
+        let result = stream.next_chunk(buf);
+        unsafe { written.write(*result.as_ref().unwrap_or(&0)) };
+        match result {
+            Ok(0) if buf_len > 0 => 1,
+            Ok(_) => 0,
+            Err(code) => -(code as i32),
+        }
+    }
+
+    #[no_mangle]
+    pub extern "C" fn merge_stream_free(stream: *mut MergeStream) {
+        if !stream.is_null() {
+            drop(unsafe { Box::from_raw(stream) });
+        }
+    }
+
+    /// `csv` is released before the merge result is converted, whether or not it was valid.
+    fn merge_and_free(
+        csv: *mut c_char,
+        free_csv: unsafe extern "C" fn(*mut c_char),
+        merge: impl FnOnce(&str) -> Result<String, CountErrorCode>,
+    ) -> *mut c_char {
+        let mut merged = ptr::null_mut();
+        error::status(|| {
+            let result = unsafe { error::str_from_ptr(csv) }.and_then(merge);
+            if !csv.is_null() {
+                unsafe { free_csv(csv) };
+            }
+            merged = CString::new(result?).unwrap().into_raw();
+            Ok(())
+        });
+        merged
     }
 }
 
+use crate::api;
+use crate::modules::error::{self, CountErrorCode};
 use crate::modules::file;
+use crate::modules::limits;
+use crate::modules::text;
+use crate::Command;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{c_void, CString};
+use std::os::raw::c_char;
+use std::path::Path;
+use std::ptr;
 
-fn for_each_value(csv: &str, callback: impl Fn(&str)) {
-    for value in csv.split(",") {
-        callback(value.trim());
+/// A `delimiter` or `quote` byte from C as a character. Bytes from 0x80 up are rejected rather
+/// than read as Latin-1, since the character they would stand for takes two bytes in UTF-8.
+fn ascii_char(byte: c_char, name: &str) -> Result<char, CountErrorCode> {
+    let byte = byte as u8;
+    if !byte.is_ascii() {
+        return error::fail(CountErrorCode::InvalidArgument, format!("The {name} must be ASCII, not 0x{byte:02X}."));
+    }
+    Ok(byte as char)
+}
+
+/// Values are only delivered when the list is within the configured value limit, and stop after
+/// the first one `callback` returns false for. Returns the number delivered.
+fn for_each_value(csv: &str, mut callback: impl FnMut(&str) -> bool) -> Result<u64, CountErrorCode> {
+    limits::check_csv_values(csv, ',')?;
+    let mut delivered = 0;
+    for value in api::csv_values(csv) {
+        delivered += 1;
+        if !callback(value) {
+            break;
+        }
+    }
+    Ok(delivered)
+}
+
+pub fn pairs(csv: &str) -> impl Iterator<Item = Result<(&str, &str), &str>> {
+    csv.split(",").map(str::trim).filter(|value| !value.is_empty()).map(|value| {
+        value.split_once('=').map(|(key, value)| (key.trim(), value.trim())).ok_or(value)
+    })
+}
+
+const DEFAULT_MAX_DEPTH: u32 = 16;
+
+/// With `recursive` set, list entries ending in `.csv` are read as nested lists and merged in
+/// place. A `max_depth` of 0 selects the default limit.
+///
+/// A nonzero `max_file_bytes` caps how much any one file contributes: larger files fail with
+/// `CountErrorCode_FileTooLarge`, or are cut at the last whole character within the cap when
+/// `truncate_oversized` is set.
+///
+/// `ensure_trailing_newline` appends `\n` to each non-empty file that does not already end with
+/// one, so files never run together.
+///
+/// `with_manifest` records where each file landed in the merged text; see
+/// `csv_merge_take_manifest`.
+///
+/// `transform`, when set, sees each file before it is merged, together with `transform_context`.
+/// `content` holds `content_len` bytes followed by a NUL. Returning NULL keeps the content;
+/// anything else replaces it and must be a NUL-terminated UTF-8 string allocated by the library
+/// (`count_string_dup` or any result freed with `count_string_free`), which the merge then frees.
+/// Size caps, limits and the manifest all apply to the replaced content.
+///
+/// A nonzero `timeout_ms` fails the merge with `CountErrorCode_TimedOut` once it passes. It is
+/// checked before each list or file is read, so one slow read can still overrun it.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct MergeOptions {
+    pub recursive: bool,
+    pub max_depth: u32,
+    pub max_file_bytes: u64,
+    pub truncate_oversized: bool,
+    pub ensure_trailing_newline: bool,
+    pub with_manifest: bool,
+    pub transform: Option<
+        unsafe extern "C" fn(
+            filename: *const c_char,
+            content: *const c_char,
+            content_len: usize,
+            context: *mut c_void,
+        ) -> *mut c_char,
+    >,
+    pub transform_context: *mut c_void,
+    pub timeout_ms: u64,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            recursive: false,
+            max_depth: 0,
+            max_file_bytes: 0,
+            truncate_oversized: false,
+            ensure_trailing_newline: false,
+            with_manifest: false,
+            transform: None,
+            transform_context: ptr::null_mut(),
+            timeout_ms: 0,
+        }
+    }
+}
+
+/// The bytes `merged[start..start + len]` came from `filename`, after any truncation. Inserted
+/// newlines fall between spans, never inside one. Nested lists contribute their leaf files.
+#[repr(C)]
+pub struct MergeSpan {
+    pub filename: *mut c_char,
+    pub start: u64,
+    pub len: u64,
+}
+
+/// Loads a file, passing its size to the check before reading it and the bytes read so far while
+/// reading, as `file::read_file_checked_within` does.
+pub type FileReader = dyn Fn(&str, &file::SizeCheck) -> Result<file::File, CountErrorCode>;
+
+pub struct Span {
+    filename: String,
+    start: usize,
+    len: usize,
+}
+
+thread_local! {
+    static LAST_MANIFEST: RefCell<Vec<Span>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn merge_files(csv: &str) -> Result<String, CountErrorCode> {
+    merge_files_with_options(csv, &MergeOptions::default()).map(|(merged, _)| merged)
+}
+
+pub fn merge_files_with_options(csv: &str, options: &MergeOptions) -> Result<(String, Vec<Span>), CountErrorCode> {
+    merge_files_from(csv, options, &file::read_file_checked_within)
+}
+
+/// Merges with files and nested lists loaded by `read` instead of through the content root.
+pub fn merge_files_from(csv: &str, options: &MergeOptions, read: &FileReader) -> Result<(String, Vec<Span>), CountErrorCode> {
+    let deadline = limits::Deadline::after_ms(options.timeout_ms);
+    let mut filenames = Vec::new();
+    flatten_list(csv, options, read, &deadline, &mut Vec::new(), &mut filenames)?;
+    let mut merged = String::new();
+    let mut spans = Vec::with_capacity(filenames.len());
+    for filename in filenames {
+        deadline.check()?;
+        let held = merged.len();
+        let file = read(&filename, &|len| fits_merge(len, held, &filename, options))?;
+        let transformed = transformed(file.to_str(), &filename, options)?;
+        let content = capped(transformed.as_deref().unwrap_or(file.to_str()), &filename, options)?;
+        let add_newline = options.ensure_trailing_newline && !content.is_empty() && !content.ends_with('\n');
+        limits::check_merge_total_bytes(merged.len() + content.len() + add_newline as usize)?;
+        spans.push(Span { start: merged.len(), len: content.len(), filename });
+        merged.push_str(content);
+        if add_newline {
+            merged.push('\n');
+        }
+    }
+    Ok((merged, spans))
+}
+
+fn count_files(
+    csv: &str,
+    metric: Command,
+    mut on_file: impl FnMut(&str, u64) -> Result<(), CountErrorCode>,
+) -> Result<(), CountErrorCode> {
+    limits::check_csv_values(csv, ',')?;
+    // Rejects commands that are not metrics before any file is read.
+    text::run_metric(&metric, "")?;
+    for filename in api::csv_values(csv) {
+        let file = file::read_file_checked(filename)?;
+        on_file(filename, text::run_metric(&metric, file.to_str())?)?;
+    }
+    Ok(())
+}
+
+fn validate_manifest(
+    csv: &str,
+    mut on_file: impl FnMut(&str, bool) -> Result<(), CountErrorCode>,
+) -> Result<u64, CountErrorCode> {
+    limits::check_csv_values(csv, ',')?;
+    let mut missing = 0;
+    for filename in api::csv_values(csv) {
+        let exists = file::file_exists(filename);
+        missing += u64::from(!exists);
+        on_file(filename, exists)?;
+    }
+    Ok(missing)
+}
+
+/// `None` when there is no transform or it kept the content.
+fn transformed(content: &str, filename: &str, options: &MergeOptions) -> Result<Option<String>, CountErrorCode> {
+    let transform = match options.transform {
+        Some(transform) => transform,
+        None => return Ok(None),
+    };
+    let c_filename = CString::new(filename).unwrap();
+    let mut c_content = Vec::with_capacity(content.len() + 1);
+    c_content.extend_from_slice(content.as_bytes());
+    c_content.push(0);
+    let replaced = error::guard_callback(|| unsafe {
+        transform(c_filename.as_ptr(), c_content.as_ptr().cast(), content.len(), options.transform_context)
+    })?;
+    if replaced.is_null() {
+        return Ok(None);
+    }
+    match unsafe { CString::from_raw(replaced) }.into_string() {
+        Ok(replaced) => Ok(Some(replaced)),
+        Err(_) => error::fail(CountErrorCode::InvalidUtf8, format!("Transform of {filename} returned invalid UTF-8")),
+    }
+}
+
+/// Checks a file of `len` bytes before and while it is read, so that one the merge would reject
+/// fails without being loaded whole. A transform may shrink the content, so only the text limit
+/// applies ahead of one.
+fn fits_merge(len: usize, held: usize, filename: &str, options: &MergeOptions) -> Result<(), CountErrorCode> {
+    limits::check_text_bytes(len)?;
+    if options.transform.is_some() {
+        return Ok(());
+    }
+    let limit = usize::try_from(options.max_file_bytes).unwrap_or(usize::MAX);
+    let kept = match (limit, options.truncate_oversized) {
+        (0, _) => len,
+        (_, true) => len.min(limit),
+        (_, false) if len > limit => return too_large(filename, len, limit),
+        (_, false) => len,
+    };
+    limits::check_merge_total_bytes(held + kept)
+}
+
+fn too_large<T>(filename: &str, len: usize, limit: usize) -> Result<T, CountErrorCode> {
+    error::fail(CountErrorCode::FileTooLarge, format!("{filename} is {len} bytes, over the {limit} byte limit"))
+}
+
+fn capped<'a>(content: &'a str, filename: &str, options: &MergeOptions) -> Result<&'a str, CountErrorCode> {
+    let limit = usize::try_from(options.max_file_bytes).unwrap_or(usize::MAX);
+    if limit == 0 || content.len() <= limit {
+        return Ok(content);
+    }
+    if !options.truncate_oversized {
+        return too_large(filename, content.len(), limit);
+    }
+    let end = (0..=limit).rev().find(|&index| content.is_char_boundary(index)).unwrap_or(0);
+    Ok(&content[..end])
+}
+
+fn flatten_list(
+    csv: &str,
+    options: &MergeOptions,
+    read: &FileReader,
+    deadline: &limits::Deadline,
+    chain: &mut Vec<String>,
+    filenames: &mut Vec<String>,
+) -> Result<(), CountErrorCode> {
+    limits::check_csv_values(csv, ',')?;
+    for value in csv.split(",").map(str::trim) {
+        if !(options.recursive && value.ends_with(".csv")) {
+            filenames.push(value.to_owned());
+            continue;
+        }
+        if chain.iter().any(|list| list == value) {
+            chain.push(value.to_owned());
+            return error::fail(CountErrorCode::ListCycle, format!("CSV list cycle: {}", chain.join(" -> ")));
+        }
+        let max_depth = if options.max_depth == 0 { DEFAULT_MAX_DEPTH } else { options.max_depth };
+        if chain.len() >= max_depth as usize {
+            return error::fail(
+                CountErrorCode::ListTooDeep,
+                format!("CSV lists nested deeper than {max_depth}: {} -> {value}", chain.join(" -> ")),
+            );
+        }
+        deadline.check()?;
+        let list = read(value, &limits::check_text_bytes)?;
+        chain.push(value.to_owned());
+        flatten_list(list.to_str(), options, read, deadline, chain, filenames)?;
+        chain.pop();
     }
+    Ok(())
 }
 
-fn merge_files(csv: &str) -> String {
+/// Also returns the length plain concatenation would have produced.
+fn merge_files_dedup_lines(csv: &str) -> Result<(String, usize), CountErrorCode> {
+    limits::check_csv_values(csv, ',')?;
+    let mut seen = HashSet::new();
     let mut merged = String::new();
+    let mut concatenated_len = 0;
     for value in csv.split(",") {
-        let file = file::read_file(value.trim());
-        merged.push_str(file.to_str());
+        let file = file::read_file_checked(value.trim())?;
+        concatenated_len += file.to_str().len();
+        for line in file.to_str().split_inclusive('\n') {
+            if seen.insert(line.trim_end_matches(['\r', '\n']).to_owned()) {
+                limits::check_merge_total_bytes(merged.len() + line.len())?;
+                merged.push_str(line);
+            }
+        }
+    }
+    Ok((merged, concatenated_len))
+}
+
+fn common_prefix(csv: &str, delimiter: char) -> String {
+    let mut values = csv.split(delimiter).map(str::trim);
+    let mut prefix = values.next().unwrap_or_default();
+    for value in values {
+        let len = prefix
+            .char_indices()
+            .zip(value.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(prefix.len().min(value.len()), |((index, _), _)| index);
+        prefix = &prefix[..len];
+    }
+    prefix.to_owned()
+}
+
+fn max_field_width(csv: &str, delimiter: char) -> Result<(u64, u64), CountErrorCode> {
+    limits::check_csv_values(csv, delimiter)?;
+    let widths: Vec<usize> = csv.split(delimiter).map(|value| value.trim().chars().count()).collect();
+    Ok((widths.len() as u64, widths.into_iter().max().unwrap_or(0) as u64))
+}
+
+fn count_excluding(csv: &str, delimiter: char, stop_values: &str) -> Result<u64, CountErrorCode> {
+    limits::check_csv_values(csv, delimiter)?;
+    let stop_values: HashSet<&str> = api::csv_values(stop_values).into_iter().collect();
+    Ok(csv.split(delimiter).map(str::trim).filter(|value| !stop_values.contains(value)).count() as u64)
+}
+
+/// Text without a delimiter is a single field, so an empty first line counts as trailing empty.
+fn has_trailing_empty(csv: &str, delimiter: char) -> bool {
+    let first_line = csv.lines().next().unwrap_or_default();
+    first_line.rsplit(delimiter).next().unwrap_or_default().trim().is_empty()
+}
+
+fn equal_unordered(a: &str, b: &str, delimiter: char) -> bool {
+    sorted_values(a, delimiter) == sorted_values(b, delimiter)
+}
+
+fn sorted_values(csv: &str, delimiter: char) -> Vec<&str> {
+    let mut values: Vec<&str> = csv.split(delimiter).map(str::trim).collect();
+    values.sort_unstable();
+    values
+}
+
+fn find_duplicates(csv: &str, callback: impl Fn(&str, usize, usize)) -> Result<u64, CountErrorCode> {
+    limits::check_csv_values(csv, ',')?;
+    let mut first_seen = HashMap::new();
+    let mut count = 0;
+    for (index, value) in csv.split(",").map(str::trim).enumerate() {
+        match first_seen.get(value) {
+            Some(&first_index) => {
+                callback(value, first_index, index);
+                count += 1;
+            }
+            None => {
+                first_seen.insert(value, index);
+            }
+        }
+    }
+    Ok(count)
+}
+
+fn split_quoted(csv: &str, delimiter: char, quote: char) -> Result<Vec<String>, CountErrorCode> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    // Length of the quoted content once its closing quote has been seen.
+    let mut quoted_len: Option<usize> = None;
+    let mut chars = csv.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if c != quote {
+                field.push(c);
+            } else if chars.peek() == Some(&quote) {
+                field.push(quote);
+                chars.next();
+            } else {
+                in_quotes = false;
+                quoted_len = Some(field.len());
+            }
+        } else if c == delimiter {
+            limits::check_csv_value_count(fields.len() + 1)?;
+            fields.push(finish_field(&field, quoted_len)?);
+            field.clear();
+            quoted_len = None;
+        } else if c == quote && quoted_len.is_none() && field.trim().is_empty() {
+            field.clear();
+            in_quotes = true;
+        } else {
+            field.push(c);
+        }
+    }
+    if in_quotes {
+        return error::fail(CountErrorCode::InvalidArgument, "Unterminated quoted field.");
+    }
+    limits::check_csv_value_count(fields.len() + 1)?;
+    fields.push(finish_field(&field, quoted_len)?);
+    Ok(fields)
+}
+
+fn quoting_overhead(csv: &str, delimiter: char) -> Result<u64, CountErrorCode> {
+    let fields = split_quoted(csv, delimiter, '"')?;
+    // Every quote in the input either survives into a field or is syntax.
+    let kept: usize = fields.iter().map(|field| field.matches('"').count()).sum();
+    Ok((csv.matches('"').count() - kept) as u64)
+}
+
+/// Rows are single lines, as in `select_columns`.
+fn header_and_row_count(csv: &str, delimiter: char) -> Result<(u64, u64), CountErrorCode> {
+    let mut rows = csv.lines().filter(|line| !line.is_empty());
+    match rows.next() {
+        Some(header) => Ok((split_quoted(header, delimiter, '"')?.len() as u64, rows.count() as u64)),
+        None => Ok((0, 0)),
+    }
+}
+
+fn finish_field(field: &str, quoted_len: Option<usize>) -> Result<String, CountErrorCode> {
+    match quoted_len {
+        Some(len) if !field[len..].trim().is_empty() => {
+            error::fail(CountErrorCode::InvalidArgument, "Unexpected text after a closing quote.")
+        }
+        Some(len) => Ok(field[..len].to_owned()),
+        None => Ok(field.trim().to_owned()),
+    }
+}
+
+/// Rows are single lines; quoted values may not span lines.
+fn select_columns(csv: &str, columns: &[&str]) -> Result<String, CountErrorCode> {
+    let mut rows = csv.lines().filter(|line| !line.is_empty());
+    let header = split_quoted(rows.next().unwrap_or_default(), ',', '"')?;
+    let indices = columns
+        .iter()
+        .map(|column| match header.iter().position(|name| name == column) {
+            Some(index) => Ok(index),
+            None => error::fail(CountErrorCode::InvalidArgument, format!("Unknown column: {column}")),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut selected = String::new();
+    let mut write_row = |fields: &[String]| {
+        let values: Vec<String> = indices
+            .iter()
+            .map(|&index| quote_field(fields.get(index).map_or("", String::as_str)))
+            .collect();
+        selected.push_str(&values.join(","));
+        selected.push('\n');
+    };
+    write_row(&header);
+    for row in rows {
+        write_row(&split_quoted(row, ',', '"')?);
+    }
+    Ok(selected)
+}
+
+fn align(csv: &str, delimiter: char) -> Result<String, CountErrorCode> {
+    let rows = csv
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|row| split_quoted(row, delimiter, '"'))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut widths: Vec<usize> = Vec::new();
+    for row in &rows {
+        widths.resize(widths.len().max(row.len()), 0);
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    let mut aligned = String::new();
+    for row in &rows {
+        let mut line = String::new();
+        for (index, &width) in widths.iter().enumerate() {
+            let value = row.get(index).map_or("", String::as_str);
+            line.push_str(&format!("{value:<width$}  "));
+        }
+        aligned.push_str(line.trim_end());
+        aligned.push('\n');
+    }
+    Ok(aligned)
+}
+
+fn stream_records(
+    path: &Path,
+    delimiter: char,
+    mut on_record: impl FnMut(&[String]) -> Result<(), CountErrorCode>,
+) -> Result<(), CountErrorCode> {
+    let mut line = Vec::new();
+    let mut line_number = 0;
+    file::read_chunks(path, |mut chunk| {
+        while let Some(end) = memchr::memchr(b'\n', chunk) {
+            line.extend_from_slice(&chunk[..end]);
+            line_number += 1;
+            stream_record(&line, line_number, delimiter, &mut on_record)?;
+            line.clear();
+            chunk = &chunk[end + 1..];
+        }
+        limits::check_text_bytes(line.len() + chunk.len())?;
+        line.extend_from_slice(chunk);
+        Ok(())
+    })?;
+    stream_record(&line, line_number + 1, delimiter, &mut on_record)
+}
+
+fn stream_record(
+    line: &[u8],
+    line_number: u64,
+    delimiter: char,
+    on_record: &mut impl FnMut(&[String]) -> Result<(), CountErrorCode>,
+) -> Result<(), CountErrorCode> {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    if line.is_empty() {
+        return Ok(());
+    }
+    match std::str::from_utf8(line) {
+        Ok(line) => on_record(&split_quoted(line, delimiter, '"')?),
+        Err(_) => error::fail(CountErrorCode::InvalidUtf8, format!("Line {line_number} is not valid UTF-8")),
+    }
+}
+
+fn quote_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) || value.trim() != value {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+const DELIMITER_CANDIDATES: [char; 4] = [',', ';', '\t', '|'];
+
+fn detect_delimiter(text: &str) -> Result<char, CountErrorCode> {
+    let mut lines = text.lines().filter(|line| !line.is_empty());
+    let first = lines.next().unwrap_or_default();
+    // Reversed so that `max_by_key`, which keeps the last maximum, prefers earlier candidates.
+    let best = DELIMITER_CANDIDATES
+        .iter()
+        .rev()
+        .copied()
+        .filter(|&delimiter| first.contains(delimiter))
+        .map(|delimiter| (delimiter, first.matches(delimiter).count()))
+        .filter(|&(delimiter, count)| lines.clone().all(|line| line.matches(delimiter).count() == count))
+        .max_by_key(|&(_, count)| count);
+    match best {
+        Some((delimiter, _)) => Ok(delimiter),
+        None => error::fail(CountErrorCode::InvalidArgument, "No consistent delimiter found."),
+    }
+}
+
+pub struct MergeStream {
+    filenames: Vec<String>,
+    next_file: usize,
+    current: Vec<u8>,
+    offset: usize,
+    loaded: usize,
+    failed: Option<CountErrorCode>,
+}
+
+impl MergeStream {
+    fn new(csv: &str) -> Result<Self, CountErrorCode> {
+        limits::check_csv_values(csv, ',')?;
+        Ok(MergeStream {
+            filenames: csv.split(",").map(|value| value.trim().to_owned()).collect(),
+            next_file: 0,
+            current: Vec::new(),
+            offset: 0,
+            loaded: 0,
+            failed: None,
+        })
+    }
+
+    fn next_chunk(&mut self, buf: &mut [u8]) -> Result<usize, CountErrorCode> {
+        if let Some(code) = self.failed {
+            return Err(code);
+        }
+        let mut written = 0;
+        while written < buf.len() {
+            if self.offset == self.current.len() {
+                let filename = match self.filenames.get(self.next_file) {
+                    Some(filename) => filename,
+                    None => break,
+                };
+                let held = self.loaded;
+                let loaded = file::read_file_checked_within(filename, &|len| {
+                    limits::check_text_bytes(len)?;
+                    limits::check_merge_total_bytes(held + len)
+                });
+                match loaded {
+                    Ok(file) => {
+                        self.current = file.to_str().as_bytes().to_vec();
+                        self.loaded += self.current.len();
+                    }
+                    Err(code) => {
+                        self.failed = Some(code);
+                        return if written > 0 { Ok(written) } else { Err(code) };
+                    }
+                }
+                self.next_file += 1;
+                self.offset = 0;
+                continue;
+            }
+            let len = (self.current.len() - self.offset).min(buf.len() - written);
+            buf[written..written + len].copy_from_slice(&self.current[self.offset..self.offset + len]);
+            self.offset += len;
+            written += len;
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{c_void, CStr, CString};
+    use std::os::raw::c_char;
+
+    use super::{ffi, MergeOptions};
+    use crate::modules::error::{self, CountErrorCode};
+    use crate::modules::file::{File, SizeCheck};
+    use crate::modules::stats::{self, CountOptions, TextStats};
+    use crate::testutil::ArgvBuilder;
+    use crate::Command;
+
+    /// Collects `(filename, count)` pairs into the `Vec` behind `context`.
+    unsafe extern "C" fn collect_count(filename: *const c_char, count: u64, context: *const c_void) {
+        let counts = &mut *(context as *mut Vec<(String, u64)>);
+        counts.push((CStr::from_ptr(filename).to_str().unwrap().to_owned(), count));
+    }
+
+    fn count_files(csv: &str, metric: Command) -> (i32, Vec<(String, u64)>) {
+        let csv = CString::new(csv).unwrap();
+        let mut counts = Vec::new();
+        let context = &mut counts as *mut Vec<(String, u64)> as *const c_void;
+        let status = ffi::csv_count_files(csv.as_ptr(), metric, collect_count, context);
+        (status, counts)
+    }
+
+    #[test]
+    fn count_files_reports_chars_per_file() {
+        let (status, counts) = count_files("chapter1.md, chapter2.md", Command::Characters);
+        assert_eq!(status, CountErrorCode::Ok as i32);
+        assert_eq!(counts, [("chapter1.md".to_owned(), 18), ("chapter2.md".to_owned(), 14)]);
+    }
+
+    #[test]
+    fn count_files_stops_at_a_missing_file() {
+        let (status, counts) = count_files("chapter1.md, missing.md, chapter2.md", Command::Bytes);
+        assert_eq!(status, CountErrorCode::FileNotFound as i32);
+        assert_eq!(counts, [("chapter1.md".to_owned(), 18)]);
+    }
+
+    /// Pulls `csv` through a merge stream `buf_len` bytes at a time until it reports done or an
+    /// error, and returns the bytes delivered with the final status.
+    fn pull_merge_stream(csv: &str, buf_len: usize) -> (Vec<u8>, i32) {
+        let csv = CString::new(csv).unwrap();
+        let stream = ffi::merge_stream_new(csv.as_ptr());
+        assert!(!stream.is_null());
+        let mut delivered = Vec::new();
+        let mut buf = vec![0; buf_len];
+        let status = loop {
+            let mut written = usize::MAX;
+            let status = ffi::merge_stream_next(stream, buf.as_mut_ptr(), buf.len(), &mut written);
+            if status != 0 {
+                assert_eq!(written, 0);
+                break status;
+            }
+            assert!(written > 0 && written <= buf_len);
+            delivered.extend_from_slice(&buf[..written]);
+        };
+        let mut written = 0;
+        assert_eq!(ffi::merge_stream_next(stream, buf.as_mut_ptr(), buf.len(), &mut written), status);
+        ffi::merge_stream_free(stream);
+        (delivered, status)
+    }
+
+    #[test]
+    fn merge_stream_reassembles_from_tiny_buffers() {
+        let csv = "chapter1.md, chapter2.md, chapter1.md";
+        let expected = super::merge_files(csv).unwrap();
+        for buf_len in [1, 3, 5, 18, 4096] {
+            let (delivered, status) = pull_merge_stream(csv, buf_len);
+            assert_eq!(status, 1);
+            assert_eq!(String::from_utf8(delivered).unwrap(), expected, "with a {buf_len} byte buffer");
+        }
+    }
+
+    #[test]
+    fn merge_stream_reports_a_missing_later_file() {
+        let (delivered, status) = pull_merge_stream("chapter1.md, missing.md, chapter2.md", 3);
+        assert_eq!(status, -(CountErrorCode::FileNotFound as i32));
+        assert_eq!(delivered, b"# Getting started\n");
+    }
+
+    #[test]
+    fn merge_stream_rejects_null_arguments() {
+        let null_pointer = -(CountErrorCode::NullPointer as i32);
+        let (mut buf, mut written) = ([0; 3], 0);
+        assert!(ffi::merge_stream_new(std::ptr::null()).is_null());
+        assert_eq!(ffi::merge_stream_next(std::ptr::null_mut(), buf.as_mut_ptr(), 3, &mut written), null_pointer);
+    }
+
+    /// A provider that takes `delay` to load each fixture.
+    fn slow_fixtures(
+        delay: std::time::Duration,
+    ) -> impl Fn(&str, &crate::modules::file::SizeCheck) -> Result<crate::modules::file::File, CountErrorCode> {
+        move |filename, check| {
+            std::thread::sleep(delay);
+            crate::modules::file::read_file_checked_within(filename, check)
+        }
+    }
+
+    #[test]
+    fn merge_times_out_between_slow_reads() {
+        let options = super::MergeOptions { timeout_ms: 20, ..super::MergeOptions::default() };
+        let read = slow_fixtures(std::time::Duration::from_millis(40));
+        let merged = super::merge_files_from("chapter1.md, chapter2.md", &options, &read);
+        assert_eq!(merged.err(), Some(CountErrorCode::TimedOut));
+    }
+
+    #[test]
+    fn merge_without_a_timeout_waits_for_slow_reads() {
+        let read = slow_fixtures(std::time::Duration::from_millis(5));
+        let (merged, _) = super::merge_files_from("chapter1.md, chapter2.md", &super::MergeOptions::default(), &read).unwrap();
+        assert_eq!(merged, "# Getting started\n# Wrapping up\n");
+    }
+
+    unsafe extern "C" fn collect_value(value: *const c_char, context: *const c_void) {
+        let values = &mut *(context as *mut Vec<String>);
+        values.push(CStr::from_ptr(value).to_str().unwrap().to_owned());
+    }
+
+    fn for_each_value(csv: *const c_char) -> (i64, Vec<String>) {
+        let mut values = Vec::new();
+        let count = ffi::csv_for_each_value(csv, collect_value, &mut values as *mut Vec<String> as *const c_void);
+        (count, values)
+    }
+
+    #[test]
+    fn for_each_value_returns_the_number_delivered() {
+        let count = |csv: &str| for_each_value(CString::new(csv).unwrap().as_ptr());
+        // Splitting never drops fields, so an empty list is one empty value.
+        assert_eq!(count(""), (1, vec![String::new()]));
+        assert_eq!(count(" one.md "), (1, vec!["one.md".to_owned()]));
+        let (delivered, values) = count("a, b, c, d, e");
+        assert_eq!(delivered, 5);
+        assert_eq!(values, ["a", "b", "c", "d", "e"]);
+    }
+
+    /// Collects values into the `(limit, Vec)` behind `context`, stopping once it holds `limit`.
+    unsafe extern "C" fn collect_up_to(value: *const c_char, context: *const c_void) -> bool {
+        let (limit, values) = &mut *(context as *mut (usize, Vec<String>));
+        values.push(CStr::from_ptr(value).to_str().unwrap().to_owned());
+        values.len() < *limit
+    }
+
+    fn for_each_value_until(csv: &str, limit: usize) -> (i64, Vec<String>) {
+        let csv = CString::new(csv).unwrap();
+        let mut state = (limit, Vec::new());
+        let count = ffi::csv_for_each_value_until(csv.as_ptr(), collect_up_to, &mut state as *mut (usize, Vec<String>) as *const c_void);
+        (count, state.1)
+    }
+
+    #[test]
+    fn for_each_value_until_counts_the_values_delivered_before_the_stop() {
+        assert_eq!(for_each_value_until("a, b, c, d, e", 2), (2, vec!["a".to_owned(), "b".to_owned()]));
+        assert_eq!(for_each_value_until("a, b, c, d, e", 9).0, 5);
+        assert_eq!(for_each_value_until("", 1), (1, vec![String::new()]));
+        assert_eq!(for_each_value_until("one.md", 1), (1, vec!["one.md".to_owned()]));
+        let context = std::ptr::null();
+        assert_eq!(ffi::csv_for_each_value_until(std::ptr::null(), collect_up_to, context), -(CountErrorCode::NullPointer as i64));
+    }
+
+    #[test]
+    fn for_each_value_rejects_null_and_invalid_utf8() {
+        assert_eq!(for_each_value(std::ptr::null()), (-(CountErrorCode::NullPointer as i64), vec![]));
+        let invalid = CString::new(b"a,\xff".to_vec()).unwrap();
+        assert_eq!(for_each_value(invalid.as_ptr()), (-(CountErrorCode::InvalidUtf8 as i64), vec![]));
+    }
+
+    unsafe extern "C" fn ignore_stats(_: *const c_char, _: *const TextStats, _: *mut c_void) {}
+
+    unsafe extern "C" fn ignore_duplicate(_: *const c_char, _: usize, _: usize, _: *mut c_void) {}
+
+    #[test]
+    fn value_callbacks_return_negative_codes_for_bad_input() {
+        let invalid = CString::new(b"\xff".to_vec()).unwrap();
+        let context = std::ptr::null_mut();
+        assert_eq!(ffi::csv_for_each_value_stats(std::ptr::null(), ignore_stats, context), -1);
+        assert_eq!(ffi::csv_for_each_value_stats(invalid.as_ptr(), ignore_stats, context), -2);
+        assert_eq!(ffi::csv_find_duplicates(std::ptr::null(), ignore_duplicate, context), -1);
+        assert_eq!(ffi::csv_find_duplicates(invalid.as_ptr(), ignore_duplicate, context), -2);
+    }
+
+    #[test]
+    fn equal_unordered_and_common_prefix_reject_bad_input() {
+        let csv = CString::new("a,b").unwrap();
+        let invalid = CString::new(b"\xff".to_vec()).unwrap();
+        assert_eq!(ffi::csv_equal_unordered(csv.as_ptr(), std::ptr::null(), b',' as c_char), -1);
+        assert_eq!(ffi::csv_equal_unordered(invalid.as_ptr(), csv.as_ptr(), b',' as c_char), -2);
+        assert!(ffi::csv_common_prefix(std::ptr::null(), b',' as c_char).is_null());
+        assert!(ffi::csv_common_prefix(invalid.as_ptr(), b',' as c_char).is_null());
+    }
+
+    thread_local! {
+        static FREED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    unsafe extern "C" fn free_counted(csv: *mut c_char) {
+        drop(CString::from_raw(csv));
+        FREED.with(|freed| freed.set(freed.get() + 1));
+    }
+
+    #[test]
+    fn merge_files_frees_invalid_csv_and_returns_null() {
+        let invalid = CString::new(b"chapter1.md,\xff".to_vec()).unwrap().into_raw();
+        assert!(ffi::csv_merge_files(invalid, free_counted).is_null());
+        let invalid = CString::new(b"\xff".to_vec()).unwrap().into_raw();
+        assert!(ffi::csv_merge_files_dedup_lines(invalid, free_counted).is_null());
+        assert_eq!(FREED.with(|freed| freed.get()), 2);
+        assert!(ffi::csv_merge_files(std::ptr::null_mut(), free_counted).is_null());
+        assert_eq!(FREED.with(|freed| freed.get()), 2);
+    }
+
+    #[test]
+    fn merge_files_frees_the_csv_it_merged() {
+        let csv = CString::new("chapter1.md, chapter2.md").unwrap().into_raw();
+        let merged = ffi::csv_merge_files(csv, free_counted);
+        assert_eq!(FREED.with(|freed| freed.get()), 1);
+        assert_eq!(unsafe { CString::from_raw(merged) }.to_str().unwrap(), "# Getting started\n# Wrapping up\n");
+    }
+
+    unsafe extern "C" fn free_csv(csv: *mut c_char) {
+        drop(CString::from_raw(csv));
+    }
+
+    #[test]
+    fn merge_dedup_keeps_the_first_copy_of_a_shared_line() {
+        let csv = CString::new("chapter1.md, chapter2.md, chapter1.md").unwrap().into_raw();
+        let merged = ffi::csv_merge_files_dedup_lines(csv, free_csv);
+        assert!(!merged.is_null());
+        let merged = unsafe { CString::from_raw(merged) }.into_string().unwrap();
+        assert_eq!(merged, "# Getting started\n# Wrapping up\n");
+        assert_eq!(merged.matches("# Getting started\n").count(), 1);
+    }
+
+    fn common_prefix(csv: &str, delimiter: u8) -> String {
+        let csv = CString::new(csv).unwrap();
+        let prefix = ffi::csv_common_prefix(csv.as_ptr(), delimiter as c_char);
+        assert!(!prefix.is_null());
+        unsafe { CString::from_raw(prefix) }.into_string().unwrap()
+    }
+
+    #[test]
+    fn common_prefix_of_trimmed_values() {
+        assert_eq!(common_prefix("abcd, abce, abxy", b','), "ab");
+        assert_eq!(common_prefix("abc;abd", b';'), "ab");
+        assert_eq!(common_prefix("abc, xyz", b','), "");
+        assert_eq!(common_prefix("same", b','), "same");
+    }
+
+    unsafe extern "C" fn collect_stats(value: *const c_char, stats: *const TextStats, context: *mut c_void) {
+        let delivered = &mut *(context as *mut Vec<(String, TextStats)>);
+        delivered.push((CStr::from_ptr(value).to_str().unwrap().to_owned(), *stats));
+    }
+
+    #[test]
+    fn value_stats_match_counting_each_value_alone() {
+        let csv = CString::new("plain, naïve café , 日本語,").unwrap();
+        let mut delivered: Vec<(String, TextStats)> = Vec::new();
+        let count = ffi::csv_for_each_value_stats(csv.as_ptr(), collect_stats, &mut delivered as *mut _ as *mut c_void);
+        assert_eq!(count, 4);
+        let values: Vec<&str> = delivered.iter().map(|(value, _)| value.as_str()).collect();
+        assert_eq!(values, ["plain", "naïve café", "日本語", ""]);
+        for (value, stats) in &delivered {
+            assert_eq!(*stats, stats::count_all(value, &CountOptions::default()), "for {value:?}");
+        }
+        assert_eq!(delivered[2].1, TextStats { bytes: 9, chars: 3, words: 1, lines: 1 });
+    }
+
+    type Files = &'static [(&'static str, &'static str)];
+
+    /// A `FileReader` over `files`, failing with `FileNotFound` for anything else.
+    fn in_memory(files: Files) -> impl Fn(&str, &SizeCheck) -> Result<File, CountErrorCode> {
+        move |filename, check| match files.iter().find(|(name, _)| *name == filename) {
+            Some((_, contents)) => check(contents.len()).map(|()| File::from_contents(contents)),
+            None => error::fail(CountErrorCode::FileNotFound, format!("File not found: {filename}")),
+        }
+    }
+
+    fn merge_nested(csv: &str, files: Files, max_depth: u32) -> Result<String, CountErrorCode> {
+        let options = MergeOptions { recursive: true, max_depth, ..MergeOptions::default() };
+        super::merge_files_from(csv, &options, &in_memory(files)).map(|(merged, _)| merged)
+    }
+
+    const NESTED: Files = &[
+        ("outer.csv", "a.md, inner.csv, d.md"),
+        ("inner.csv", "b.md, c.md"),
+        ("a.md", "a\n"),
+        ("b.md", "b\n"),
+        ("c.md", "c\n"),
+        ("d.md", "d\n"),
+    ];
+
+    #[test]
+    fn merges_a_list_nested_two_levels_deep() {
+        assert_eq!(merge_nested("outer.csv", NESTED, 0), Ok("a\nb\nc\nd\n".to_owned()));
+        let flat = MergeOptions::default();
+        let result = super::merge_files_from("inner.csv", &flat, &in_memory(NESTED)).map(|(merged, _)| merged);
+        assert_eq!(result, Ok("b.md, c.md".to_owned()));
+    }
+
+    #[test]
+    fn rejects_a_cycle_of_lists() {
+        const CYCLE: Files = &[("one.csv", "a.md, two.csv"), ("two.csv", "one.csv"), ("a.md", "a\n")];
+        assert_eq!(merge_nested("one.csv", CYCLE, 0), Err(CountErrorCode::ListCycle));
+        assert_eq!(error::message_for(CountErrorCode::ListCycle), "CSV list cycle: one.csv -> two.csv -> one.csv");
+    }
+
+    #[test]
+    fn rejects_lists_nested_past_the_depth_limit() {
+        assert_eq!(merge_nested("outer.csv", NESTED, 1), Err(CountErrorCode::ListTooDeep));
+        assert_eq!(merge_nested("outer.csv", NESTED, 2), Ok("a\nb\nc\nd\n".to_owned()));
+    }
+
+    fn equal_unordered(a: &str, b: &str) -> i32 {
+        let (a, b) = (CString::new(a).unwrap(), CString::new(b).unwrap());
+        ffi::csv_equal_unordered(a.as_ptr(), b.as_ptr(), b',' as c_char)
+    }
+
+    #[test]
+    fn equal_unordered_compares_multisets() {
+        assert_eq!(equal_unordered("a,b", "b,a"), 1);
+        assert_eq!(equal_unordered("a,a", "a"), 0);
+        assert_eq!(equal_unordered(" a , b,a", "a,a,b"), 1);
+        assert_eq!(equal_unordered("a,b", "a,c"), 0);
+    }
+
+    unsafe extern "C" fn collect_duplicate(value: *const c_char, first_index: usize, duplicate_index: usize, context: *mut c_void) {
+        let duplicates = &mut *(context as *mut Vec<(String, usize, usize)>);
+        duplicates.push((CStr::from_ptr(value).to_str().unwrap().to_owned(), first_index, duplicate_index));
+    }
+
+    fn duplicates(csv: &str) -> (i64, Vec<(String, usize, usize)>) {
+        let csv = CString::new(csv).unwrap();
+        let mut duplicates = Vec::new();
+        let context = &mut duplicates as *mut Vec<(String, usize, usize)> as *mut c_void;
+        let count = ffi::csv_find_duplicates(csv.as_ptr(), collect_duplicate, context);
+        (count, duplicates)
+    }
+
+    #[test]
+    fn finds_repeats_with_the_position_of_the_first_occurrence() {
+        assert_eq!(duplicates("a.md, b.md, c.md"), (0, vec![]));
+        assert_eq!(duplicates("a.md,a.md,b.md"), (1, vec![("a.md".to_owned(), 0, 1)]));
+        // Values are trimmed before they are compared.
+        let expected = vec![("a.md".to_owned(), 0, 2), ("a.md".to_owned(), 0, 3)];
+        assert_eq!(duplicates("a.md, b.md,  a.md ,\ta.md"), (2, expected));
+    }
+
+    fn merge_capped(csv: &str, max_file_bytes: u64, truncate_oversized: bool) -> Result<String, CountErrorCode> {
+        const FILES: Files = &[("short.md", "tiny\n"), ("long.md", "much longer\n"), ("accents.md", "naïve\n")];
+        let options = MergeOptions { max_file_bytes, truncate_oversized, ..MergeOptions::default() };
+        super::merge_files_from(csv, &options, &in_memory(FILES)).map(|(merged, _)| merged)
+    }
+
+    #[test]
+    fn oversized_files_fail_or_are_truncated() {
+        assert_eq!(merge_capped("short.md, long.md", 5, false), Err(CountErrorCode::FileTooLarge));
+        assert_eq!(merge_capped("short.md, long.md", 5, true).as_deref(), Ok("tiny\nmuch "));
+        assert_eq!(merge_capped("short.md, long.md", 0, false).as_deref(), Ok("tiny\nmuch longer\n"));
+        // A cut never splits a character: the 3rd byte is inside "ï".
+        assert_eq!(merge_capped("accents.md", 3, true).as_deref(), Ok("na"));
+    }
+
+    fn merge_newlines(csv: &str, ensure_trailing_newline: bool) -> String {
+        const FILES: Files = &[("ends.md", "ends\n"), ("open.md", "open"), ("empty.md", ""), ("crlf.md", "crlf\r\n")];
+        let options = MergeOptions { ensure_trailing_newline, ..MergeOptions::default() };
+        super::merge_files_from(csv, &options, &in_memory(FILES)).unwrap().0
+    }
+
+    #[test]
+    fn trailing_newlines_are_added_only_when_asked_and_missing() {
+        let csv = "open.md, ends.md, empty.md, open.md, crlf.md";
+        assert_eq!(merge_newlines(csv, true), "open\nends\nopen\ncrlf\r\n");
+        assert_eq!(merge_newlines(csv, false), "openends\nopencrlf\r\n");
+        assert_eq!(merge_newlines("empty.md", true), "");
+    }
+
+    fn delimiter_of(text: &str) -> Result<u8, i32> {
+        let text = CString::new(text).unwrap();
+        let mut out: c_char = 0;
+        match ffi::detect_delimiter(text.as_ptr(), &mut out) {
+            0 => Ok(out as u8),
+            status => Err(status),
+        }
+    }
+
+    #[test]
+    fn detects_semicolons_and_tabs() {
+        assert_eq!(delimiter_of("name;pages;words\nintro;3;900\noutro;1;200\n"), Ok(b';'));
+        assert_eq!(delimiter_of("name\tpages\nintro\t3\n\noutro\t1\n"), Ok(b'\t'));
+        // Both split every line consistently; the semicolons give more fields.
+        assert_eq!(delimiter_of("a,b;c;d\n1,5;2;3\n"), Ok(b';'));
+        assert_eq!(delimiter_of("a;b,c\n1;2,3;4\n"), Ok(b','));
+        assert_eq!(delimiter_of("no delimiter here"), Err(CountErrorCode::InvalidArgument as i32));
+    }
+
+    unsafe extern "C" fn collect_field(value: *const c_char, context: *mut c_void) {
+        collect_value(value, context);
+    }
+
+    fn quoted_fields(csv: &str, delimiter: u8, quote: u8) -> Result<Vec<String>, i32> {
+        let csv = CString::new(csv).unwrap();
+        let mut fields = Vec::new();
+        let context = &mut fields as *mut Vec<String> as *mut c_void;
+        match ffi::csv_for_each_field_quoted(csv.as_ptr(), delimiter as c_char, quote as c_char, collect_field, context) {
+            0 => Ok(fields),
+            status => Err(status),
+        }
+    }
+
+    #[test]
+    fn a_quoted_field_keeps_its_delimiters() {
+        assert_eq!(quoted_fields("a.md, 'b, c.md' ,d.md", b',', b'\''), Ok(vec!["a.md".into(), "b, c.md".into(), "d.md".into()]));
+        assert_eq!(quoted_fields("'it''s';x", b';', b'\''), Ok(vec!["it's".into(), "x".into()]));
+        assert_eq!(quoted_fields("\"a,b\",c", b',', b'"'), Ok(vec!["a,b".into(), "c".into()]));
+        let invalid = CountErrorCode::InvalidArgument as i32;
+        assert_eq!(quoted_fields("'open, b", b',', b'\''), Err(invalid));
+        assert_eq!(quoted_fields("'a'b, c", b',', b'\''), Err(invalid));
+    }
+
+    fn dedup_stats(csv: &str) -> Result<(String, u64), i32> {
+        let csv = CString::new(csv).unwrap();
+        let (mut merged, mut saved) = (std::ptr::null_mut(), u64::MAX);
+        match ffi::csv_merge_dedup_stats(csv.as_ptr(), &mut merged, &mut saved) {
+            0 => {
+                let text = unsafe { CStr::from_ptr(merged) }.to_str().unwrap().to_owned();
+                ffi::csv_free_merged_file(merged);
+                Ok((text, saved))
+            }
+            status => Err(status),
+        }
+    }
+
+    #[test]
+    fn dedup_stats_report_the_bytes_saved() {
+        assert_eq!(dedup_stats("chapter1.md, chapter2.md"), Ok(("# Getting started\n# Wrapping up\n".to_owned(), 0)));
+        assert_eq!(dedup_stats("chapter1.md, chapter2.md, chapter1.md"), Ok(("# Getting started\n# Wrapping up\n".to_owned(), 18)));
+        assert_eq!(dedup_stats("missing.md"), Err(CountErrorCode::FileNotFound as i32));
+        let csv = CString::new("chapter1.md").unwrap();
+        assert_eq!(ffi::csv_merge_dedup_stats(csv.as_ptr(), std::ptr::null_mut(), std::ptr::null_mut()), CountErrorCode::NullPointer as i32);
+    }
+
+    fn select(csv: &str, columns: &[&str]) -> Option<String> {
+        let csv = CString::new(csv).unwrap();
+        let mut columns = ArgvBuilder::new(columns);
+        let (n_columns, columns) = columns.build();
+        let selected = ffi::csv_select_columns(csv.as_ptr(), columns, n_columns);
+        if selected.is_null() {
+            return None;
+        }
+        let copy = unsafe { CStr::from_ptr(selected) }.to_str().unwrap().to_owned();
+        crate::count_string_free(selected);
+        Some(copy)
+    }
+
+    const BOOKS: &str = "title,author,pages\n\"Commas, Quotes\",\"Doe, Jane\",120\nPlain,Smith,80\n";
+
+    #[test]
+    fn selects_named_columns_in_the_requested_order() {
+        let expected = "pages,title\n120,\"Commas, Quotes\"\n80,Plain\n";
+        assert_eq!(select(BOOKS, &["pages", "title"]).as_deref(), Some(expected));
+        let expected = "author\n\"Doe, Jane\"\nSmith\n";
+        assert_eq!(select(BOOKS, &["author"]).as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn a_missing_column_returns_null_and_names_it() {
+        assert_eq!(select(BOOKS, &["title", "isbn"]), None);
+        assert_eq!(error::message_for(CountErrorCode::InvalidArgument), "Unknown column: isbn");
+    }
+
+    #[test]
+    fn spans_cover_each_file_but_not_the_inserted_newlines() {
+        const FILES: Files = &[("one.md", "one"), ("two.md", "two\n"), ("three.md", "three")];
+        let options = MergeOptions { ensure_trailing_newline: true, with_manifest: true, ..MergeOptions::default() };
+        let (merged, spans) = super::merge_files_from("one.md, two.md, three.md", &options, &in_memory(FILES)).unwrap();
+        assert_eq!(merged, "one\ntwo\nthree\n");
+        let spans: Vec<(&str, usize, usize)> = spans.iter().map(|span| (span.filename.as_str(), span.start, span.len)).collect();
+        assert_eq!(spans, [("one.md", 0, 3), ("two.md", 4, 4), ("three.md", 8, 5)]);
+        for (filename, start, len) in spans {
+            let contents = FILES.iter().find(|(name, _)| *name == filename).unwrap().1;
+            assert_eq!(&merged[start..start + len], contents);
+        }
+    }
+
+    /// `(filename, start, len)` of each span `csv_merge_take_manifest` hands over.
+    fn take_manifest() -> Vec<(String, u64, u64)> {
+        let (mut spans, mut len) = (std::ptr::null_mut(), usize::MAX);
+        assert_eq!(ffi::csv_merge_take_manifest(&mut spans, &mut len), CountErrorCode::Ok as i32);
+        let taken = unsafe { std::slice::from_raw_parts(spans, len) }
+            .iter()
+            .map(|span| (unsafe { CStr::from_ptr(span.filename) }.to_str().unwrap().to_owned(), span.start, span.len))
+            .collect();
+        ffi::merge_span_array_free(spans, len);
+        taken
+    }
+
+    #[test]
+    fn the_manifest_is_taken_once_after_a_checked_merge() {
+        let csv = CString::new("chapter1.md, chapter2.md, chapter1.md").unwrap();
+        let options = MergeOptions { with_manifest: true, ..MergeOptions::default() };
+        let mut merged = std::ptr::null_mut();
+        assert_eq!(ffi::csv_merge_files_checked(csv.as_ptr(), &options, &mut merged), CountErrorCode::Ok as i32);
+        ffi::csv_free_merged_file(merged);
+        let expected = [("chapter1.md".to_owned(), 0, 18), ("chapter2.md".to_owned(), 18, 14), ("chapter1.md".to_owned(), 32, 18)];
+        assert_eq!(take_manifest(), expected);
+        assert_eq!(take_manifest(), []);
+    }
+
+    fn quoting_overhead(csv: &str) -> Result<u64, i32> {
+        let csv = CString::new(csv).unwrap();
+        let mut overhead = u64::MAX;
+        match ffi::csv_quoting_overhead(csv.as_ptr(), b',' as c_char, &mut overhead) {
+            0 => Ok(overhead),
+            status => Err(status),
+        }
+    }
+
+    #[test]
+    fn counts_quote_syntax_but_not_quoted_content() {
+        assert_eq!(quoting_overhead("a,b,c"), Ok(0));
+        assert_eq!(quoting_overhead("\"a,b\",c"), Ok(2));
+        // The doubled quote costs one byte; the other is content.
+        assert_eq!(quoting_overhead("\"say \"\"hi\"\"\",x"), Ok(4));
+        // Quotes inside an unquoted field are content.
+        assert_eq!(quoting_overhead("5\" disk,\"\""), Ok(2));
+        assert_eq!(quoting_overhead("\"open"), Err(CountErrorCode::InvalidArgument as i32));
+    }
+
+    /// Uppercases the file named by the `CStr` behind `context` and keeps every other file.
+    unsafe extern "C" fn uppercase_one(filename: *const c_char, content: *const c_char, len: usize, context: *mut c_void) -> *mut c_char {
+        if CStr::from_ptr(filename) != CStr::from_ptr(context as *const c_char) {
+            return std::ptr::null_mut();
+        }
+        let content = std::str::from_utf8(std::slice::from_raw_parts(content as *const u8, len)).unwrap();
+        let upper = CString::new(content.to_uppercase()).unwrap();
+        crate::count_string_dup(upper.as_ptr())
+    }
+
+    #[test]
+    fn a_transform_can_replace_one_file_and_keep_the_rest() {
+        const FILES: Files = &[("a.md", "first\n"), ("b.md", "naïve\n"), ("c.md", "last\n")];
+        let target = CString::new("b.md").unwrap();
+        let options = MergeOptions {
+            transform: Some(uppercase_one),
+            transform_context: target.as_ptr() as *mut c_void,
+            with_manifest: true,
+            ..MergeOptions::default()
+        };
+        let (merged, spans) = super::merge_files_from("a.md, b.md, c.md", &options, &in_memory(FILES)).unwrap();
+        assert_eq!(merged, "first\nNAÏVE\nlast\n");
+        assert_eq!(spans.iter().map(|span| span.len).collect::<Vec<_>>(), [6, 7, 5]);
+    }
+
+    fn header_and_rows(csv: &str, delimiter: u8) -> Result<(u64, u64), i32> {
+        let csv = CString::new(csv).unwrap();
+        let (mut columns, mut rows) = (u64::MAX, u64::MAX);
+        match ffi::csv_header_and_row_count(csv.as_ptr(), delimiter as c_char, &mut columns, &mut rows) {
+            0 => Ok((columns, rows)),
+            status => Err(status),
+        }
+    }
+
+    #[test]
+    fn counts_header_columns_and_data_rows() {
+        assert_eq!(header_and_rows("name,\"pages, total\",words\na,1,2\n\nb,3,4\nc,5,6\n", b','), Ok((3, 3)));
+        assert_eq!(header_and_rows("a;b\n1;2", b';'), Ok((2, 1)));
+        assert_eq!(header_and_rows("only,header\n", b','), Ok((2, 0)));
+        assert_eq!(header_and_rows("", b','), Ok((0, 0)));
+        assert_eq!(header_and_rows("\"open,header\n", b','), Err(CountErrorCode::InvalidArgument as i32));
+    }
+
+    fn max_field_width(csv: &str, delimiter: u8) -> (u64, u64) {
+        let csv = CString::new(csv).unwrap();
+        let (mut count, mut width) = (u64::MAX, u64::MAX);
+        assert_eq!(ffi::csv_max_field_width(csv.as_ptr(), delimiter as c_char, &mut count, &mut width), CountErrorCode::Ok as i32);
+        (count, width)
+    }
+
+    #[test]
+    fn reports_the_widest_trimmed_value() {
+        assert_eq!(max_field_width("a, bbbb, cc", b','), (3, 4));
+        assert_eq!(max_field_width("naïve|  x  ", b'|'), (2, 5));
+        // Quotes are counted as characters.
+        assert_eq!(max_field_width("\"a,b\"", b','), (2, 2));
+        assert_eq!(max_field_width("", b','), (1, 0));
+    }
+
+    fn count_excluding(csv: &str, delimiter: u8, stop_values: &str) -> u64 {
+        let (csv, stop_values) = (CString::new(csv).unwrap(), CString::new(stop_values).unwrap());
+        let mut count = u64::MAX;
+        let status = ffi::csv_count_excluding(csv.as_ptr(), delimiter as c_char, stop_values.as_ptr(), &mut count);
+        assert_eq!(status, CountErrorCode::Ok as i32);
+        count
+    }
+
+    #[test]
+    fn stop_values_are_left_out_of_the_count() {
+        assert_eq!(count_excluding("a, NA, b, null", b',', "NA,null"), 2);
+        assert_eq!(count_excluding("a, NA, b, null", b',', ""), 4);
+        assert_eq!(count_excluding("a;;b; ", b';', "NA"), 4);
+        // An empty stop value excludes empty fields.
+        assert_eq!(count_excluding("a;;b; ", b';', "NA,,null"), 2);
+        assert_eq!(count_excluding("na, NA", b',', " NA "), 1);
+    }
+
+    fn aligned(csv: &str, delimiter: u8) -> Option<String> {
+        let csv = CString::new(csv).unwrap();
+        let aligned = ffi::csv_align(csv.as_ptr(), delimiter as c_char);
+        if aligned.is_null() {
+            return None;
+        }
+        let copy = unsafe { CStr::from_ptr(aligned) }.to_str().unwrap().to_owned();
+        crate::count_string_free(aligned);
+        Some(copy)
+    }
+
+    #[test]
+    fn aligns_columns_to_the_widest_value() {
+        let csv = "name,pages,words\nintro,3,900\n\"naïve, café\",12\n";
+        let expected = "\
+name         pages  words
+intro        3      900
+naïve, café  12
+";
+        assert_eq!(aligned(csv, b',').as_deref(), Some(expected));
+        assert_eq!(aligned("a\tb\n", b'\t').as_deref(), Some("a  b\n"));
+        assert_eq!(aligned("", b',').as_deref(), Some(""));
+        assert_eq!(aligned("\"open,b\n", b','), None);
+    }
+
+    unsafe extern "C" fn collect_record(fields: *const *const c_char, field_count: usize, context: *const c_void) {
+        let records = &mut *(context as *mut Vec<Vec<String>>);
+        let fields = std::slice::from_raw_parts(fields, field_count);
+        records.push(fields.iter().map(|&field| CStr::from_ptr(field).to_str().unwrap().to_owned()).collect());
+    }
+
+    #[test]
+    fn streams_each_record_of_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.csv");
+        std::fs::write(&path, "name,count\r\n\"a,b\",1\r\n\r\nsolo\nx,y,z").unwrap();
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+        let mut records: Vec<Vec<String>> = Vec::new();
+        let context = &mut records as *mut Vec<Vec<String>> as *const c_void;
+        assert_eq!(ffi::csv_stream_from_path(path.as_ptr(), b',' as c_char, collect_record, context), CountErrorCode::Ok as i32);
+        assert_eq!(records.iter().map(Vec::len).collect::<Vec<_>>(), [2, 2, 1, 3]);
+        assert_eq!(records[1], ["a,b", "1"]);
+        assert_eq!(records[3], ["x", "y", "z"]);
+    }
+
+    #[test]
+    fn streaming_a_missing_file_never_calls_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = CString::new(dir.path().join("missing.csv").to_str().unwrap()).unwrap();
+        let mut records: Vec<Vec<String>> = Vec::new();
+        let context = &mut records as *mut Vec<Vec<String>> as *const c_void;
+        let status = ffi::csv_stream_from_path(path.as_ptr(), b',' as c_char, collect_record, context);
+        assert_eq!((status, records.len()), (CountErrorCode::FileNotFound as i32, 0));
+    }
+
+    fn trailing_empty(csv: &str, delimiter: u8) -> i32 {
+        let csv = CString::new(csv).unwrap();
+        ffi::csv_has_trailing_empty(csv.as_ptr(), delimiter as c_char)
+    }
+
+    #[test]
+    fn flags_a_first_row_ending_in_a_delimiter() {
+        assert_eq!(trailing_empty("a,b,", b','), 1);
+        assert_eq!(trailing_empty("a,b", b','), 0);
+        assert_eq!(trailing_empty("a,b,  \r\nc,d", b','), 1);
+        assert_eq!(trailing_empty("a,b\nc,", b','), 0);
+        assert_eq!(trailing_empty("a;b;", b';'), 1);
+        assert_eq!(trailing_empty("a;b;", b','), 0);
+        assert_eq!(ffi::csv_has_trailing_empty(std::ptr::null(), b',' as c_char), -1);
+    }
+
+    #[test]
+    fn non_ascii_delimiters_and_quotes_are_rejected() {
+        // 0xE9 is `é` in Latin-1, which must not be mistaken for the two UTF-8 bytes of `é`.
+        let csv = CString::new("aéb,c").unwrap();
+        let e_acute = 0xE9u8 as c_char;
+        let mut count = u64::MAX;
+        let status = ffi::csv_count_fields_quoted(csv.as_ptr(), e_acute, b'"' as c_char, &mut count);
+        assert_eq!((status, count), (CountErrorCode::InvalidArgument as i32, u64::MAX));
+        assert!(error::message_for(CountErrorCode::InvalidArgument).contains("0xE9"));
+        let status = ffi::csv_count_fields_quoted(csv.as_ptr(), b',' as c_char, e_acute, &mut count);
+        assert_eq!(status, CountErrorCode::InvalidArgument as i32);
+        assert_eq!(ffi::csv_count_fields_quoted(csv.as_ptr(), b',' as c_char, b'"' as c_char, &mut count), 0);
+        assert_eq!(count, 2);
+        assert_eq!(ffi::csv_has_trailing_empty(csv.as_ptr(), e_acute), -1);
+        assert!(ffi::csv_common_prefix(csv.as_ptr(), e_acute).is_null());
+        assert!(ffi::csv_align(csv.as_ptr(), e_acute).is_null());
+    }
+
+    #[test]
+    fn null_columns_with_a_nonzero_count_return_null() {
+        let csv = CString::new(BOOKS).unwrap();
+        assert!(ffi::csv_select_columns(csv.as_ptr(), std::ptr::null(), 2).is_null());
+        assert_eq!(error::message_for(CountErrorCode::NullPointer), "columns is NULL but n_columns is not 0.");
     }
-    merged
 }
@@ -0,0 +1,232 @@
+mod ffi {
+    use std::os::raw::c_char;
+    use std::path::Path;
+
+    use crate::modules::error;
+    use crate::modules::file;
+
+    #[no_mangle]
+    pub extern "C" fn count_file_digest(path: *const c_char, out: *mut [u8; 32]) -> i32 {
+        error::status(|| {
+            let path = unsafe { error::str_from_ptr(path) }?;
+            let mut hasher = super::Sha256::new();
+            file::read_chunks(Path::new(path), |chunk| {
+                hasher.update(chunk);
+                Ok(())
+            })?;
+            unsafe { error::write_out(out, hasher.finalize()) }
+        })
+    }
+
+    /// SHA-256 of the bytes before the NUL terminator, whatever their encoding, so it matches
+    /// `count_file_digest` of a file holding the same bytes.
+    #[no_mangle]
+    pub extern "C" fn count_text_digest(text: *const c_char, out: *mut [u8; 32]) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::bytes_from_ptr(text) }?;
+            unsafe { error::write_out(out, super::sha256(text)) }
+        })
+    }
+}
+
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Lowercase hex, two digits per byte.
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub struct Sha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    block_len: usize,
+    total_len: u64,
+}
+
+impl Sha256 {
+    pub fn new() -> Self {
+        Sha256 { state: INITIAL_STATE, block: [0; 64], block_len: 0, total_len: 0 }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+        while !data.is_empty() {
+            let take = (64 - self.block_len).min(data.len());
+            self.block[self.block_len..self.block_len + take].copy_from_slice(&data[..take]);
+            self.block_len += take;
+            data = &data[take..];
+            if self.block_len == 64 {
+                let block = self.block;
+                self.compress(&block);
+                self.block_len = 0;
+            }
+        }
+    }
+
+    pub fn finalize(mut self) -> [u8; 32] {
+        let bit_len = self.total_len.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.block_len != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bit_len.to_be_bytes());
+
+        let mut digest = [0; 32];
+        for (bytes, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        let mut schedule = [0u32; 64];
+        for (word, bytes) in schedule.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes(bytes.try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 = schedule[i - 15].rotate_right(7) ^ schedule[i - 15].rotate_right(18) ^ (schedule[i - 15] >> 3);
+            let s1 = schedule[i - 2].rotate_right(17) ^ schedule[i - 2].rotate_right(19) ^ (schedule[i - 2] >> 10);
+            schedule[i] = schedule[i - 16].wrapping_add(s0).wrapping_add(schedule[i - 7]).wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let temp1 = h.wrapping_add(s1).wrapping_add(choice).wrapping_add(ROUND_CONSTANTS[i]).wrapping_add(schedule[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use super::{ffi, sha256, to_hex, Sha256};
+    use crate::modules::error::CountErrorCode;
+
+    /// `(input, SHA-256)` pairs: the FIPS 180-2 examples and a common pangram.
+    const VECTORS: [(&str, &str); 4] = [
+        ("", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+        ("abc", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+        (
+            "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+        ),
+        ("The quick brown fox jumps over the lazy dog", "d7a8fbb307d7809469ca9abcb0082e4f8d5651e46d3cdb762d02d0bf37c9e592"),
+    ];
+
+    /// Runs of `a` around the 56-byte padding boundary, and one spanning several blocks.
+    const REPEATED_A: [(usize, &str); 4] = [
+        (55, "9f4390f8d30c2dd92ec9f095b65e2b9ae9b0a925a5258e241c9f1e910f734318"),
+        (56, "b35439a4ac6f0948b6d6f9e3c6af0f5f590ce20f1bde7090ef7970686ec6738a"),
+        (64, "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb"),
+        (199, "60048478ae47edd7ef18f1235afd254a72ffaf32c4bc5726e8d250c3be51e3cb"),
+    ];
+
+    #[test]
+    fn sha256_matches_known_vectors() {
+        for (input, expected) in VECTORS {
+            assert_eq!(to_hex(&sha256(input.as_bytes())), expected, "{input:?}");
+        }
+        for (len, expected) in REPEATED_A {
+            assert_eq!(to_hex(&sha256("a".repeat(len).as_bytes())), expected, "{len} times 'a'");
+        }
+    }
+
+    #[test]
+    fn sha256_of_a_million_a() {
+        let digest = sha256(&vec![b'a'; 1_000_000]);
+        assert_eq!(to_hex(&digest), "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+    }
+
+    #[test]
+    fn updates_in_pieces_match_one_update() {
+        let data = "a".repeat(199);
+        for split in 0..=data.len() {
+            let mut hasher = Sha256::new();
+            hasher.update(&data.as_bytes()[..split]);
+            hasher.update(&data.as_bytes()[split..]);
+            assert_eq!(hasher.finalize(), sha256(data.as_bytes()), "split at {split}");
+        }
+    }
+
+    fn file_digest(path: &std::path::Path) -> Result<String, i32> {
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+        let mut digest = [0; 32];
+        match ffi::count_file_digest(path.as_ptr(), &mut digest) {
+            0 => Ok(to_hex(&digest)),
+            status => Err(status),
+        }
+    }
+
+    #[test]
+    fn file_digests_match_text_digests() {
+        let dir = tempfile::tempdir().unwrap();
+        let empty = dir.path().join("empty.md");
+        std::fs::write(&empty, "").unwrap();
+        assert_eq!(file_digest(&empty).as_deref(), Ok(VECTORS[0].1));
+
+        let abc = dir.path().join("abc.md");
+        std::fs::write(&abc, "abc").unwrap();
+        assert_eq!(file_digest(&abc).as_deref(), Ok(VECTORS[1].1));
+
+        let mut digest = [0; 32];
+        assert_eq!(ffi::count_text_digest(CString::new("abc").unwrap().as_ptr(), &mut digest), 0);
+        assert_eq!(to_hex(&digest), VECTORS[1].1);
+    }
+
+    #[test]
+    fn a_missing_file_has_no_digest() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(file_digest(&dir.path().join("missing.md")), Err(CountErrorCode::FileNotFound as i32));
+    }
+
+    #[test]
+    fn text_digests_hash_bytes_that_are_not_utf8() {
+        let latin1 = b"caf\xe9\xff";
+        let mut digest = [0; 32];
+        assert_eq!(ffi::count_text_digest(CString::new(latin1.to_vec()).unwrap().as_ptr(), &mut digest), 0);
+        assert_eq!(to_hex(&digest), "5b1ac3b29933212f535dec84974f0fbf103c1c8f31377665f948428f2663168a");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("latin1.md");
+        std::fs::write(&path, latin1).unwrap();
+        assert_eq!(file_digest(&path), Ok(to_hex(&digest)));
+        assert_eq!(ffi::count_text_digest(std::ptr::null(), &mut digest), CountErrorCode::NullPointer as i32);
+    }
+}
@@ -0,0 +1,373 @@
+mod ffi {
+    use std::ffi::c_void;
+    use std::os::raw::c_char;
+    use std::ptr;
+    use std::sync::atomic::Ordering;
+
+    use crate::modules::buffer::StrView;
+
+    /// In strict mode every warning also fails the operation that raised it, with the error code
+    /// for its class. The warning callback still fires first.
+    #[no_mangle]
+    pub extern "C" fn set_strict_mode(enabled: bool) {
+        super::STRICT.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Passing NULL removes the callback. Warnings are dropped while no callback is set.
+    #[no_mangle]
+    pub extern "C" fn set_warning_callback(
+        callback: Option<unsafe extern "C" fn(message: *const c_char, context: *mut c_void)>,
+        context: *mut c_void,
+    ) {
+        *super::WARNING_CALLBACK.lock().unwrap() = callback.map(|callback| (callback, context as usize));
+    }
+
+    #[no_mangle]
+    pub extern "C" fn last_error_code() -> i32 {
+        super::LAST_ERROR.with(|last| last.borrow().as_ref().map_or(0, |(code, _)| *code as i32))
+    }
+
+    /// The returned message is owned by the library and stays valid until the next failing call on
+    /// this thread. Inside a callback, a message from a nested call also ends when the call that
+    /// invoked the callback returns.
+    #[no_mangle]
+    pub extern "C" fn last_error_message() -> *const c_char {
+        super::LAST_ERROR.with(|last| {
+            last.borrow().as_ref().map_or(ptr::null(), |(_, message)| message.as_ptr())
+        })
+    }
+
+    /// Same lifetime as `last_error_message`; empty when no error has been recorded.
+    #[no_mangle]
+    pub extern "C" fn last_error_message_view() -> StrView {
+        super::LAST_ERROR.with(|last| match last.borrow().as_ref() {
+            Some((_, message)) => StrView::new(message.to_str().unwrap()),
+            None => StrView::new(""),
+        })
+    }
+
+    /// Static description of a `CountErrorCode` value, valid for the lifetime of the program.
+    #[no_mangle]
+    pub extern "C" fn count_error_message(code: i32) -> StrView {
+        StrView::new(super::describe(code))
+    }
+
+    /// NUL-terminated form of `count_error_message`. Takes a plain integer because C may pass any
+    /// value; unknown codes get a generic description.
+    #[no_mangle]
+    pub extern "C" fn error_code_message(code: i32) -> *const c_char {
+        super::describe_with_nul(code).as_ptr().cast()
+    }
+}
+
+use crate::modules::limits;
+use std::cell::RefCell;
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+type WarningCallback = unsafe extern "C" fn(message: *const c_char, context: *mut c_void);
+
+static WARNING_CALLBACK: Mutex<Option<(WarningCallback, usize)>> = Mutex::new(None);
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+type LastError = Option<(CountErrorCode, CString)>;
+
+thread_local! {
+    static LAST_ERROR: RefCell<LastError> = const { RefCell::new(None) };
+    /// One pending error per `status` call in progress on this thread, innermost last. A callback
+    /// that re-enters the library gets its own scope, so its failures never stand in for the
+    /// outer call's.
+    static SCOPES: RefCell<Vec<LastError>> = const { RefCell::new(Vec::new()) };
+}
+
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CountErrorCode {
+    Ok,
+    NullPointer,
+    InvalidUtf8,
+    CallbackPanicked,
+    FileNotFound,
+    PathOutsideRoot,
+    Io,
+    InvalidArgument,
+    BufferTooSmall,
+    Cancelled,
+    ListCycle,
+    ListTooDeep,
+    FileTooLarge,
+    UnknownMetric,
+    LimitExceeded,
+    UnknownKey,
+    TimedOut,
+    SelfTestFailed,
+}
+
+impl CountErrorCode {
+    const ALL: [CountErrorCode; 18] = [
+        CountErrorCode::Ok,
+        CountErrorCode::NullPointer,
+        CountErrorCode::InvalidUtf8,
+        CountErrorCode::CallbackPanicked,
+        CountErrorCode::FileNotFound,
+        CountErrorCode::PathOutsideRoot,
+        CountErrorCode::Io,
+        CountErrorCode::InvalidArgument,
+        CountErrorCode::BufferTooSmall,
+        CountErrorCode::Cancelled,
+        CountErrorCode::ListCycle,
+        CountErrorCode::ListTooDeep,
+        CountErrorCode::FileTooLarge,
+        CountErrorCode::UnknownMetric,
+        CountErrorCode::LimitExceeded,
+        CountErrorCode::UnknownKey,
+        CountErrorCode::TimedOut,
+        CountErrorCode::SelfTestFailed,
+    ];
+
+    pub fn from_code(code: i32) -> Option<CountErrorCode> {
+        CountErrorCode::ALL.get(usize::try_from(code).ok()?).copied()
+    }
+
+    pub fn message(self) -> &'static str {
+        let message = self.message_with_nul();
+        &message[..message.len() - 1]
+    }
+
+    fn message_with_nul(self) -> &'static str {
+        match self {
+            CountErrorCode::Ok => "No error.\0",
+            CountErrorCode::NullPointer => "A required pointer argument was NULL.\0",
+            CountErrorCode::InvalidUtf8 => "Input is not valid UTF-8.\0",
+            CountErrorCode::CallbackPanicked => "A callback panicked.\0",
+            CountErrorCode::FileNotFound => "File not found.\0",
+            CountErrorCode::PathOutsideRoot => "File is outside the content root.\0",
+            CountErrorCode::Io => "An I/O error occurred.\0",
+            CountErrorCode::InvalidArgument => "An argument was invalid.\0",
+            CountErrorCode::BufferTooSmall => "The output buffer is too small.\0",
+            CountErrorCode::Cancelled => "The operation was cancelled.\0",
+            CountErrorCode::ListCycle => "CSV lists reference each other in a cycle.\0",
+            CountErrorCode::ListTooDeep => "CSV lists are nested too deeply.\0",
+            CountErrorCode::FileTooLarge => "A file exceeds the size limit.\0",
+            CountErrorCode::UnknownMetric => "No metric has that name.\0",
+            CountErrorCode::LimitExceeded => "A configured input limit was exceeded.\0",
+            CountErrorCode::UnknownKey => "An unknown key was found (strict mode).\0",
+            CountErrorCode::TimedOut => "The operation timed out.\0",
+            CountErrorCode::SelfTestFailed => "A self-test stage failed.\0",
+        }
+    }
+}
+
+const UNKNOWN_CODE_MESSAGE: &str = "Unknown error code.\0";
+
+fn describe(code: i32) -> &'static str {
+    let message = describe_with_nul(code);
+    &message[..message.len() - 1]
+}
+
+fn describe_with_nul(code: i32) -> &'static str {
+    CountErrorCode::from_code(code).map_or(UNKNOWN_CODE_MESSAGE, CountErrorCode::message_with_nul)
+}
+
+pub fn fail<T>(code: CountErrorCode, message: impl Into<String>) -> Result<T, CountErrorCode> {
+    let message = CString::new(message.into().replace('\0', "")).unwrap();
+    SCOPES.with(|scopes| {
+        if let Some(pending) = scopes.borrow_mut().last_mut() {
+            *pending = Some((code, message.clone()));
+        }
+    });
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some((code, message)));
+    Err(code)
+}
+
+/// Failures that did not go through `fail` record the code's default message, so
+/// `last_error_message` always describes the most recent failure. A nested call that fails and
+/// is handled inside `operation` is visible to its caller but gone once `operation` returns.
+pub fn status(operation: impl FnOnce() -> Result<(), CountErrorCode>) -> i32 {
+    let mut scope = ErrorScope::enter();
+    match operation() {
+        Ok(()) => CountErrorCode::Ok as i32,
+        Err(code) => {
+            scope.fail(code);
+            code as i32
+        }
+    }
+}
+
+/// For exports that return a count: the count from `operation`, or its failure code negated,
+/// with the failure recorded as `status` records it.
+pub fn count_or_code(operation: impl FnOnce() -> Result<u64, CountErrorCode>) -> i64 {
+    let mut count = 0;
+    let code = status(|| {
+        count = operation()?;
+        Ok(())
+    });
+    if code == CountErrorCode::Ok as i32 {
+        count as i64
+    } else {
+        -i64::from(code)
+    }
+}
+
+/// Pops its scope on drop, including when `operation` panics or returns early, and publishes
+/// either this call's own failure or whatever was current when it started.
+struct ErrorScope {
+    previous: LastError,
+    outcome: LastError,
+}
+
+impl ErrorScope {
+    fn enter() -> ErrorScope {
+        SCOPES.with(|scopes| scopes.borrow_mut().push(None));
+        ErrorScope { previous: LAST_ERROR.with(|last| last.borrow_mut().take()), outcome: None }
+    }
+
+    fn fail(&mut self, code: CountErrorCode) {
+        let pending = SCOPES.with(|scopes| scopes.borrow_mut().last_mut().and_then(Option::take));
+        // A message recorded for a different code belongs to a failure that was recovered from.
+        let message = match pending {
+            Some((pending_code, message)) if pending_code == code => message,
+            _ => CString::new(code.message()).unwrap(),
+        };
+        self.outcome = Some((code, message));
+    }
+}
+
+impl Drop for ErrorScope {
+    fn drop(&mut self) {
+        SCOPES.with(|scopes| scopes.borrow_mut().pop());
+        let published = self.outcome.take().or_else(|| self.previous.take());
+        LAST_ERROR.with(|last| *last.borrow_mut() = published);
+    }
+}
+
+/// The recorded message when the last failure on this thread was `code`, otherwise the code's
+/// default message.
+pub fn message_for(code: CountErrorCode) -> String {
+    LAST_ERROR.with(|last| match last.borrow().as_ref() {
+        Some((last_code, message)) if *last_code == code => message.to_string_lossy().into_owned(),
+        _ => code.message().to_owned(),
+    })
+}
+
+pub fn guard_callback<T>(callback: impl FnOnce() -> T) -> Result<T, CountErrorCode> {
+    panic::catch_unwind(AssertUnwindSafe(callback)).map_err(|_| CountErrorCode::CallbackPanicked)
+}
+
+pub unsafe fn str_from_ptr<'a>(text: *const c_char) -> Result<&'a str, CountErrorCode> {
+    std::str::from_utf8(bytes_from_ptr(text)?).map_err(|_| CountErrorCode::InvalidUtf8)
+}
+
+/// The bytes before the NUL terminator, without any encoding check.
+pub unsafe fn bytes_from_ptr<'a>(text: *const c_char) -> Result<&'a [u8], CountErrorCode> {
+    if text.is_null() {
+        return Err(CountErrorCode::NullPointer);
+    }
+    let bytes = CStr::from_ptr(text).to_bytes();
+    limits::check_text_bytes(bytes.len())?;
+    Ok(bytes)
+}
+
+pub unsafe fn write_out<T>(out: *mut T, value: T) -> Result<(), CountErrorCode> {
+    if out.is_null() {
+        return Err(CountErrorCode::NullPointer);
+    }
+    out.write(value);
+    Ok(())
+}
+
+/// Reports `message` to the warning callback. In strict mode the warning then fails with `code`,
+/// which identifies the warning's class.
+pub fn warn(code: CountErrorCode, message: &str) -> Result<(), CountErrorCode> {
+    let callback = *WARNING_CALLBACK.lock().unwrap();
+    if let Some((callback, context)) = callback {
+        let message = CString::new(message.replace('\0', "")).unwrap();
+        unsafe { callback(message.as_ptr(), context as *mut c_void) };
+    }
+    if STRICT.load(Ordering::Relaxed) {
+        return fail(code, message);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ffi, CountErrorCode};
+    use crate::modules::buffer::StrView;
+
+    fn read(view: StrView) -> String {
+        let bytes = unsafe { std::slice::from_raw_parts(view.ptr as *const u8, view.len) };
+        std::str::from_utf8(bytes).unwrap().to_owned()
+    }
+
+    #[test]
+    fn views_read_the_messages_they_point_at() {
+        assert_eq!(read(ffi::count_error_message(CountErrorCode::NullPointer as i32)), CountErrorCode::NullPointer.message());
+        super::status(|| super::fail(CountErrorCode::Io, "disk on fire"));
+        assert_eq!(read(ffi::last_error_message_view()), "disk on fire");
+    }
+
+    #[test]
+    fn every_code_has_its_own_message() {
+        assert_eq!(CountErrorCode::ALL.len(), CountErrorCode::SelfTestFailed as usize + 1);
+        let mut seen = std::collections::HashSet::new();
+        for code in CountErrorCode::ALL {
+            assert_eq!(CountErrorCode::from_code(code as i32), Some(code));
+            assert!(seen.insert(code.message()), "{code:?} repeats a message");
+            let c_message = unsafe { std::ffi::CStr::from_ptr(ffi::error_code_message(code as i32)) };
+            assert_eq!(c_message.to_str().unwrap(), code.message());
+            assert_eq!(read(ffi::count_error_message(code as i32)), code.message());
+        }
+    }
+
+    #[test]
+    fn unknown_codes_get_a_generic_message() {
+        for code in [-1, CountErrorCode::ALL.len() as i32, i32::MAX] {
+            assert_eq!(read(ffi::count_error_message(code)), "Unknown error code.");
+            let c_message = unsafe { std::ffi::CStr::from_ptr(ffi::error_code_message(code)) };
+            assert_eq!(c_message.to_str().unwrap(), "Unknown error code.");
+        }
+    }
+
+    fn last_message() -> String {
+        read(ffi::last_error_message_view())
+    }
+
+    /// Stands in for a C callback that calls a failing export, and records what it saw afterwards.
+    unsafe extern "C" fn nested_failure(context: *mut std::ffi::c_void) {
+        let seen = &mut *(context as *mut (i32, String));
+        super::status(|| super::fail(CountErrorCode::FileNotFound, "inner"));
+        *seen = (ffi::last_error_code(), last_message());
+    }
+
+    /// An export that calls `callback` and then finishes with `outcome`.
+    fn with_callback(callback: unsafe extern "C" fn(*mut std::ffi::c_void), outcome: Result<(), CountErrorCode>) -> (i32, (i32, String)) {
+        let mut seen = (0, String::new());
+        let code = super::status(|| {
+            unsafe { callback(&mut seen as *mut (i32, String) as *mut std::ffi::c_void) };
+            outcome
+        });
+        (code, seen)
+    }
+
+    #[test]
+    fn nested_failures_stay_inside_the_callback() {
+        super::status(|| super::fail(CountErrorCode::Io, "earlier"));
+
+        let (code, seen) = with_callback(nested_failure, Ok(()));
+        assert_eq!(seen, (CountErrorCode::FileNotFound as i32, "inner".to_owned()));
+        assert_eq!(code, CountErrorCode::Ok as i32);
+        assert_eq!((ffi::last_error_code(), last_message()), (CountErrorCode::Io as i32, "earlier".to_owned()));
+
+        // The outer call's failure gets its own message, even with the same code as the nested one.
+        for outer in [CountErrorCode::InvalidArgument, CountErrorCode::FileNotFound] {
+            let (code, _) = with_callback(nested_failure, Err(outer));
+            assert_eq!(code, outer as i32);
+            assert_eq!((ffi::last_error_code(), last_message()), (outer as i32, outer.message().to_owned()));
+        }
+    }
+}
@@ -1,17 +1,637 @@
+mod ffi {
+    use std::ffi::c_void;
+    use std::os::raw::c_char;
+    use std::path::{Path, PathBuf};
+    use std::ptr;
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    use super::LoadedFile;
+    use crate::modules::error::{self, CountErrorCode};
+    use crate::modules::limits;
+    use crate::modules::stats::{self, CountOptions, TextStats};
+
+    #[no_mangle]
+    pub extern "C" fn set_content_root(path: *const c_char) -> i32 {
+        error::status(|| {
+            let root = if path.is_null() {
+                None
+            } else {
+                Some(PathBuf::from(unsafe { error::str_from_ptr(path) }?))
+            };
+            super::set_content_root(root).or_else(|_| {
+                error::fail(CountErrorCode::FileNotFound, "Content root does not exist.")
+            })
+        })
+    }
+
+    /// Counting stops with `CountErrorCode_Cancelled` once the byte behind `cancel` becomes
+    /// non-zero; it is checked before every chunk. A NULL `cancel` never cancels.
+    #[no_mangle]
+    pub extern "C" fn count_file_bytes(path: *const c_char, cancel: *const u8, out: *mut u64) -> i32 {
+        error::status(|| {
+            let path = unsafe { error::str_from_ptr(path) }?;
+            let cancel = unsafe { (cancel as *const AtomicU8).as_ref() };
+            let is_cancelled = || cancel.is_some_and(|flag| flag.load(Ordering::Acquire) != 0);
+            if is_cancelled() {
+                return error::fail(CountErrorCode::Cancelled, "Counting was cancelled.");
+            }
+            let mut bytes = 0;
+            super::read_chunks(Path::new(path), |chunk| {
+                bytes += chunk.len() as u64;
+                if is_cancelled() {
+                    return error::fail(CountErrorCode::Cancelled, "Counting was cancelled.");
+                }
+                Ok(())
+            })?;
+            unsafe { error::write_out(out, bytes) }
+        })
+    }
+
+    /// Line count of the file at `path`, read in chunks so it never has to fit in memory, or -1
+    /// on failure with the cause in `last_error_code` and `last_error_message`. A non-NULL
+    /// `progress` is called every 16 MiB and once at the end with `bytes_done == bytes_total`.
+    #[no_mangle]
+    pub extern "C" fn count_lines_in_file(
+        path: *const c_char,
+        progress: Option<unsafe extern "C" fn(bytes_done: u64, bytes_total: u64, context: *mut c_void)>,
+        context: *mut c_void,
+    ) -> i64 {
+        let mut lines = 0;
+        let status = error::status(|| {
+            let path = unsafe { error::str_from_ptr(path) }?;
+            lines = super::count_lines(Path::new(path), |done, total| match progress {
+                Some(progress) => error::guard_callback(|| unsafe { progress(done, total, context) }),
+                None => Ok(()),
+            })?;
+            Ok(())
+        });
+        if status == CountErrorCode::Ok as i32 {
+            lines as i64
+        } else {
+            -1
+        }
+    }
+
+    /// `count_all` over the file at `path`, decoded as `options->encoding` (NULL options count
+    /// with the defaults). `options->timeout_ms` is checked after every chunk read.
+    #[no_mangle]
+    pub extern "C" fn count_file_stats(
+        path: *const c_char,
+        options: *const CountOptions,
+        out: *mut TextStats,
+    ) -> i32 {
+        error::status(|| {
+            let path = unsafe { error::str_from_ptr(path) }?;
+            let options = unsafe { options.as_ref() }.copied().unwrap_or_default();
+            let deadline = limits::Deadline::after_ms(options.timeout_ms);
+            let mut contents = Vec::new();
+            super::read_chunks(Path::new(path), |chunk| {
+                deadline.check()?;
+                limits::check_text_bytes(contents.len() + chunk.len())?;
+                contents.extend_from_slice(chunk);
+                Ok(())
+            })?;
+            let stats = stats::count_encoded(&contents, &options)?;
+            unsafe { error::write_out(out, stats) }
+        })
+    }
+
+    /// Non-overlapping occurrences of `needle` in the file at `path`, searched in a read-only
+    /// memory mapping of the whole file instead of through a read buffer. An empty `needle`
+    /// fails with `CountErrorCode_InvalidArgument`. The file must not shrink while it is being
+    /// searched.
+    #[no_mangle]
+    pub extern "C" fn count_substring_mmap(
+        path: *const c_char,
+        needle: *const c_char,
+        out: *mut u64,
+    ) -> i32 {
+        error::status(|| {
+            let path = unsafe { error::str_from_ptr(path) }?;
+            let needle = unsafe { error::bytes_from_ptr(needle) }?;
+            if needle.is_empty() {
+                return error::fail(CountErrorCode::InvalidArgument, "Needle must not be empty.");
+            }
+            let count = super::with_mapped(Path::new(path), |bytes| {
+                memchr::memmem::find_iter(bytes, needle).count()
+            })?;
+            unsafe { error::write_out(out, count as u64) }
+        })
+    }
+
+    /// Reads the UTF-8 file at `path` once for repeated `file_chars`, `file_words` and
+    /// `file_lines` queries, each of which is counted on first use only. Returns NULL on failure
+    /// with the cause in `last_error_message`. A handle must not be used from two threads at
+    /// once. Close it with `file_close`.
+    #[no_mangle]
+    pub extern "C" fn file_open(path: *const c_char) -> *mut LoadedFile {
+        let mut opened = ptr::null_mut();
+        error::status(|| {
+            let path = unsafe { error::str_from_ptr(path) }?;
+            opened = Box::into_raw(Box::new(LoadedFile::open(Path::new(path))?));
+            Ok(())
+        });
+        opened
+    }
+
+    /// Characters in the file. Each metric returns 0 for a NULL handle.
+    #[no_mangle]
+    pub extern "C" fn file_chars(file: *mut LoadedFile) -> u64 {
+        unsafe { file.as_ref() }.map_or(0, LoadedFile::chars)
+    }
+
+    /// Whitespace-separated words.
+    #[no_mangle]
+    pub extern "C" fn file_words(file: *mut LoadedFile) -> u64 {
+        unsafe { file.as_ref() }.map_or(0, LoadedFile::words)
+    }
+
+    #[no_mangle]
+    pub extern "C" fn file_lines(file: *mut LoadedFile) -> u64 {
+        unsafe { file.as_ref() }.map_or(0, LoadedFile::lines)
+    }
+
+    #[no_mangle]
+    pub extern "C" fn file_close(file: *mut LoadedFile) {
+        if !file.is_null() {
+            drop(unsafe { Box::from_raw(file) });
+        }
+    }
+}
+
+use crate::modules::error::{self, CountErrorCode};
+use crate::modules::limits;
+use crate::modules::stats::{self, WordRule};
+use std::cell::OnceCell;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::{fs, io};
+
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Bytes between progress reports of `count_lines`, so a slow callback stays a small share of
+/// the scan.
+const PROGRESS_INTERVAL: u64 = 16 * 1024 * 1024;
+
+static CONTENT_ROOT: Mutex<Option<PathBuf>> = Mutex::new(None);
+
 pub struct File(String);
 
+/// Sees a byte count and fails if it is over a limit.
+pub type SizeCheck<'a> = dyn Fn(usize) -> Result<(), CountErrorCode> + 'a;
+
 impl File {
     pub fn to_str(&self) -> &str {
-        if self.0 == "chapter1.md" {
-            "# Getting started\n"
-        } else if self.0 == "chapter2.md" {
-            "# Wrapping up\n"
-        } else {
-            panic!("No content defined for file: {}", self.0);
+        &self.0
+    }
+
+    /// For tests that hand in-memory files to a `csv::FileReader`.
+    #[cfg(test)]
+    pub fn from_contents(contents: &str) -> File {
+        File(contents.to_owned())
+    }
+}
+
+/// Contents of a file behind a `file_open` handle, with each metric filled in on first query.
+pub struct LoadedFile {
+    text: String,
+    chars: OnceCell<u64>,
+    words: OnceCell<u64>,
+    lines: OnceCell<u64>,
+}
+
+impl LoadedFile {
+    fn open(path: &Path) -> Result<LoadedFile, CountErrorCode> {
+        let mut contents = Vec::new();
+        read_chunks(path, |chunk| {
+            limits::check_text_bytes(contents.len() + chunk.len())?;
+            contents.extend_from_slice(chunk);
+            Ok(())
+        })?;
+        match String::from_utf8(contents) {
+            Ok(text) => Ok(LoadedFile { text, chars: OnceCell::new(), words: OnceCell::new(), lines: OnceCell::new() }),
+            Err(_) => error::fail(CountErrorCode::InvalidUtf8, format!("File is not valid UTF-8: {}", path.display())),
         }
     }
+
+    fn chars(&self) -> u64 {
+        *self.chars.get_or_init(|| self.text.chars().count() as u64)
+    }
+
+    fn words(&self) -> u64 {
+        *self.words.get_or_init(|| stats::count_words(&self.text, WordRule::Whitespace))
+    }
+
+    /// Same rule as `TextStats::lines`.
+    fn lines(&self) -> u64 {
+        *self.lines.get_or_init(|| self.text.lines().count() as u64)
+    }
 }
 
 pub fn read_file(filename: &str) -> File {
-    File(filename.to_owned())
+    match fixture(filename) {
+        Some(contents) => File(contents.to_owned()),
+        None => panic!("No content defined for file: {}", filename),
+    }
+}
+
+/// Contents of the built-in files read when no content root is set.
+fn fixture(filename: &str) -> Option<&'static str> {
+    match filename {
+        "chapter1.md" => Some("# Getting started\n"),
+        "chapter2.md" => Some("# Wrapping up\n"),
+        _ => None,
+    }
+}
+
+pub fn read_file_checked(filename: &str) -> Result<File, CountErrorCode> {
+    read_file_checked_within(filename, &limits::check_text_bytes)
+}
+
+/// Like `read_file_checked`, but calls `check` with the file's size before reading it and with
+/// the bytes read so far after every chunk, so a file over a limit fails without being loaded
+/// whole.
+pub fn read_file_checked_within(filename: &str, check: &SizeCheck) -> Result<File, CountErrorCode> {
+    let root = CONTENT_ROOT.lock().unwrap().clone();
+    let root = match root {
+        Some(root) => root,
+        None => {
+            return match fixture(filename) {
+                Some(contents) => check(contents.len()).map(|()| File(contents.to_owned())),
+                None => error::fail(CountErrorCode::FileNotFound, format!("File not found: {filename}")),
+            }
+        }
+    };
+
+    let path = match fs::canonicalize(root.join(filename)) {
+        Ok(path) => path,
+        Err(_) => return error::fail(CountErrorCode::FileNotFound, format!("File not found: {filename}")),
+    };
+    if !path.starts_with(&root) {
+        return error::fail(
+            CountErrorCode::PathOutsideRoot,
+            format!("File is outside the content root: {filename}"),
+        );
+    }
+    let file = open(&path)?;
+    let size = file.metadata().map_or(0, |metadata| usize::try_from(metadata.len()).unwrap_or(usize::MAX));
+    check(size)?;
+    let mut contents = Vec::with_capacity(size);
+    read_chunks_from(file, &path, |chunk| {
+        // The file may have grown since its size was checked.
+        check(contents.len() + chunk.len())?;
+        contents.extend_from_slice(chunk);
+        Ok(())
+    })?;
+    match String::from_utf8(contents) {
+        Ok(contents) => Ok(File(contents)),
+        Err(_) => error::fail(CountErrorCode::InvalidUtf8, format!("File is not valid UTF-8: {filename}")),
+    }
+}
+
+/// Whether `filename` is a regular file, judged from its metadata alone. Under a content root
+/// this is whether `read_file_checked` would find it; without one, `filename` is a path of its
+/// own or one of the built-in fixtures.
+pub fn file_exists(filename: &str) -> bool {
+    let is_file = |path: &Path| fs::metadata(path).is_ok_and(|metadata| metadata.is_file());
+    let root = CONTENT_ROOT.lock().unwrap().clone();
+    match root {
+        Some(root) => fs::canonicalize(root.join(filename)).is_ok_and(|path| path.starts_with(&root) && is_file(&path)),
+        None => fixture(filename).is_some() || is_file(Path::new(filename)),
+    }
+}
+
+pub fn read_chunks(
+    path: &Path,
+    on_chunk: impl FnMut(&[u8]) -> Result<(), CountErrorCode>,
+) -> Result<(), CountErrorCode> {
+    read_chunks_from(open(path)?, path, on_chunk)
+}
+
+/// Lines as `TextStats::lines` counts them: one per `\n`, plus an unterminated last line.
+/// `progress` sees the bytes read so far after every `PROGRESS_INTERVAL` bytes, then once more
+/// when the whole file has been read, with both arguments equal to the bytes read.
+pub fn count_lines(
+    path: &Path,
+    mut progress: impl FnMut(u64, u64) -> Result<(), CountErrorCode>,
+) -> Result<u64, CountErrorCode> {
+    let file = open(path)?;
+    let total = file.metadata().map_or(0, |metadata| metadata.len());
+    let mut done = 0;
+    let mut next_report = PROGRESS_INTERVAL;
+    let mut newlines = 0;
+    let mut last = b'\n';
+    read_chunks_from(file, path, |chunk| {
+        newlines += memchr::memchr_iter(b'\n', chunk).count() as u64;
+        last = chunk[chunk.len() - 1];
+        done += chunk.len() as u64;
+        if done >= next_report {
+            next_report = done + PROGRESS_INTERVAL;
+            // The file may have grown since it was opened.
+            progress(done, total.max(done))?;
+        }
+        Ok(())
+    })?;
+    progress(done, done)?;
+    Ok(newlines + u64::from(last != b'\n'))
+}
+
+fn open(path: &Path) -> Result<fs::File, CountErrorCode> {
+    let display = path.display();
+    match fs::File::open(path) {
+        Ok(file) => Ok(file),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            error::fail(CountErrorCode::FileNotFound, format!("File not found: {display}"))
+        }
+        Err(err) => error::fail(CountErrorCode::Io, format!("Could not open file {display}: {err}")),
+    }
+}
+
+fn read_chunks_from(
+    mut file: fs::File,
+    path: &Path,
+    mut on_chunk: impl FnMut(&[u8]) -> Result<(), CountErrorCode>,
+) -> Result<(), CountErrorCode> {
+    let display = path.display();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        match file.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(len) => on_chunk(&buffer[..len])?,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return error::fail(CountErrorCode::Io, format!("Could not read file {display}: {err}")),
+        }
+    }
+}
+
+/// Calls `search` on the whole contents of the file at `path`, memory-mapped on Unix and read
+/// into memory elsewhere.
+pub fn with_mapped<T>(path: &Path, search: impl FnOnce(&[u8]) -> T) -> Result<T, CountErrorCode> {
+    let file = open(path)?;
+    #[cfg(unix)]
+    {
+        Ok(search(Mapping::new(&file, path)?.bytes()))
+    }
+    #[cfg(not(unix))]
+    {
+        let mut contents = Vec::new();
+        read_chunks_from(file, path, |chunk| {
+            contents.extend_from_slice(chunk);
+            Ok(())
+        })?;
+        Ok(search(&contents))
+    }
+}
+
+/// A private read-only mapping, unmapped on drop. Truncating the file while it is mapped makes
+/// reads past the new end raise SIGBUS.
+#[cfg(unix)]
+struct Mapping {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+#[cfg(unix)]
+impl Mapping {
+    fn new(file: &fs::File, path: &Path) -> Result<Mapping, CountErrorCode> {
+        use std::os::unix::io::AsRawFd;
+
+        let display = path.display();
+        let len = match file.metadata() {
+            Ok(metadata) => usize::try_from(metadata.len()).unwrap_or(usize::MAX),
+            Err(err) => return error::fail(CountErrorCode::Io, format!("Could not read file {display}: {err}")),
+        };
+        // mmap rejects empty mappings.
+        if len == 0 {
+            return Ok(Mapping { ptr: std::ptr::null_mut(), len });
+        }
+        let ptr = unsafe {
+            libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0)
+        };
+        if ptr == libc::MAP_FAILED {
+            let err = io::Error::last_os_error();
+            return error::fail(CountErrorCode::Io, format!("Could not map file {display}: {err}"));
+        }
+        Ok(Mapping { ptr, len })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe { libc::munmap(self.ptr, self.len) };
+        }
+    }
+}
+
+fn set_content_root(root: Option<PathBuf>) -> io::Result<()> {
+    let root = root.map(fs::canonicalize).transpose()?;
+    *CONTENT_ROOT.lock().unwrap() = root;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+    use std::fs;
+    use std::io::Write;
+    use std::sync::atomic::{AtomicU8, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{ffi, CHUNK_SIZE};
+    use crate::modules::error::CountErrorCode;
+    use crate::modules::stats::{CountOptions, TextEncoding, TextStats};
+
+    fn path_arg(path: &std::path::Path) -> CString {
+        CString::new(path.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn count_file_bytes_reads_every_chunk_when_not_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.txt");
+        fs::write(&path, vec![b'a'; CHUNK_SIZE * 2 + 10]).unwrap();
+        let cancel = AtomicU8::new(0);
+        let mut bytes = 0;
+        let status = ffi::count_file_bytes(path_arg(&path).as_ptr(), cancel.as_ptr(), &mut bytes);
+        assert_eq!((status, bytes), (CountErrorCode::Ok as i32, (CHUNK_SIZE * 2 + 10) as u64));
+    }
+
+    #[test]
+    fn count_file_bytes_cancels_before_the_first_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("small.txt");
+        fs::write(&path, "abc").unwrap();
+        let cancel = AtomicU8::new(1);
+        let mut bytes = u64::MAX;
+        let status = ffi::count_file_bytes(path_arg(&path).as_ptr(), cancel.as_ptr(), &mut bytes);
+        assert_eq!((status, bytes), (CountErrorCode::Cancelled as i32, u64::MAX));
+    }
+
+    /// Feeds a FIFO one chunk, raises the flag, then feeds another, so the count can only end
+    /// in cancellation however the reads are split.
+    #[cfg(unix)]
+    #[test]
+    fn count_file_bytes_stops_once_the_flag_is_set_after_the_first_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fifo");
+        let fifo = path_arg(&path);
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+        let cancel = Arc::new(AtomicU8::new(0));
+        let writer = {
+            let (path, cancel) = (path.clone(), Arc::clone(&cancel));
+            thread::spawn(move || {
+                let mut fifo = fs::OpenOptions::new().write(true).open(path).unwrap();
+                fifo.write_all(&[b'a'; 100]).unwrap();
+                cancel.store(1, Ordering::Release);
+                // The reader may already have stopped, closing its end.
+                let _ = fifo.write_all(&[b'b'; 100]);
+            })
+        };
+        let mut bytes = u64::MAX;
+        let status = ffi::count_file_bytes(fifo.as_ptr(), cancel.as_ptr(), &mut bytes);
+        writer.join().unwrap();
+        assert_eq!((status, bytes), (CountErrorCode::Cancelled as i32, u64::MAX));
+    }
+
+    fn file_stats(path: &std::path::Path, encoding: TextEncoding) -> Result<TextStats, i32> {
+        let path = path_arg(path);
+        let options = CountOptions { encoding, ..CountOptions::default() };
+        let mut stats = TextStats::default();
+        match ffi::count_file_stats(path.as_ptr(), &options, &mut stats) {
+            0 => Ok(stats),
+            status => Err(status),
+        }
+    }
+
+    #[test]
+    fn count_file_stats_honors_latin1() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("latin1.txt");
+        let mut contents: Vec<u8> = (0x80..=0xFF).collect();
+        contents.extend_from_slice(b" caf\xE9\n");
+        fs::write(&path, &contents).unwrap();
+
+        let stats = file_stats(&path, TextEncoding::Latin1).unwrap();
+        // 0x85 (next line) and 0xA0 (no-break space) are whitespace, so the high half holds three words.
+        assert_eq!(stats, TextStats { bytes: 134, chars: 134, words: 4, lines: 1 });
+        assert_eq!(file_stats(&path, TextEncoding::Utf8), Err(CountErrorCode::InvalidUtf8 as i32));
+    }
+
+    unsafe extern "C" fn record_progress(done: u64, total: u64, context: *mut std::ffi::c_void) {
+        let reports = &mut *(context as *mut Vec<(u64, u64)>);
+        reports.push((done, total));
+    }
+
+    fn lines_in_file(path: &std::path::Path) -> (i64, Vec<(u64, u64)>) {
+        let path = path_arg(path);
+        let mut reports: Vec<(u64, u64)> = Vec::new();
+        let context = &mut reports as *mut _ as *mut std::ffi::c_void;
+        let lines = ffi::count_lines_in_file(path.as_ptr(), Some(record_progress), context);
+        (lines, reports)
+    }
+
+    #[test]
+    fn count_lines_in_file_reads_100_mib_with_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large.txt");
+        let line = b"0123456789abcde\n";
+        let mut file = std::io::BufWriter::new(fs::File::create(&path).unwrap());
+        for _ in 0..100 * 1024 * 1024 / line.len() {
+            file.write_all(line).unwrap();
+        }
+        file.into_inner().unwrap().sync_all().unwrap();
+
+        let (lines, reports) = lines_in_file(&path);
+        assert_eq!(lines, 100 * 1024 * 1024 / 16);
+        let total = 100 * 1024 * 1024;
+        assert_eq!(reports.last(), Some(&(total, total)));
+        assert!(reports.len() > 2, "{reports:?}");
+        assert!(reports.windows(2).all(|pair| pair[0].0 < pair[1].0), "{reports:?}");
+        assert!(reports.iter().all(|&(done, reported_total)| done <= reported_total && reported_total == total));
+    }
+
+    #[test]
+    fn count_lines_in_file_counts_an_unterminated_last_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("open.txt");
+        fs::write(&path, "one\ntwo").unwrap();
+        assert_eq!(lines_in_file(&path), (2, vec![(7, 7)]));
+        fs::write(&path, "").unwrap();
+        assert_eq!(lines_in_file(&path), (0, vec![(0, 0)]));
+        let missing = path_arg(&dir.path().join("missing.txt"));
+        assert_eq!(ffi::count_lines_in_file(missing.as_ptr(), None, std::ptr::null_mut()), -1);
+    }
+
+    #[test]
+    fn file_open_counts_each_metric_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        fs::write(&path, "naïve café\nsecond line here\n").unwrap();
+        let file = ffi::file_open(path_arg(&path).as_ptr());
+        assert!(!file.is_null());
+        assert_eq!((ffi::file_chars(file), ffi::file_words(file), ffi::file_lines(file)), (28, 5, 2));
+        // Later queries use the cached counts rather than the file, which can change meanwhile.
+        fs::write(&path, "").unwrap();
+        assert_eq!((ffi::file_chars(file), ffi::file_words(file), ffi::file_lines(file)), (28, 5, 2));
+        ffi::file_close(file);
+    }
+
+    #[test]
+    fn file_open_returns_null_for_unreadable_files() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(ffi::file_open(path_arg(&dir.path().join("missing.md")).as_ptr()).is_null());
+        let path = dir.path().join("latin1.txt");
+        fs::write(&path, b"caf\xe9").unwrap();
+        assert!(ffi::file_open(path_arg(&path).as_ptr()).is_null());
+        assert_eq!(crate::modules::error::message_for(CountErrorCode::InvalidUtf8), format!("File is not valid UTF-8: {}", path.display()));
+        assert!(ffi::file_open(std::ptr::null()).is_null());
+        assert_eq!(ffi::file_chars(std::ptr::null_mut()), 0);
+        ffi::file_close(std::ptr::null_mut());
+    }
+
+    fn substring_count(path: &std::path::Path, needle: &str) -> Result<u64, i32> {
+        let needle = CString::new(needle).unwrap();
+        let mut count = u64::MAX;
+        match ffi::count_substring_mmap(path_arg(path).as_ptr(), needle.as_ptr(), &mut count) {
+            0 => Ok(count),
+            status => Err(status),
+        }
+    }
+
+    #[test]
+    fn count_substring_mmap_searches_a_large_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large.txt");
+        let mut contents = vec![b'.'; 64 << 20];
+        // One occurrence per MiB, the last one ending exactly at the end of the file.
+        for start in (0..64).map(|mib| (mib << 20) + (1 << 20) - 6) {
+            contents[start..start + 6].copy_from_slice(b"needle");
+        }
+        fs::write(&path, &contents).unwrap();
+        assert_eq!(substring_count(&path, "needle"), Ok(64));
+        assert_eq!(substring_count(&path, "needles"), Ok(0));
+        assert_eq!(substring_count(&path, ".."), Ok((((1 << 20) - 6) / 2 * 64) as u64));
+    }
+
+    #[test]
+    fn count_substring_mmap_handles_empty_files_and_bad_arguments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("empty.txt");
+        fs::write(&path, "").unwrap();
+        assert_eq!(substring_count(&path, "a"), Ok(0));
+        assert_eq!(substring_count(&path, ""), Err(CountErrorCode::InvalidArgument as i32));
+        let missing = dir.path().join("missing.txt");
+        assert_eq!(substring_count(&missing, "a"), Err(CountErrorCode::FileNotFound as i32));
+    }
 }
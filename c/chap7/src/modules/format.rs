@@ -0,0 +1,159 @@
+mod ffi {
+    use std::ffi::CString;
+    use std::os::raw::c_char;
+    use std::ptr;
+
+    use super::CountFormat;
+
+    /// Writes `value` with `group_separator` between groups of three digits, e.g. `1,234,567`.
+    /// A NUL separator disables grouping; a non-ASCII one returns NULL. Free the result with
+    /// `count_string_free`.
+    #[no_mangle]
+    pub extern "C" fn format_count(value: u64, group_separator: c_char) -> *mut c_char {
+        let separator = group_separator as u8;
+        if !separator.is_ascii() {
+            return ptr::null_mut();
+        }
+        let formatted = match separator {
+            0 => value.to_string(),
+            separator => super::group_digits(value, separator as char),
+        };
+        CString::new(formatted).unwrap().into_raw()
+    }
+
+    /// Locale-independent formatting of `value` in the given style. Free the result with
+    /// `count_string_free`.
+    #[no_mangle]
+    pub extern "C" fn format_count_styled(value: u64, style: CountFormat) -> *mut c_char {
+        CString::new(super::format_count(value, style)).unwrap().into_raw()
+    }
+}
+
+/// `Human` abbreviates with SI suffixes to one decimal place, e.g. `1.2M`; values below 1000 are
+/// written in full.
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CountFormat {
+    #[default]
+    Plain,
+    ThousandsSeparated,
+    Human,
+}
+
+impl CountFormat {
+    pub const NAMES: [(CountFormat, &'static str); 3] = [
+        (CountFormat::Plain, "plain"),
+        (CountFormat::ThousandsSeparated, "thousands"),
+        (CountFormat::Human, "human"),
+    ];
+
+    pub fn from_name(name: &str) -> Option<CountFormat> {
+        CountFormat::NAMES.iter().find(|(_, n)| *n == name).map(|(format, _)| *format)
+    }
+
+    pub fn name(self) -> &'static str {
+        CountFormat::NAMES.iter().find(|(format, _)| *format == self).unwrap().1
+    }
+}
+
+const HUMAN_SUFFIXES: [&str; 6] = ["k", "M", "G", "T", "P", "E"];
+
+pub fn format_count(value: u64, style: CountFormat) -> String {
+    match style {
+        CountFormat::Plain => value.to_string(),
+        CountFormat::ThousandsSeparated => group_digits(value, ','),
+        CountFormat::Human => human(value),
+    }
+}
+
+fn human(value: u64) -> String {
+    if value < 1000 {
+        return value.to_string();
+    }
+    let mut unit: u128 = 1000;
+    let mut suffix = 0;
+    // Round to tenths first so values like 999_950 carry over into the next suffix.
+    let mut tenths = (u128::from(value) * 10 + unit / 2) / unit;
+    while tenths >= 10_000 && suffix + 1 < HUMAN_SUFFIXES.len() {
+        unit *= 1000;
+        suffix += 1;
+        tenths = (u128::from(value) * 10 + unit / 2) / unit;
+    }
+    let suffix = HUMAN_SUFFIXES[suffix];
+    match tenths % 10 {
+        0 => format!("{}{suffix}", tenths / 10),
+        fraction => format!("{}.{fraction}{suffix}", tenths / 10),
+    }
+}
+
+fn group_digits(value: u64, separator: char) -> String {
+    let digits = value.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(separator);
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+    use std::os::raw::c_char;
+
+    use super::{ffi, CountFormat};
+
+    /// Copies a string from the library and frees it.
+    fn take(formatted: *mut c_char) -> Option<String> {
+        if formatted.is_null() {
+            return None;
+        }
+        let copy = unsafe { CStr::from_ptr(formatted) }.to_str().unwrap().to_owned();
+        crate::count_string_free(formatted);
+        Some(copy)
+    }
+
+    fn grouped(value: u64, separator: u8) -> Option<String> {
+        take(ffi::format_count(value, separator as c_char))
+    }
+
+    #[test]
+    fn groups_digits_with_commas_and_dots() {
+        assert_eq!(grouped(1_234_567, b',').as_deref(), Some("1,234,567"));
+        assert_eq!(grouped(1_234_567, b'.').as_deref(), Some("1.234.567"));
+        assert_eq!(grouped(123_456, b',').as_deref(), Some("123,456"));
+        assert_eq!(grouped(999, b',').as_deref(), Some("999"));
+        assert_eq!(grouped(0, b',').as_deref(), Some("0"));
+        assert_eq!(grouped(u64::MAX, b',').as_deref(), Some("18,446,744,073,709,551,615"));
+    }
+
+    #[test]
+    fn nul_disables_grouping_and_non_ascii_is_rejected() {
+        assert_eq!(grouped(1_234_567, 0).as_deref(), Some("1234567"));
+        assert_eq!(grouped(1_234_567, 0xA0), None);
+    }
+
+    #[test]
+    fn every_style_formats_the_same_values() {
+        let values = [0, 999, 1_000, 1_234, 999_950, 1_234_567, u64::MAX];
+        let formatted: Vec<Vec<String>> = CountFormat::NAMES
+            .iter()
+            .map(|&(style, _)| values.iter().map(|&value| take(ffi::format_count_styled(value, style)).unwrap()).collect())
+            .collect();
+        assert_eq!(formatted[0], ["0", "999", "1000", "1234", "999950", "1234567", "18446744073709551615"]);
+        assert_eq!(formatted[1], ["0", "999", "1,000", "1,234", "999,950", "1,234,567", "18,446,744,073,709,551,615"]);
+        assert_eq!(formatted[2], ["0", "999", "1k", "1.2k", "1M", "1.2M", "18.4E"]);
+    }
+
+    #[test]
+    fn style_names_round_trip() {
+        for (style, name) in CountFormat::NAMES {
+            assert_eq!(CountFormat::from_name(name), Some(style));
+            assert_eq!(style.name(), name);
+        }
+        assert_eq!(CountFormat::from_name("Human"), None);
+    }
+}
@@ -0,0 +1,239 @@
+mod ffi {
+    use std::os::raw::c_char;
+    use std::ptr;
+
+    use super::MergeJob;
+    use crate::modules::csv::MergeOptions;
+    use crate::modules::error::{self, CountErrorCode};
+
+    /// Starts `csv_merge_files_checked` for `csv` on a background thread and returns a handle to
+    /// it, or NULL with the cause in `last_error_message` when `csv` is invalid. `options` may be
+    /// NULL for the defaults; a `transform` in it is called on the job's thread. Wait with
+    /// `csv_job_wait_timeout` and release the handle with `csv_job_free`.
+    #[no_mangle]
+    pub extern "C" fn csv_job_start(csv: *const c_char, options: *const MergeOptions) -> *mut MergeJob {
+        let mut job = ptr::null_mut();
+        error::status(|| {
+            let csv = unsafe { error::str_from_ptr(csv) }?;
+            let options = unsafe { options.as_ref() }.copied().unwrap_or_default();
+            job = Box::into_raw(Box::new(MergeJob::start(csv.to_owned(), options)));
+            Ok(())
+        });
+        job
+    }
+
+    /// Waits up to `ms` milliseconds for the job to finish; 0 waits for as long as it takes.
+    /// Returns `CountErrorCode_TimedOut` while the job is still running, which keeps running and
+    /// can be waited on again. Once it has finished, every call returns the merge's own status,
+    /// with its message in `last_error_message`.
+    #[no_mangle]
+    pub extern "C" fn csv_job_wait_timeout(job: *mut MergeJob, ms: u64) -> i32 {
+        error::status(|| match unsafe { job.as_ref() } {
+            Some(job) => job.wait(ms),
+            None => Err(CountErrorCode::NullPointer),
+        })
+    }
+
+    /// Hands over the merged text of a job that finished successfully, freed with
+    /// `csv_free_merged_file`. Fails with the job's status if it failed, with
+    /// `CountErrorCode_TimedOut` if it is still running, and with `CountErrorCode_InvalidArgument`
+    /// once the text has been taken.
+    #[no_mangle]
+    pub extern "C" fn csv_job_take_result(job: *mut MergeJob, merged: *mut *mut c_char) -> i32 {
+        error::status(|| {
+            let job = unsafe { job.as_ref() }.ok_or(CountErrorCode::NullPointer)?;
+            if merged.is_null() {
+                return Err(CountErrorCode::NullPointer);
+            }
+            let result = job.take()?;
+            unsafe { error::write_out(merged, result.into_raw()) }
+        })
+    }
+
+    /// Releases the handle without waiting. A job that is still running finishes in the
+    /// background, and its result is discarded.
+    #[no_mangle]
+    pub extern "C" fn csv_job_free(job: *mut MergeJob) {
+        if !job.is_null() {
+            drop(unsafe { Box::from_raw(job) });
+        }
+    }
+}
+
+use crate::modules::csv::{self, MergeOptions};
+use crate::modules::error::{self, CountErrorCode};
+use std::ffi::CString;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub struct MergeJob {
+    state: Arc<(Mutex<JobState>, Condvar)>,
+}
+
+enum JobState {
+    Running,
+    Merged(CString),
+    Taken,
+    /// The failing code with the message the merge recorded on the job's thread.
+    Failed(CountErrorCode, String),
+}
+
+/// `MergeOptions` holds the caller's transform and its context, which `csv_job_start` documents
+/// as being used from the job's thread.
+struct SendOptions(MergeOptions);
+
+unsafe impl Send for SendOptions {}
+
+impl MergeJob {
+    fn start(csv: String, options: MergeOptions) -> MergeJob {
+        let state = Arc::new((Mutex::new(JobState::Running), Condvar::new()));
+        let job_state = Arc::clone(&state);
+        let options = SendOptions(options);
+        thread::spawn(move || {
+            // Moves the whole wrapper in; the closure would otherwise capture just the field.
+            let options = options;
+            let finished = match csv::merge_files_with_options(&csv, &options.0) {
+                Ok((merged, _)) => JobState::Merged(CString::new(merged).unwrap()),
+                Err(code) => JobState::Failed(code, error::message_for(code)),
+            };
+            let (lock, done) = &*job_state;
+            *lock.lock().unwrap() = finished;
+            done.notify_all();
+        });
+        MergeJob { state }
+    }
+
+    fn wait(&self, ms: u64) -> Result<(), CountErrorCode> {
+        let (lock, done) = &*self.state;
+        let deadline = Instant::now().checked_add(Duration::from_millis(ms)).filter(|_| ms > 0);
+        let mut state = lock.lock().unwrap();
+        while let JobState::Running = *state {
+            state = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return error::fail(CountErrorCode::TimedOut, format!("Job still running after {ms} ms"));
+                    }
+                    done.wait_timeout(state, remaining).unwrap().0
+                }
+                None => done.wait(state).unwrap(),
+            };
+        }
+        match &*state {
+            JobState::Failed(code, message) => error::fail(*code, message.clone()),
+            _ => Ok(()),
+        }
+    }
+
+    fn take(&self) -> Result<CString, CountErrorCode> {
+        let mut state = self.state.0.lock().unwrap();
+        match std::mem::replace(&mut *state, JobState::Taken) {
+            JobState::Merged(merged) => Ok(merged),
+            JobState::Running => {
+                *state = JobState::Running;
+                error::fail(CountErrorCode::TimedOut, "Job is still running")
+            }
+            JobState::Taken => error::fail(CountErrorCode::InvalidArgument, "Job result was already taken"),
+            JobState::Failed(code, message) => {
+                let failed = error::fail(code, message.clone());
+                *state = JobState::Failed(code, message);
+                failed
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{c_void, CStr, CString};
+    use std::os::raw::c_char;
+    use std::ptr;
+    use std::sync::{Condvar, Mutex};
+
+    use super::ffi;
+    use crate::modules::csv::MergeOptions;
+    use crate::modules::error::CountErrorCode;
+
+    /// Closed until `open` is called; the transform below blocks on it.
+    #[derive(Default)]
+    struct Gate {
+        open: Mutex<bool>,
+        opened: Condvar,
+    }
+
+    impl Gate {
+        fn open(&self) {
+            *self.open.lock().unwrap() = true;
+            self.opened.notify_all();
+        }
+    }
+
+    unsafe extern "C" fn wait_for_gate(
+        _filename: *const c_char,
+        _content: *const c_char,
+        _content_len: usize,
+        context: *mut c_void,
+    ) -> *mut c_char {
+        let gate = &*(context as *const Gate);
+        let mut open = gate.open.lock().unwrap();
+        while !*open {
+            open = gate.opened.wait(open).unwrap();
+        }
+        ptr::null_mut()
+    }
+
+    fn take_result(job: *mut super::MergeJob) -> Result<String, i32> {
+        let mut merged = ptr::null_mut();
+        match ffi::csv_job_take_result(job, &mut merged) {
+            0 => {
+                let text = unsafe { CStr::from_ptr(merged) }.to_str().unwrap().to_owned();
+                drop(unsafe { CString::from_raw(merged) });
+                Ok(text)
+            }
+            status => Err(status),
+        }
+    }
+
+    #[test]
+    fn wait_times_out_while_the_merge_is_blocked_and_succeeds_after() {
+        let gate = Gate::default();
+        let options = MergeOptions {
+            transform: Some(wait_for_gate),
+            transform_context: &gate as *const Gate as *mut c_void,
+            ..MergeOptions::default()
+        };
+        let csv = CString::new("chapter1.md, chapter2.md").unwrap();
+        let job = ffi::csv_job_start(csv.as_ptr(), &options);
+        assert!(!job.is_null());
+
+        let timed_out = CountErrorCode::TimedOut as i32;
+        assert_eq!(ffi::csv_job_wait_timeout(job, 20), timed_out);
+        assert_eq!(ffi::csv_job_wait_timeout(job, 1), timed_out);
+        assert_eq!(take_result(job), Err(timed_out));
+
+        gate.open();
+        assert_eq!(ffi::csv_job_wait_timeout(job, 0), CountErrorCode::Ok as i32);
+        assert_eq!(ffi::csv_job_wait_timeout(job, 1), CountErrorCode::Ok as i32);
+        assert_eq!(take_result(job).as_deref(), Ok("# Getting started\n# Wrapping up\n"));
+        assert_eq!(take_result(job), Err(CountErrorCode::InvalidArgument as i32));
+        ffi::csv_job_free(job);
+    }
+
+    #[test]
+    fn a_failed_merge_reports_its_own_status() {
+        let csv = CString::new("chapter1.md, missing.md").unwrap();
+        let job = ffi::csv_job_start(csv.as_ptr(), ptr::null());
+        let not_found = CountErrorCode::FileNotFound as i32;
+        assert_eq!(ffi::csv_job_wait_timeout(job, 0), not_found);
+        assert_eq!(take_result(job), Err(not_found));
+        ffi::csv_job_free(job);
+    }
+
+    #[test]
+    fn null_handles_are_rejected() {
+        assert!(ffi::csv_job_start(ptr::null(), ptr::null()).is_null());
+        assert_eq!(ffi::csv_job_wait_timeout(ptr::null_mut(), 0), CountErrorCode::NullPointer as i32);
+        ffi::csv_job_free(ptr::null_mut());
+    }
+}
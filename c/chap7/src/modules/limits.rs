@@ -0,0 +1,73 @@
+mod ffi {
+    use std::sync::atomic::Ordering;
+
+    /// 0 leaves a limit unset, which is the default for all three. Exceeding one fails with
+    /// `CountErrorCode_LimitExceeded`, and `last_error_message` names the limit.
+    #[no_mangle]
+    pub extern "C" fn set_limits(max_text_bytes: u64, max_merge_total_bytes: u64, max_csv_values: u64) {
+        super::MAX_TEXT_BYTES.store(max_text_bytes, Ordering::Relaxed);
+        super::MAX_MERGE_TOTAL_BYTES.store(max_merge_total_bytes, Ordering::Relaxed);
+        super::MAX_CSV_VALUES.store(max_csv_values, Ordering::Relaxed);
+    }
+}
+
+use crate::modules::error::{self, CountErrorCode};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+static MAX_TEXT_BYTES: AtomicU64 = AtomicU64::new(0);
+static MAX_MERGE_TOTAL_BYTES: AtomicU64 = AtomicU64::new(0);
+static MAX_CSV_VALUES: AtomicU64 = AtomicU64::new(0);
+
+pub fn check_text_bytes(len: usize) -> Result<(), CountErrorCode> {
+    check("max_text_bytes", &MAX_TEXT_BYTES, len)
+}
+
+pub fn check_merge_total_bytes(len: usize) -> Result<(), CountErrorCode> {
+    check("max_merge_total_bytes", &MAX_MERGE_TOTAL_BYTES, len)
+}
+
+pub fn check_csv_value_count(count: usize) -> Result<(), CountErrorCode> {
+    check("max_csv_values", &MAX_CSV_VALUES, count)
+}
+
+/// Counts the values `csv` would split into without allocating them.
+pub fn check_csv_values(csv: &str, delimiter: char) -> Result<(), CountErrorCode> {
+    check_csv_value_count(csv.matches(delimiter).count() + 1)
+}
+
+fn check(name: &str, limit: &AtomicU64, value: usize) -> Result<(), CountErrorCode> {
+    let limit = limit.load(Ordering::Relaxed);
+    if limit != 0 && value as u64 > limit {
+        return error::fail(CountErrorCode::LimitExceeded, format!("{name} exceeded: {value} > {limit}"));
+    }
+    Ok(())
+}
+
+/// A cooperative time limit, checked between files and between `file::CHUNK_SIZE` reads. A single
+/// read that blocks (a hung network mount, say) still overruns by however long it takes; the
+/// chunked reads keep each one small so that is the only way to overrun.
+pub struct Deadline {
+    timeout_ms: u64,
+    at: Option<Instant>,
+}
+
+impl Deadline {
+    /// A `timeout_ms` of 0 never expires.
+    pub fn after_ms(timeout_ms: u64) -> Deadline {
+        let at = match timeout_ms {
+            0 => None,
+            ms => Instant::now().checked_add(Duration::from_millis(ms)),
+        };
+        Deadline { timeout_ms, at }
+    }
+
+    pub fn check(&self) -> Result<(), CountErrorCode> {
+        match self.at {
+            Some(at) if Instant::now() >= at => {
+                error::fail(CountErrorCode::TimedOut, format!("Timed out after {} ms", self.timeout_ms))
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+mod ffi {
+    use std::ffi::c_void;
+    use std::os::raw::c_char;
+
+    use crate::modules::buffer::StringArray;
+    use crate::modules::error;
+
+    /// Adds a metric named `name` after the built-ins. Fails with
+    /// `CountErrorCode_InvalidArgument` when the name is already taken.
+    #[no_mangle]
+    pub extern "C" fn register_metric(
+        name: *const c_char,
+        metric: unsafe extern "C" fn(text: *const c_char, context: *mut c_void) -> u64,
+        context: *mut c_void,
+    ) -> i32 {
+        error::status(|| {
+            let name = unsafe { error::str_from_ptr(name) }?;
+            super::register(name, metric, context as usize)
+        })
+    }
+
+    /// Built-ins come first (bytes, characters, words, lines), then registered metrics in
+    /// registration order. Release `out` with `string_array_free`.
+    #[no_mangle]
+    pub extern "C" fn list_metrics(out: *mut StringArray) -> i32 {
+        error::status(|| unsafe { error::write_out(out, StringArray::new(super::names())) })
+    }
+
+    /// Unknown names fail with `CountErrorCode_UnknownMetric`.
+    #[no_mangle]
+    pub extern "C" fn run_metric(name: *const c_char, text: *const c_char, out: *mut u64) -> i32 {
+        error::status(|| {
+            let name = unsafe { error::str_from_ptr(name) }?;
+            let text = unsafe { error::str_from_ptr(text) }?;
+            let value = super::run(name, text)?;
+            unsafe { error::write_out(out, value) }
+        })
+    }
+}
+
+use crate::modules::error::{self, CountErrorCode};
+use crate::modules::stats::{self, WordRule};
+use std::ffi::{c_void, CString};
+use std::os::raw::c_char;
+use std::sync::Mutex;
+
+type MetricCallback = unsafe extern "C" fn(text: *const c_char, context: *mut c_void) -> u64;
+
+type BuiltinMetric = fn(&str) -> u64;
+
+const BUILTIN_METRICS: [(&str, BuiltinMetric); 4] = [
+    ("bytes", |text| text.len() as u64),
+    ("characters", |text| text.chars().count() as u64),
+    ("words", |text| stats::count_words(text, WordRule::Whitespace)),
+    ("lines", |text| text.lines().count() as u64),
+];
+
+static REGISTERED_METRICS: Mutex<Vec<(String, MetricCallback, usize)>> = Mutex::new(Vec::new());
+
+fn register(name: &str, metric: MetricCallback, context: usize) -> Result<(), CountErrorCode> {
+    let mut registered = REGISTERED_METRICS.lock().unwrap();
+    let taken = BUILTIN_METRICS.iter().any(|(builtin, _)| *builtin == name)
+        || registered.iter().any(|(existing, _, _)| existing == name);
+    if taken {
+        return error::fail(CountErrorCode::InvalidArgument, format!("Metric already exists: {name}"));
+    }
+    registered.push((name.to_owned(), metric, context));
+    Ok(())
+}
+
+fn names() -> Vec<String> {
+    let builtins = BUILTIN_METRICS.iter().map(|(name, _)| (*name).to_owned());
+    let registered = REGISTERED_METRICS.lock().unwrap();
+    builtins.chain(registered.iter().map(|(name, _, _)| name.clone())).collect()
+}
+
+fn run(name: &str, text: &str) -> Result<u64, CountErrorCode> {
+    if let Some((_, metric)) = BUILTIN_METRICS.iter().find(|(builtin, _)| *builtin == name) {
+        return Ok(metric(text));
+    }
+    // Copied out so the lock is released before calling into the metric, which may itself
+    // register or run metrics.
+    let found = REGISTERED_METRICS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(existing, _, _)| existing == name)
+        .map(|(_, metric, context)| (*metric, *context));
+    match found {
+        Some((metric, context)) => {
+            let text = CString::new(text).unwrap();
+            error::guard_callback(|| unsafe { metric(text.as_ptr(), context as *mut c_void) })
+        }
+        None => error::fail(CountErrorCode::UnknownMetric, format!("Unknown metric: {name}")),
+    }
+}
@@ -0,0 +1,65 @@
+mod ffi {
+    use std::ffi::c_void;
+    use std::os::raw::c_char;
+
+    use super::OutputMode;
+
+    /// Receives each line of library output, without its newline, while the mode is `Callback` or
+    /// `Both`. Passing NULL removes the callback.
+    #[no_mangle]
+    pub extern "C" fn set_output_callback(
+        callback: Option<unsafe extern "C" fn(line: *const c_char, context: *mut c_void)>,
+        context: *mut c_void,
+    ) {
+        super::OUTPUT.lock().unwrap().callback = callback.map(|callback| (callback, context as usize));
+    }
+
+    /// Chooses where library-printed text such as `print_version` goes; the default is
+    /// `OutputMode_Stdout`. Functions that return strings are unaffected.
+    #[no_mangle]
+    pub extern "C" fn set_output_mode(mode: OutputMode) {
+        super::OUTPUT.lock().unwrap().mode = mode;
+    }
+}
+
+use std::ffi::{c_void, CString};
+use std::io::{self, Write};
+use std::os::raw::c_char;
+use std::sync::Mutex;
+
+type OutputCallback = unsafe extern "C" fn(line: *const c_char, context: *mut c_void);
+
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)] // Only ever constructed by C callers.
+pub enum OutputMode {
+    Stdout,
+    Callback,
+    Both,
+    Discard,
+}
+
+struct Output {
+    mode: OutputMode,
+    callback: Option<(OutputCallback, usize)>,
+}
+
+static OUTPUT: Mutex<Output> = Mutex::new(Output { mode: OutputMode::Stdout, callback: None });
+
+/// The lock is held across both sinks so concurrent lines keep the same order in each. The
+/// callback must not print through the library itself.
+pub fn write_line(line: &str) {
+    let output = OUTPUT.lock().unwrap();
+    if matches!(output.mode, OutputMode::Stdout | OutputMode::Both) {
+        let mut stdout = io::stdout().lock();
+        // Output is best effort, as with `println!`, minus the panic on a closed stdout.
+        let _ = writeln!(stdout, "{line}").and_then(|()| stdout.flush());
+    }
+    if matches!(output.mode, OutputMode::Callback | OutputMode::Both) {
+        if let Some((callback, context)) = output.callback {
+            let line = CString::new(line.replace('\0', "")).unwrap();
+            unsafe { callback(line.as_ptr(), context as *mut c_void) };
+        }
+    }
+}
@@ -0,0 +1,120 @@
+mod ffi {
+    use super::SelfTestStage;
+    use crate::modules::error;
+
+    /// Runs the core counting, CSV, merge and string-ownership paths on built-in fixtures and
+    /// returns the first `SelfTestStage` that failed, or `SelfTestStage_Passed`. The details of a
+    /// failure are in `last_error_message`. Nothing is read from disk, and the content root and
+    /// registered callbacks are neither used nor changed.
+    #[no_mangle]
+    pub extern "C" fn count_self_test() -> i32 {
+        let mut failed = SelfTestStage::Passed;
+        error::status(|| {
+            for (stage, check) in super::STAGES {
+                if let Err(code) = super::run_stage(stage, check) {
+                    failed = stage;
+                    return Err(code);
+                }
+            }
+            Ok(())
+        });
+        failed as i32
+    }
+}
+
+use std::ffi::{CStr, CString};
+use std::fmt::Debug;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::api;
+use crate::modules::csv::{self, MergeOptions};
+use crate::modules::error::{self, CountErrorCode};
+use crate::modules::file;
+use crate::modules::stats::{self, CountOptions, TextStats};
+
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub enum SelfTestStage {
+    Passed,
+    Count,
+    CsvSplit,
+    Merge,
+    StringRoundTrip,
+}
+
+type Check = fn() -> Result<(), CountErrorCode>;
+
+const STAGES: [(SelfTestStage, Check); 4] = [
+    (SelfTestStage::Count, check_count),
+    (SelfTestStage::CsvSplit, check_csv_split),
+    (SelfTestStage::Merge, check_merge),
+    (SelfTestStage::StringRoundTrip, check_string_round_trip),
+];
+
+fn run_stage(stage: SelfTestStage, check: Check) -> Result<(), CountErrorCode> {
+    match panic::catch_unwind(AssertUnwindSafe(check)) {
+        Ok(result) => result,
+        Err(_) => error::fail(CountErrorCode::SelfTestFailed, format!("Self-test stage {stage:?} panicked")),
+    }
+}
+
+fn check_count() -> Result<(), CountErrorCode> {
+    let stats = stats::count_encoded("naïve café\n".as_bytes(), &CountOptions::default())?;
+    expect("count of \"naïve café\\n\"", TextStats { bytes: 13, chars: 11, words: 2, lines: 1 }, stats)
+}
+
+fn check_csv_split() -> Result<(), CountErrorCode> {
+    expect("values of \" a.md , b.md\"", vec!["a.md", "b.md"], api::csv_values(" a.md , b.md"))
+}
+
+/// Goes through the built-in fixtures rather than the content root.
+fn check_merge() -> Result<(), CountErrorCode> {
+    let read = |filename: &str, check: &file::SizeCheck| {
+        let file = file::read_file(filename);
+        check(file.to_str().len()).map(|()| file)
+    };
+    let (merged, _) = csv::merge_files_from("chapter1.md, chapter2.md", &MergeOptions::default(), &read)?;
+    expect("merge of the chapter fixtures", "# Getting started\n# Wrapping up\n", merged.as_str())
+}
+
+fn check_string_round_trip() -> Result<(), CountErrorCode> {
+    let original = CString::new("round trip").unwrap();
+    let copy = crate::count_string_dup(original.as_ptr());
+    if copy.is_null() {
+        return error::fail(CountErrorCode::SelfTestFailed, "count_string_dup returned NULL");
+    }
+    let matches = unsafe { CStr::from_ptr(copy) } == original.as_c_str();
+    crate::count_string_free(copy);
+    expect("copy made by count_string_dup", true, matches)
+}
+
+fn expect<T: Debug + PartialEq>(what: &str, expected: T, actual: T) -> Result<(), CountErrorCode> {
+    if expected == actual {
+        return Ok(());
+    }
+    error::fail(CountErrorCode::SelfTestFailed, format!("Unexpected {what}: {actual:?}, expected {expected:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ffi, SelfTestStage, STAGES};
+
+    #[test]
+    fn self_test_passes() {
+        assert_eq!(ffi::count_self_test(), SelfTestStage::Passed as i32);
+    }
+
+    #[test]
+    fn every_stage_passes_on_its_own() {
+        for (stage, check) in STAGES {
+            assert_eq!(super::run_stage(stage, check), Ok(()), "stage {stage:?}");
+        }
+    }
+
+    #[test]
+    fn a_panicking_stage_fails_with_self_test_failed() {
+        let result = super::run_stage(SelfTestStage::Count, || panic!("broken"));
+        assert_eq!(result, Err(crate::modules::error::CountErrorCode::SelfTestFailed));
+    }
+}
@@ -0,0 +1,737 @@
+mod ffi {
+    use std::ffi::{c_void, CString};
+    use std::os::raw::c_char;
+    use std::ptr;
+
+    use super::{CountOptions, StreamCounter, TextStats};
+    use crate::modules::cache;
+    use crate::modules::error::{self, CountErrorCode};
+    use crate::modules::file;
+
+    /// A NULL `options` counts with the defaults. With `TextEncoding_Latin1` every byte is one
+    /// character and the text is never rejected as invalid.
+    #[no_mangle]
+    pub extern "C" fn count_all(text: *const c_char, options: *const CountOptions, out: *mut TextStats) -> i32 {
+        error::status(|| {
+            let options = unsafe { options.as_ref() }.copied().unwrap_or_default();
+            let bytes = unsafe { error::bytes_from_ptr(text) }?;
+            let stats = cache::count_cached(bytes, &options, || super::count_encoded(bytes, &options))?;
+            unsafe { error::write_out(out, stats) }
+        })
+    }
+
+    /// Counts data pulled from `read`, which fills up to `buf_len` bytes of `buf` and returns how
+    /// many it wrote, 0 at the end of the stream, or a negative value on failure. A failure ends
+    /// the count with `CountErrorCode_Io`, and the returned value appears in `last_error_message`.
+    /// Words follow the whitespace rule; characters may be split across reads.
+    #[no_mangle]
+    pub extern "C" fn count_stream(
+        read: unsafe extern "C" fn(buf: *mut u8, buf_len: usize, context: *mut c_void) -> isize,
+        context: *mut c_void,
+        out: *mut TextStats,
+    ) -> i32 {
+        count_stream_with_options(read, context, ptr::null(), out)
+    }
+
+    /// `count_stream` with the word rule and encoding of `options`, giving the same counts as
+    /// `count_all` over the concatenated reads. A NULL `options` counts with the defaults, and
+    /// `options->timeout_ms` is ignored.
+    #[no_mangle]
+    pub extern "C" fn count_stream_with_options(
+        read: unsafe extern "C" fn(buf: *mut u8, buf_len: usize, context: *mut c_void) -> isize,
+        context: *mut c_void,
+        options: *const CountOptions,
+        out: *mut TextStats,
+    ) -> i32 {
+        error::status(|| {
+            let options = unsafe { options.as_ref() }.copied().unwrap_or_default();
+            if out.is_null() {
+                return Err(CountErrorCode::NullPointer);
+            }
+            let mut counter = StreamCounter::new(&options);
+            let mut buffer = vec![0; file::CHUNK_SIZE];
+            loop {
+                let read = error::guard_callback(|| unsafe {
+                    read(buffer.as_mut_ptr(), buffer.len(), context)
+                })?;
+                match usize::try_from(read) {
+                    Ok(0) => break,
+                    Ok(len) if len <= buffer.len() => counter.feed(&buffer[..len])?,
+                    Ok(len) => {
+                        return error::fail(
+                            CountErrorCode::InvalidArgument,
+                            format!("Read callback reported {len} bytes for a {} byte buffer", buffer.len()),
+                        );
+                    }
+                    Err(_) => return error::fail(CountErrorCode::Io, format!("Read callback failed with {read}")),
+                }
+            }
+            unsafe { error::write_out(out, counter.finish()?) }
+        })
+    }
+
+    /// Markdown table of the default counts, one row per metric with the counts right-aligned.
+    /// Free the result with `count_string_free`; NULL or non-UTF-8 text returns NULL.
+    #[no_mangle]
+    pub extern "C" fn count_report_markdown(text: *const c_char) -> *mut c_char {
+        match unsafe { error::str_from_ptr(text) } {
+            Ok(text) => {
+                let stats = super::count_all(text, &CountOptions::default());
+                CString::new(super::markdown_report(&stats)).unwrap().into_raw()
+            }
+            Err(_) => ptr::null_mut(),
+        }
+    }
+
+    /// `{"chars":N,"bytes":N,"words":N,"lines":N,"graphemes":N}` for `text`, with the keys always
+    /// in that order. The first four match `count_all` with the defaults and come from one pass;
+    /// graphemes are approximated as `unicode::count_graphemes` describes. Free the result with
+    /// `count_string_free`; NULL or non-UTF-8 text returns NULL.
+    #[no_mangle]
+    pub extern "C" fn count_summary_json(text: *const c_char) -> *mut c_char {
+        count_summary_json_with_options(text, ptr::null())
+    }
+
+    /// `count_summary_json` counted with `options`, which may be NULL for the defaults. With
+    /// `options->with_digest` set the object ends with `"sha256":"<64 hex digits>"` over the
+    /// bytes of `text`. Under `TextEncoding_Latin1` no text is rejected.
+    #[no_mangle]
+    pub extern "C" fn count_summary_json_with_options(text: *const c_char, options: *const CountOptions) -> *mut c_char {
+        let options = unsafe { options.as_ref() }.copied().unwrap_or_default();
+        let bytes = match unsafe { error::bytes_from_ptr(text) } {
+            Ok(bytes) => bytes,
+            Err(_) => return ptr::null_mut(),
+        };
+        match super::summary_json(bytes, &options) {
+            Ok(summary) => CString::new(summary).unwrap().into_raw(),
+            Err(_) => ptr::null_mut(),
+        }
+    }
+}
+
+use crate::modules::digest;
+use crate::modules::error::CountErrorCode;
+use crate::modules::unicode;
+use std::borrow::Cow;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TextStats {
+    pub bytes: u64,
+    pub chars: u64,
+    pub words: u64,
+    pub lines: u64,
+}
+
+/// `Whitespace` is the default and splits on Unicode whitespace. `UnicodeWords` follows the
+/// UAX #29 word boundaries closely enough for prose: ideographs and hiragana count individually,
+/// katakana runs form one word, apostrophes and decimal points stay inside a word, and hyphens
+/// separate words.
+///
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WordRule {
+    #[default]
+    Whitespace,
+    UnicodeWords,
+}
+
+impl WordRule {
+    pub const NAMES: [(WordRule, &'static str); 2] =
+        [(WordRule::Whitespace, "whitespace"), (WordRule::UnicodeWords, "unicode")];
+
+    pub fn from_name(name: &str) -> Option<WordRule> {
+        WordRule::NAMES.iter().find(|(_, n)| *n == name).map(|(rule, _)| *rule)
+    }
+
+    pub fn name(self) -> &'static str {
+        WordRule::NAMES.iter().find(|(rule, _)| *rule == self).unwrap().1
+    }
+}
+
+/// How input bytes become characters. `Latin1` maps each byte to the code point of the same
+/// value, so decoding cannot fail.
+///
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    Latin1,
+}
+
+impl TextEncoding {
+    pub const NAMES: [(TextEncoding, &'static str); 2] =
+        [(TextEncoding::Utf8, "utf8"), (TextEncoding::Latin1, "latin1")];
+
+    pub fn from_name(name: &str) -> Option<TextEncoding> {
+        TextEncoding::NAMES.iter().find(|(_, n)| *n == name).map(|(encoding, _)| *encoding)
+    }
+
+    pub fn name(self) -> &'static str {
+        TextEncoding::NAMES.iter().find(|(encoding, _)| *encoding == self).unwrap().1
+    }
+}
+
+/// A nonzero `timeout_ms` limits counts that read files, which fail with
+/// `CountErrorCode_TimedOut` once it passes; counts of in-memory text ignore it.
+///
+/// `with_digest` adds the SHA-256 of the input bytes, as lowercase hex, to JSON output such as
+/// `count_summary_json_with_options`. Other counts ignore it.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CountOptions {
+    pub word_rule: WordRule,
+    pub encoding: TextEncoding,
+    pub timeout_ms: u64,
+    pub with_digest: bool,
+}
+
+pub fn decode_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| byte as char).collect()
+}
+
+pub fn count_encoded(bytes: &[u8], options: &CountOptions) -> Result<TextStats, CountErrorCode> {
+    let stats = match options.encoding {
+        TextEncoding::Utf8 => {
+            count_all(std::str::from_utf8(bytes).map_err(|_| CountErrorCode::InvalidUtf8)?, options)
+        }
+        // Bytes stay the input size rather than the size of the decoded text.
+        TextEncoding::Latin1 => TextStats { bytes: bytes.len() as u64, ..count_all(&decode_latin1(bytes), options) },
+    };
+    Ok(stats)
+}
+
+pub fn count_all(text: &str, options: &CountOptions) -> TextStats {
+    TextStats {
+        bytes: text.len() as u64,
+        chars: text.chars().count() as u64,
+        words: count_words(text, options.word_rule),
+        lines: text.lines().count() as u64,
+    }
+}
+
+pub fn count_words(text: &str, rule: WordRule) -> u64 {
+    match rule {
+        WordRule::Whitespace => text.split_whitespace().count() as u64,
+        WordRule::UnicodeWords => count_unicode_words(text),
+    }
+}
+
+/// Single-pass counter over byte chunks, matching `count_encoded` with the same options. The
+/// default counts UTF-8 with the whitespace word rule. A character split across chunks is
+/// carried over until its remaining bytes arrive.
+#[derive(Default)]
+pub struct StreamCounter {
+    word_rule: WordRule,
+    encoding: TextEncoding,
+    stats: TextStats,
+    carry: Vec<u8>,
+    in_word: bool,
+    unicode_words: UnicodeWordCounter,
+    ends_with_newline: bool,
+}
+
+impl StreamCounter {
+    pub fn new(options: &CountOptions) -> StreamCounter {
+        StreamCounter { word_rule: options.word_rule, encoding: options.encoding, ..StreamCounter::default() }
+    }
+
+    pub fn feed(&mut self, mut chunk: &[u8]) -> Result<(), CountErrorCode> {
+        self.stats.bytes += chunk.len() as u64;
+        if self.encoding == TextEncoding::Latin1 {
+            self.count_str(&decode_latin1(chunk));
+            return Ok(());
+        }
+        if let Some(&lead) = self.carry.first() {
+            let missing = utf8_sequence_len(lead) - self.carry.len();
+            let take = missing.min(chunk.len());
+            self.carry.extend_from_slice(&chunk[..take]);
+            chunk = &chunk[take..];
+            if take < missing {
+                return Ok(());
+            }
+            let carry = std::mem::take(&mut self.carry);
+            self.count_str(std::str::from_utf8(&carry).map_err(|_| CountErrorCode::InvalidUtf8)?);
+        }
+        match std::str::from_utf8(chunk) {
+            Ok(text) => self.count_str(text),
+            Err(err) => {
+                let (valid, rest) = chunk.split_at(err.valid_up_to());
+                self.count_str(std::str::from_utf8(valid).unwrap());
+                if err.error_len().is_some() {
+                    return Err(CountErrorCode::InvalidUtf8);
+                }
+                self.carry.extend_from_slice(rest);
+            }
+        }
+        Ok(())
+    }
+
+    /// Words seen so far; a word still running at the end of the last chunk is included.
+    pub fn words(&self) -> u64 {
+        self.stats.words
+    }
+
+    pub fn finish(self) -> Result<TextStats, CountErrorCode> {
+        if !self.carry.is_empty() {
+            return Err(CountErrorCode::InvalidUtf8);
+        }
+        let mut stats = self.stats;
+        if stats.bytes > 0 && !self.ends_with_newline {
+            stats.lines += 1;
+        }
+        Ok(stats)
+    }
+
+    fn count_str(&mut self, text: &str) {
+        for c in text.chars() {
+            self.stats.chars += 1;
+            match self.word_rule {
+                WordRule::Whitespace => {
+                    let whitespace = c.is_whitespace();
+                    if !whitespace && !self.in_word {
+                        self.stats.words += 1;
+                    }
+                    self.in_word = !whitespace;
+                }
+                WordRule::UnicodeWords => self.stats.words += u64::from(self.unicode_words.starts_word(c)),
+            }
+            if c == '\n' {
+                self.stats.lines += 1;
+            }
+            self.ends_with_newline = c == '\n';
+        }
+    }
+}
+
+/// Only called with the lead byte of an incomplete but so far valid sequence.
+fn utf8_sequence_len(lead: u8) -> usize {
+    match lead {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        _ => 4,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum WordClass {
+    Letter,
+    Digit,
+    Katakana,
+    Ideograph,
+    Extend,
+    MidLetter,
+    MidNum,
+    Other,
+}
+
+fn word_class(c: char) -> WordClass {
+    match c {
+        '\u{30A0}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}' | '\u{FF66}'..='\u{FF9F}' => WordClass::Katakana,
+        '\u{3040}'..='\u{309F}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' => WordClass::Ideograph,
+        '\u{F900}'..='\u{FAFF}' | '\u{20000}'..='\u{2FFFF}' => WordClass::Ideograph,
+        '\'' | '\u{2019}' | ':' | '\u{B7}' => WordClass::MidLetter,
+        '.' => WordClass::MidLetter,
+        ',' | ';' => WordClass::MidNum,
+        '\u{200D}' | '\u{FE00}'..='\u{FE0F}' => WordClass::Extend,
+        '_' => WordClass::Letter,
+        c if c.is_numeric() => WordClass::Digit,
+        c if c.is_alphabetic() => WordClass::Letter,
+        c if unicode::general_category(c).starts_with('M') => WordClass::Extend,
+        _ => WordClass::Other,
+    }
+}
+
+fn count_unicode_words(text: &str) -> u64 {
+    let mut counter = UnicodeWordCounter::default();
+    text.chars().map(|c| u64::from(counter.starts_word(c))).sum()
+}
+
+/// Whether a character starts a word depends only on the two word classes before it, ignoring
+/// `Extend` characters, so words can be counted one character at a time.
+#[derive(Default)]
+struct UnicodeWordCounter {
+    before: Option<WordClass>,
+    previous: Option<WordClass>,
+}
+
+impl UnicodeWordCounter {
+    fn starts_word(&mut self, c: char) -> bool {
+        let class = word_class(c);
+        if class == WordClass::Extend {
+            return false;
+        }
+        let starts_word = match class {
+            WordClass::Ideograph => true,
+            WordClass::Katakana => self.previous != Some(WordClass::Katakana),
+            WordClass::Letter | WordClass::Digit => !self.continues_word(class),
+            _ => false,
+        };
+        self.before = self.previous.replace(class);
+        starts_word
+    }
+
+    fn continues_word(&self, class: WordClass) -> bool {
+        let previous = match self.previous {
+            Some(WordClass::Letter | WordClass::Digit) => return true,
+            Some(previous) => previous,
+            None => return false,
+        };
+        match (self.before, previous) {
+            (Some(before), WordClass::MidLetter) => {
+                (before == WordClass::Letter && class == WordClass::Letter)
+                    || (before == WordClass::Digit && class == WordClass::Digit)
+            }
+            (Some(before), WordClass::MidNum) => before == WordClass::Digit && class == WordClass::Digit,
+            _ => false,
+        }
+    }
+}
+
+fn summary_json(bytes: &[u8], options: &CountOptions) -> Result<String, CountErrorCode> {
+    let text = match options.encoding {
+        TextEncoding::Utf8 => Cow::Borrowed(std::str::from_utf8(bytes).map_err(|_| CountErrorCode::InvalidUtf8)?),
+        TextEncoding::Latin1 => Cow::Owned(decode_latin1(bytes)),
+    };
+    let mut counter = StreamCounter::new(options);
+    counter.feed(bytes)?;
+    let stats = counter.finish()?;
+    let mut summary = format!(
+        "{{\"chars\":{},\"bytes\":{},\"words\":{},\"lines\":{},\"graphemes\":{}",
+        stats.chars,
+        stats.bytes,
+        stats.words,
+        stats.lines,
+        unicode::count_graphemes(&text),
+    );
+    if options.with_digest {
+        summary.push_str(&format!(",\"sha256\":\"{}\"", digest::to_hex(&digest::sha256(bytes))));
+    }
+    summary.push('}');
+    Ok(summary)
+}
+
+fn markdown_report(stats: &TextStats) -> String {
+    let rows = [
+        ("Characters", stats.chars),
+        ("Bytes", stats.bytes),
+        ("Words", stats.words),
+        ("Lines", stats.lines),
+    ];
+    let counts: Vec<String> = rows.iter().map(|(_, count)| count.to_string()).collect();
+    let name_width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or_default();
+    let count_width = counts.iter().map(String::len).chain(["Count".len()]).max().unwrap_or_default();
+
+    let mut report = format!("| {:<name_width$} | {:>count_width$} |\n", "Metric", "Count");
+    report.push_str(&format!("|{}|{}:|\n", "-".repeat(name_width + 2), "-".repeat(count_width + 1)));
+    for ((name, _), count) in rows.iter().zip(&counts) {
+        report.push_str(&format!("| {name:<name_width$} | {count:>count_width$} |\n"));
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::c_void;
+
+    use super::{count_all, count_encoded, ffi, CountOptions, StreamCounter, TextEncoding, TextStats, WordRule};
+    use crate::modules::error::CountErrorCode;
+    use crate::testutil::Rng;
+
+    /// Characters the generated texts are drawn from: ASCII, combining marks, CR/LF, Unicode
+    /// whitespace, characters next to the surrogate range, the replacement character that lossy
+    /// decoding produces, and 3- and 4-byte characters.
+    const ALPHABET: [char; 24] = [
+        'a', 'Z', '7', ' ', '\t', '\n', '\r', '-', '\u{301}', '\u{308}', '\u{903}', '\u{a0}', '\u{2028}',
+        '\u{3000}', '\u{d7ff}', '\u{e000}', '\u{fffd}', 'é', 'ß', '日', '本', '🦀', '\u{1f1fa}', '\u{200d}',
+    ];
+
+    /// Strings up to 64 characters from `ALPHABET`, some with CRLF runs, plus lossy decodings of
+    /// random bytes.
+    fn generated_texts() -> Vec<String> {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        let mut texts = vec![String::new(), "\r\n\r\n".to_owned(), "\n".to_owned(), "a\r".to_owned()];
+        for _ in 0..500 {
+            let len = rng.below(65);
+            let mut text: String = (0..len).map(|_| ALPHABET[rng.below(ALPHABET.len())]).collect();
+            if rng.below(4) == 0 {
+                text.push_str(&"\r\n".repeat(rng.below(4) + 1));
+            }
+            texts.push(text);
+        }
+        for _ in 0..200 {
+            let bytes: Vec<u8> = (0..rng.below(48)).map(|_| rng.next() as u8).collect();
+            texts.push(String::from_utf8_lossy(&bytes).into_owned());
+        }
+        texts
+    }
+
+    /// One line per `\n`, plus an unterminated last line.
+    fn reference_lines(text: &str) -> u64 {
+        let mut lines = 0;
+        let mut open_line = false;
+        for c in text.chars() {
+            open_line = c != '\n';
+            lines += u64::from(c == '\n');
+        }
+        lines + u64::from(open_line)
+    }
+
+    fn reference_words(text: &str) -> u64 {
+        let mut words = 0;
+        let mut in_word = false;
+        for c in text.chars() {
+            words += u64::from(!c.is_whitespace() && !in_word);
+            in_word = !c.is_whitespace();
+        }
+        words
+    }
+
+    fn every_option() -> Vec<CountOptions> {
+        let mut options = Vec::new();
+        for (word_rule, _) in WordRule::NAMES {
+            for (encoding, _) in TextEncoding::NAMES {
+                options.push(CountOptions { word_rule, encoding, ..CountOptions::default() });
+            }
+        }
+        options
+    }
+
+    fn stream_in_chunks(bytes: &[u8], chunk_sizes: &mut impl FnMut() -> usize) -> TextStats {
+        stream_with_options(bytes, &CountOptions::default(), chunk_sizes)
+    }
+
+    fn stream_with_options(bytes: &[u8], options: &CountOptions, chunk_sizes: &mut impl FnMut() -> usize) -> TextStats {
+        let mut counter = StreamCounter::new(options);
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let (chunk, tail) = rest.split_at(chunk_sizes().clamp(1, rest.len()));
+            counter.feed(chunk).unwrap();
+            rest = tail;
+        }
+        counter.finish().unwrap()
+    }
+
+    #[test]
+    fn count_all_matches_naive_references() {
+        for text in generated_texts() {
+            let stats = count_all(&text, &CountOptions::default());
+            assert_eq!(stats.bytes, text.len() as u64, "{text:?}");
+            assert_eq!(stats.chars, text.chars().count() as u64, "{text:?}");
+            assert!(stats.chars <= stats.bytes, "{text:?}");
+            assert_eq!(stats.words, text.split_whitespace().count() as u64, "{text:?}");
+            assert_eq!(stats.words, reference_words(&text), "{text:?}");
+            assert_eq!(stats.lines, reference_lines(&text), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn streaming_agrees_with_count_all_for_any_chunking() {
+        let mut rng = Rng(42);
+        for text in generated_texts() {
+            let expected = count_all(&text, &CountOptions::default());
+            for size in [1, 2, 3, 4, 5, 7, 64] {
+                assert_eq!(stream_in_chunks(text.as_bytes(), &mut || size), expected, "{text:?} in {size} byte chunks");
+            }
+            let random = stream_in_chunks(text.as_bytes(), &mut || rng.below(9));
+            assert_eq!(random, expected, "{text:?} in random chunks");
+        }
+    }
+
+    #[test]
+    fn streaming_rejects_invalid_utf8_at_any_split() {
+        let bytes = b"ab\xe6\x97\xa5\xff\xe6\x97";
+        for split in 0..=bytes.len() {
+            let mut counter = StreamCounter::default();
+            let fed = counter.feed(&bytes[..split]).and_then(|()| counter.feed(&bytes[split..]));
+            assert!(fed.and_then(|()| counter.finish()).is_err(), "split at {split}");
+        }
+        let mut counter = StreamCounter::default();
+        counter.feed(b"ok\xe6\x97").unwrap();
+        assert!(counter.finish().is_err());
+    }
+
+    #[test]
+    fn empty_stream_has_no_lines() {
+        assert_eq!(StreamCounter::default().finish().unwrap(), TextStats::default());
+    }
+
+    #[test]
+    fn streaming_honors_every_word_rule_and_encoding() {
+        let mut texts = generated_texts();
+        texts.extend(["The cat's well-known 3.14 café.", "私は東京でカタカナとコーヒーを飲みます。", "state-of-the-art end-to-end"].map(str::to_owned));
+        let mut rng = Rng(7);
+        for options in every_option() {
+            for text in &texts {
+                let expected = count_encoded(text.as_bytes(), &options).unwrap();
+                for size in [1, 2, 3, 64] {
+                    let streamed = stream_with_options(text.as_bytes(), &options, &mut || size);
+                    assert_eq!(streamed, expected, "{text:?} with {options:?} in {size} byte chunks");
+                }
+                let streamed = stream_with_options(text.as_bytes(), &options, &mut || rng.below(9));
+                assert_eq!(streamed, expected, "{text:?} with {options:?} in random chunks");
+            }
+        }
+    }
+
+    /// Hands out the bytes behind `context`, a `(&[u8], usize)` of data and read position, three
+    /// at a time.
+    unsafe extern "C" fn read_three(buf: *mut u8, buf_len: usize, context: *mut c_void) -> isize {
+        let (data, position) = &mut *(context as *mut (&[u8], usize));
+        let len = (data.len() - *position).min(buf_len).min(3);
+        std::ptr::copy_nonoverlapping(data[*position..].as_ptr(), buf, len);
+        *position += len;
+        len as isize
+    }
+
+    #[test]
+    fn count_stream_with_options_matches_count_all() {
+        let text = "カタカナ and hyphen-ated words, 日本語.";
+        for options in every_option() {
+            let mut source: (&[u8], usize) = (text.as_bytes(), 0);
+            let mut stats = TextStats::default();
+            let context = &mut source as *mut (&[u8], usize) as *mut c_void;
+            let status = ffi::count_stream_with_options(read_three, context, &options, &mut stats);
+            assert_eq!(status, CountErrorCode::Ok as i32);
+            assert_eq!(stats, count_encoded(text.as_bytes(), &options).unwrap(), "with {options:?}");
+        }
+    }
+
+    /// A source for `read_pattern` that hands out `data` in reads of `sizes` taken in turn, and
+    /// reports -5 instead of reading once `fail_at` bytes have gone out.
+    struct Source<'a> {
+        data: &'a [u8],
+        position: usize,
+        sizes: &'a [usize],
+        reads: usize,
+        fail_at: Option<usize>,
+    }
+
+    unsafe extern "C" fn read_pattern(buf: *mut u8, buf_len: usize, context: *mut c_void) -> isize {
+        let source = &mut *(context as *mut Source);
+        if source.fail_at.is_some_and(|fail_at| source.position >= fail_at) {
+            return -5;
+        }
+        let size = source.sizes[source.reads % source.sizes.len()];
+        source.reads += 1;
+        let len = (source.data.len() - source.position).min(buf_len).min(size);
+        std::ptr::copy_nonoverlapping(source.data[source.position..].as_ptr(), buf, len);
+        source.position += len;
+        len as isize
+    }
+
+    fn stream(text: &str, sizes: &[usize], fail_at: Option<usize>) -> Result<TextStats, i32> {
+        let mut source = Source { data: text.as_bytes(), position: 0, sizes, reads: 0, fail_at };
+        let mut stats = TextStats::default();
+        match ffi::count_stream(read_pattern, &mut source as *mut Source as *mut c_void, &mut stats) {
+            0 => Ok(stats),
+            status => Err(status),
+        }
+    }
+
+    #[test]
+    fn count_stream_survives_tiny_and_uneven_reads() {
+        let text = "naïve café
+日本語 🦀 text
+last line";
+        let expected = count_all(text, &CountOptions::default());
+        assert_eq!(stream(text, &[1], None), Ok(expected));
+        // Empty reads only end the stream, so the uneven sizes never include 0.
+        assert_eq!(stream(text, &[2, 7, 1, 5, 3], None), Ok(expected));
+        assert_eq!(stream(text, &[usize::MAX], None), Ok(expected));
+    }
+
+    #[test]
+    fn count_stream_reports_a_failing_read() {
+        let text = "one two three four";
+        assert_eq!(stream(text, &[4], Some(8)), Err(CountErrorCode::Io as i32));
+        let message = crate::modules::error::message_for(CountErrorCode::Io);
+        assert!(message.contains("-5"), "{message}");
+        assert_eq!(stream(text, &[1], Some(0)), Err(CountErrorCode::Io as i32));
+    }
+
+    fn words(text: &str, word_rule: WordRule) -> u64 {
+        count_all(text, &CountOptions { word_rule, ..CountOptions::default() }).words
+    }
+
+    #[test]
+    fn word_rules_agree_on_an_english_sentence() {
+        let text = "The quick brown fox jumps over the lazy dog.";
+        assert_eq!(words(text, WordRule::Whitespace), 9);
+        assert_eq!(words(text, WordRule::UnicodeWords), 9);
+    }
+
+    #[test]
+    fn unicode_words_split_a_japanese_sentence() {
+        // 私 は 東 京 で each count, as do コーヒー and を 飲 み ま す.
+        let text = "私は東京でコーヒーを飲みます。";
+        assert_eq!(words(text, WordRule::Whitespace), 1);
+        assert_eq!(words(text, WordRule::UnicodeWords), 11);
+    }
+
+    #[test]
+    fn hyphens_separate_unicode_words_only() {
+        let text = "a well-known state-of-the-art design";
+        assert_eq!(words(text, WordRule::Whitespace), 4);
+        assert_eq!(words(text, WordRule::UnicodeWords), 8);
+    }
+
+    fn summary(text: &str, options: Option<&CountOptions>) -> String {
+        let text = std::ffi::CString::new(text).unwrap();
+        let summary = ffi::count_summary_json_with_options(text.as_ptr(), options.map_or(std::ptr::null(), |o| o as *const _));
+        assert!(!summary.is_null());
+        unsafe { std::ffi::CString::from_raw(summary) }.into_string().unwrap()
+    }
+
+    #[test]
+    fn summary_json_adds_the_digest_only_when_asked() {
+        assert_eq!(summary("abc", None), r#"{"chars":3,"bytes":3,"words":1,"lines":1,"graphemes":3}"#);
+        let options = CountOptions { with_digest: true, ..CountOptions::default() };
+        assert_eq!(
+            summary("abc", Some(&options)),
+            r#"{"chars":3,"bytes":3,"words":1,"lines":1,"graphemes":3,"sha256":"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"}"#
+        );
+        assert_eq!(
+            summary("", Some(&options)),
+            r#"{"chars":0,"bytes":0,"words":0,"lines":0,"graphemes":0,"sha256":"e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"}"#
+        );
+    }
+
+    #[test]
+    fn summary_json_fields_match_independent_counts() {
+        let texts = ["", "abc", "naïve café\n", "one\ntwo three\n\nfour", "\u{D55C}\u{AD6D} \u{1F1FA}\u{1F1F8}\r\n"];
+        let graphemes = [0, 3, 11, 19, 5];
+        for (text, graphemes) in texts.into_iter().zip(graphemes) {
+            let expected = format!(
+                r#"{{"chars":{},"bytes":{},"words":{},"lines":{},"graphemes":{}}}"#,
+                text.chars().count(),
+                text.len(),
+                reference_words(text),
+                reference_lines(text),
+                graphemes,
+            );
+            assert_eq!(summary(text, None), expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn markdown_report_has_a_row_per_metric() {
+        let text = std::ffi::CString::new("naïve café\n").unwrap();
+        let report = ffi::count_report_markdown(text.as_ptr());
+        assert!(!report.is_null());
+        let report = unsafe { std::ffi::CString::from_raw(report) }.into_string().unwrap();
+        let expected = "\
+| Metric     | Count |
+|------------|------:|
+| Characters |    11 |
+| Bytes      |    13 |
+| Words      |     2 |
+| Lines      |     1 |
+";
+        assert_eq!(report, expected);
+        assert!(ffi::count_report_markdown(std::ptr::null()).is_null());
+    }
+}
@@ -0,0 +1,1816 @@
+mod ffi {
+    use std::ffi::{c_void, CString};
+    use std::os::raw::c_char;
+    use std::{ptr, slice};
+
+    use crate::modules::args;
+    use crate::modules::error::{self, CountErrorCode};
+    use crate::modules::limits;
+    use crate::modules::stats::{self, StreamCounter, WordRule};
+    use crate::modules::unicode;
+    use crate::Command;
+
+    #[no_mangle]
+    pub extern "C" fn weighted_char_count(
+        text: *const c_char,
+        weight_cb: unsafe extern "C" fn(codepoint: u32, context: *const c_void) -> u64,
+        context: *const c_void,
+        out: *mut u64,
+    ) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            let weight = error::guard_callback(|| {
+                super::weighted_count(text, |c| unsafe { weight_cb(c as u32, context) })
+            })?;
+            unsafe { error::write_out(out, weight) }
+        })
+    }
+
+    #[no_mangle]
+    pub extern "C" fn first_distinct_chars(text: *const c_char, n: u64) -> *mut c_char {
+        match unsafe { error::str_from_ptr(text) } {
+            Ok(text) => {
+                let distinct = super::first_distinct(text, n.try_into().unwrap_or(usize::MAX));
+                CString::new(distinct).unwrap().into_raw()
+            }
+            Err(_) => ptr::null_mut(),
+        }
+    }
+
+    #[no_mangle]
+    pub extern "C" fn window_char_counts(
+        text: *const c_char,
+        window: u64,
+        cb: unsafe extern "C" fn(window_index: u64, count: u64, context: *const c_void),
+        context: *const c_void,
+    ) {
+        if let Ok(text) = unsafe { error::str_from_ptr(text) } {
+            let window = window.try_into().unwrap_or(usize::MAX);
+            super::window_counts(text, window, |index, count| unsafe { cb(index, count, context) });
+        }
+    }
+
+    /// Groups are reported in order of first appearance. Blocks come from a built-in table of the
+    /// common ones: the Latin, Greek, Cyrillic, Armenian, Hebrew, Arabic, Syriac, Devanagari,
+    /// Bengali, Thai, Georgian and Hangul blocks, general punctuation and symbols, kana and CJK
+    /// ideographs (including Extension B), and the main emoji blocks. Characters in any other
+    /// block are counted under "Unknown".
+    #[no_mangle]
+    pub extern "C" fn for_each_block(
+        text: *const c_char,
+        cb: unsafe extern "C" fn(block_name: *const c_char, count: u64, context: *const c_void),
+        context: *const c_void,
+    ) {
+        if let Ok(text) = unsafe { error::str_from_ptr(text) } {
+            for (block_name, count) in super::block_counts(text) {
+                let block_name = CString::new(block_name).unwrap();
+                unsafe { cb(block_name.as_ptr(), count, context) };
+            }
+        }
+    }
+
+    /// Groups are reported in order of first appearance. Letters are assigned a script from a
+    /// built-in table covering Latin, Greek, Cyrillic, Armenian, Hebrew, Arabic, Syriac,
+    /// Devanagari, Bengali, Thai, Georgian, Hangul, Hiragana, Katakana and Han; other letters are
+    /// "Unknown". Combining marks are "Inherited" and everything else, such as digits,
+    /// punctuation and spaces, is "Common".
+    #[no_mangle]
+    pub extern "C" fn for_each_script(
+        text: *const c_char,
+        cb: unsafe extern "C" fn(script_name: *const c_char, count: u64, context: *const c_void),
+        context: *const c_void,
+    ) {
+        if let Ok(text) = unsafe { error::str_from_ptr(text) } {
+            for (script_name, count) in super::script_counts(text) {
+                let script_name = CString::new(script_name).unwrap();
+                unsafe { cb(script_name.as_ptr(), count, context) };
+            }
+        }
+    }
+
+    /// Words are whitespace-separated and measured in characters; lengths are reported in
+    /// ascending order.
+    #[no_mangle]
+    pub extern "C" fn for_each_word_length(
+        text: *const c_char,
+        cb: unsafe extern "C" fn(length: u64, count: u64, context: *const c_void),
+        context: *const c_void,
+    ) {
+        if let Ok(text) = unsafe { error::str_from_ptr(text) } {
+            for (length, count) in super::word_length_counts(text) {
+                unsafe { cb(length, count, context) };
+            }
+        }
+    }
+
+    #[no_mangle]
+    pub extern "C" fn count_characters_capped(
+        text: *const c_char,
+        max_bytes: usize,
+        out_chars: *mut u64,
+        out_truncated: *mut bool,
+    ) -> i32 {
+        error::status(|| {
+            if text.is_null() {
+                return Err(CountErrorCode::NullPointer);
+            }
+            let bytes = unsafe { bytes_up_to(text, max_bytes.saturating_add(1)) };
+            let truncated = bytes.len() > max_bytes;
+            let chars = super::count_chars_prefix(&bytes[..bytes.len().min(max_bytes)])?;
+            unsafe { error::write_out(out_chars, chars) }?;
+            unsafe { error::write_out(out_truncated, truncated) }
+        })
+    }
+
+    /// Characters in the Mark categories (Mn, Mc, Me) as `general_category` classifies them, so
+    /// a decomposed `"e\u{301}"` has 1. Marks are only recognized in the ranges its tables list:
+    /// the common combining and enclosing diacritics, and the Devanagari and Bengali vowel signs,
+    /// including spacing (Mc) ones such as U+093E.
+    #[no_mangle]
+    pub extern "C" fn count_combining_marks(text: *const c_char, out: *mut u64) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            let marks = text.chars().filter(|&c| unicode::in_category(c, "M")).count();
+            unsafe { error::write_out(out, marks as u64) }
+        })
+    }
+
+    #[no_mangle]
+    pub extern "C" fn count_excluding_categories(
+        text: *const c_char,
+        categories: *const c_char,
+        out: *mut u64,
+    ) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            let categories = unsafe { error::str_from_ptr(categories) }?;
+            let count = super::count_excluding_categories(text, categories)?;
+            unsafe { error::write_out(out, count) }
+        })
+    }
+
+    /// Returns 1 when a NUL byte appears before the last of the `len` bytes, meaning the data would
+    /// be cut short (or rejected) when passed as a C string; a single trailing terminator is fine.
+    /// Returns 0 otherwise, including for a NULL `text` with `len` 0, and -1 for NULL with `len`
+    /// above 0.
+    #[no_mangle]
+    pub extern "C" fn contains_interior_nul(text: *const c_char, len: usize) -> i32 {
+        if len == 0 {
+            return 0;
+        }
+        if text.is_null() {
+            return -1;
+        }
+        let bytes = unsafe { slice::from_raw_parts(text as *const u8, len) };
+        bytes[..len - 1].contains(&0) as i32
+    }
+
+    /// Counts `len` bytes the way Python's `surrogateescape` decodes them: valid UTF-8 sequences
+    /// count as one character each, and every byte that is not part of one counts as one unit of
+    /// its own. Strict counting rejects such input instead, and a truncated multi-byte sequence
+    /// here counts one unit per byte rather than one character. NUL bytes are ordinary
+    /// characters. A NULL `bytes` is only accepted with `len` 0.
+    #[no_mangle]
+    pub extern "C" fn count_characters_os(bytes: *const c_char, len: usize, out: *mut u64) -> i32 {
+        error::status(|| {
+            let bytes = match (bytes.is_null(), len) {
+                (_, 0) => &[][..],
+                (true, _) => return Err(CountErrorCode::NullPointer),
+                (false, _) => unsafe { slice::from_raw_parts(bytes as *const u8, len) },
+            };
+            limits::check_text_bytes(bytes.len())?;
+            unsafe { error::write_out(out, super::count_chars_lossless(bytes)) }
+        })
+    }
+
+    /// The Unicode scalar values of `text`, one `u32` each, with their number in `out_len`.
+    /// Returns NULL when `text` is NULL or not UTF-8, with the cause in `last_error_message`.
+    /// Empty text gives a non-NULL array of length 0. Free with `decode_codepoints_free`.
+    #[no_mangle]
+    pub extern "C" fn decode_codepoints(text: *const c_char, out_len: *mut usize) -> *mut u32 {
+        let mut decoded = ptr::null_mut();
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            let codepoints: Box<[u32]> = text.chars().map(u32::from).collect();
+            unsafe { error::write_out(out_len, codepoints.len()) }?;
+            decoded = Box::into_raw(codepoints) as *mut u32;
+            Ok(())
+        });
+        decoded
+    }
+
+    /// `len` must be the length `decode_codepoints` reported.
+    #[no_mangle]
+    pub extern "C" fn decode_codepoints_free(codepoints: *mut u32, len: usize) {
+        if !codepoints.is_null() {
+            drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(codepoints, len)) });
+        }
+    }
+
+    /// Guesses which supported encoding the `len` bytes are in and writes its `--encoding` name,
+    /// `"utf8"` or `"latin1"`, to `out_name` (free with `count_string_free`). Non-ASCII input
+    /// that decodes as UTF-8 scores 100; pure ASCII decodes the same either way and scores 50.
+    /// Anything else is Latin-1, scored by the share of its high bytes outside the C1 control
+    /// range 0x80-0x9F, which real Latin-1 text rarely uses. A NULL `bytes` is only accepted with
+    /// `len` 0.
+    #[no_mangle]
+    pub extern "C" fn detect_encoding(
+        bytes: *const c_char,
+        len: usize,
+        out_name: *mut *mut c_char,
+        out_confidence_x100: *mut u64,
+    ) -> i32 {
+        error::status(|| {
+            let bytes = match (bytes.is_null(), len) {
+                (_, 0) => &[][..],
+                (true, _) => return Err(CountErrorCode::NullPointer),
+                (false, _) => unsafe { slice::from_raw_parts(bytes as *const u8, len) },
+            };
+            limits::check_text_bytes(bytes.len())?;
+            if out_name.is_null() {
+                return Err(CountErrorCode::NullPointer);
+            }
+            let (encoding, confidence) = super::detect_encoding(bytes);
+            unsafe { error::write_out(out_confidence_x100, confidence) }?;
+            unsafe { out_name.write(CString::new(encoding.name()).unwrap().into_raw()) };
+            Ok(())
+        })
+    }
+
+    /// Per-mille of the `len` bytes that are ASCII control bytes other than tab, line feed, form
+    /// feed and carriage return. Bytes from 0x80 up count as text, so UTF-8 and Latin-1 both
+    /// score low. Empty input scores 0. A NULL `text` is only accepted with `len` 0.
+    #[no_mangle]
+    pub extern "C" fn binary_ratio_x1000(text: *const c_char, len: usize, out: *mut u64) -> i32 {
+        error::status(|| {
+            let bytes = match (text.is_null(), len) {
+                (_, 0) => &[][..],
+                (true, _) => return Err(CountErrorCode::NullPointer),
+                (false, _) => unsafe { slice::from_raw_parts(text as *const u8, len) },
+            };
+            limits::check_text_bytes(bytes.len())?;
+            unsafe { error::write_out(out, super::binary_ratio_x1000(bytes)) }
+        })
+    }
+
+    /// A NULL `error_offset` skips reporting where the first invalid sequence starts.
+    #[no_mangle]
+    pub extern "C" fn validate_utf8(data: *const u8, len: usize, error_offset: *mut usize) -> bool {
+        let data = if len == 0 {
+            &[]
+        } else if data.is_null() {
+            return false;
+        } else {
+            unsafe { slice::from_raw_parts(data, len) }
+        };
+        match std::str::from_utf8(data) {
+            Ok(_) => true,
+            Err(err) => {
+                if !error_offset.is_null() {
+                    unsafe { error_offset.write(err.valid_up_to()) };
+                }
+                false
+            }
+        }
+    }
+
+    /// Counts maximal runs of ASCII digits, so `1.5` is two integers. Signs are ignored: `-4`
+    /// counts the same as `4`, and `1-2` is two integers.
+    #[no_mangle]
+    pub extern "C" fn count_integers(text: *const c_char, out: *mut u64) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            unsafe { error::write_out(out, super::count_integers(text)) }
+        })
+    }
+
+    #[no_mangle]
+    pub extern "C" fn count_sections(
+        text: *const c_char,
+        separator: *const c_char,
+        metric: Command,
+        cb: unsafe extern "C" fn(section_index: u64, count: u64, context: *const c_void),
+        context: *const c_void,
+    ) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            let separator = unsafe { error::str_from_ptr(separator) }?;
+            for (index, section) in super::sections(text, separator).iter().enumerate() {
+                let count = super::run_metric(&metric, section)?;
+                unsafe { cb(index as u64, count, context) };
+            }
+            Ok(())
+        })
+    }
+
+    #[no_mangle]
+    pub extern "C" fn count_chars_in_set(text: *const c_char, set: *const c_char, out: *mut u64) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            let set = unsafe { error::str_from_ptr(set) }?;
+            unsafe { error::write_out(out, super::count_chars_in_set(text, set)) }
+        })
+    }
+
+    /// Ties go to the shorter length; text without lines reports 0.
+    #[no_mangle]
+    pub extern "C" fn modal_line_length(text: *const c_char, out: *mut u64) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            unsafe { error::write_out(out, super::modal_line_length(text)) }
+        })
+    }
+
+    /// Understands `\n`, `\t`, `\\` and `\xNN` (taken as the code point U+00NN); any other
+    /// escape fails with `CountErrorCode_InvalidArgument`.
+    #[no_mangle]
+    pub extern "C" fn count_characters_unescaped(text: *const c_char, out: *mut u64) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            let unescaped = super::unescape(text)?;
+            unsafe { error::write_out(out, unescaped.chars().count() as u64) }
+        })
+    }
+
+    /// Spaces and tabs at the end of each line; line terminators are not counted.
+    #[no_mangle]
+    pub extern "C" fn count_trailing_whitespace_bytes(text: *const c_char, out: *mut u64) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            unsafe { error::write_out(out, super::trailing_whitespace_bytes(text)) }
+        })
+    }
+
+    /// Consecutive `\n` bytes ending the text; a `\r` before one stops the run.
+    #[no_mangle]
+    pub extern "C" fn count_trailing_newlines(text: *const c_char, out: *mut u64) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            let count = text.bytes().rev().take_while(|&byte| byte == b'\n').count();
+            unsafe { error::write_out(out, count as u64) }
+        })
+    }
+
+    /// Lines longer than `max_len` characters; terminators are not counted.
+    #[no_mangle]
+    pub extern "C" fn count_long_lines(text: *const c_char, max_len: u64, out: *mut u64) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            unsafe { error::write_out(out, super::long_lines(text, max_len, |_, _| ())) }
+        })
+    }
+
+    /// Same count as `count_long_lines`, also calling `callback` with the 1-based number and the
+    /// length of each offending line. A NULL `out` skips the total.
+    #[no_mangle]
+    pub extern "C" fn for_each_long_line(
+        text: *const c_char,
+        max_len: u64,
+        callback: unsafe extern "C" fn(line_number: u64, length: u64, context: *mut c_void),
+        context: *mut c_void,
+        out: *mut u64,
+    ) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            let count = error::guard_callback(|| {
+                super::long_lines(text, max_len, |line_number, length| unsafe {
+                    callback(line_number, length, context)
+                })
+            })?;
+            if !out.is_null() {
+                unsafe { out.write(count) };
+            }
+            Ok(())
+        })
+    }
+
+    /// A sentence is text ending in one or more of `.` `!` `?`, or trailing text without one.
+    /// Words are whitespace-separated; the average is words per sentence times 100, or 0 for no
+    /// sentences. NULL outputs are skipped.
+    #[no_mangle]
+    pub extern "C" fn readability_stats(
+        text: *const c_char,
+        out_sentences: *mut u64,
+        out_words: *mut u64,
+        out_avg_words_x100: *mut u64,
+    ) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            let sentences = super::count_sentences(text);
+            let words = stats::count_words(text, WordRule::Whitespace);
+            let average = (words * 100).checked_div(sentences).unwrap_or(0);
+            let outputs = [(out_sentences, sentences), (out_words, words), (out_avg_words_x100, average)];
+            for (out, value) in outputs {
+                if !out.is_null() {
+                    unsafe { out.write(value) };
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// A URL starts at the first `http://` or `https://` in a whitespace-separated token and runs
+    /// to the end of that token, punctuation included; a bare scheme is not counted.
+    #[no_mangle]
+    pub extern "C" fn count_urls(text: *const c_char, out: *mut u64) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            unsafe { error::write_out(out, super::urls(text).count() as u64) }
+        })
+    }
+
+    /// Same matching as `count_urls`, calling `callback` with each URL. A NULL `out` skips the
+    /// total.
+    #[no_mangle]
+    pub extern "C" fn for_each_url(
+        text: *const c_char,
+        callback: unsafe extern "C" fn(url: *const c_char, context: *mut c_void),
+        context: *mut c_void,
+        out: *mut u64,
+    ) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            let mut count = 0;
+            for url in super::urls(text) {
+                let url = CString::new(url).unwrap();
+                error::guard_callback(|| unsafe { callback(url.as_ptr(), context) })?;
+                count += 1;
+            }
+            if !out.is_null() {
+                unsafe { out.write(count) };
+            }
+            Ok(())
+        })
+    }
+
+    /// Lines identical to the line right before them, as `uniq -d` sees runs: `a a b b b` has
+    /// three repeats.
+    #[no_mangle]
+    pub extern "C" fn count_consecutive_duplicate_lines(text: *const c_char, out: *mut u64) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            unsafe { error::write_out(out, super::consecutive_duplicate_lines(text)) }
+        })
+    }
+
+    /// Calls `cb` once per non-empty bucket in ascending order, with the bucket's lower bound. Line
+    /// lengths are in characters without terminators; a `bucket_size` of 0 fails with
+    /// `CountErrorCode_InvalidArgument`.
+    #[no_mangle]
+    pub extern "C" fn line_length_histogram(
+        text: *const c_char,
+        bucket_size: u64,
+        cb: unsafe extern "C" fn(bucket_start: u64, count: u64, context: *const c_void),
+        context: *const c_void,
+    ) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            if bucket_size == 0 {
+                return error::fail(CountErrorCode::InvalidArgument, "Bucket size must be positive.");
+            }
+            error::guard_callback(|| {
+                for (bucket_start, count) in super::line_length_buckets(text, bucket_size) {
+                    unsafe { cb(bucket_start, count, context) };
+                }
+            })
+        })
+    }
+
+    /// Compares the sets of distinct lines: `added` counts lines of `b` missing from `a`, and
+    /// `removed` the reverse. Order and repetition are ignored.
+    #[no_mangle]
+    pub extern "C" fn count_line_diff(
+        a: *const c_char,
+        b: *const c_char,
+        out_added: *mut u64,
+        out_removed: *mut u64,
+    ) -> i32 {
+        error::status(|| {
+            let a = unsafe { error::str_from_ptr(a) }?;
+            let b = unsafe { error::str_from_ptr(b) }?;
+            let (added, removed) = super::line_diff(a, b);
+            unsafe { error::write_out(out_added, added) }?;
+            unsafe { error::write_out(out_removed, removed) }
+        })
+    }
+
+    /// The character at the 1-based `rank` when distinct characters are ordered by descending
+    /// count, then ascending code point. A `rank` of 0 or past the last distinct character fails
+    /// with `CountErrorCode_InvalidArgument`.
+    #[no_mangle]
+    pub extern "C" fn nth_frequent_char(
+        text: *const c_char,
+        rank: u64,
+        out_codepoint: *mut u32,
+        out_count: *mut u64,
+    ) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            let ranked = super::chars_by_frequency(text);
+            let index = usize::try_from(rank).ok().and_then(|rank| rank.checked_sub(1));
+            let (c, count) = match index.and_then(|index| ranked.get(index)) {
+                Some(entry) => *entry,
+                None => {
+                    let message = format!("Rank {rank} is outside 1..={} distinct characters", ranked.len());
+                    return error::fail(CountErrorCode::InvalidArgument, message);
+                }
+            };
+            unsafe { error::write_out(out_codepoint, c as u32) }?;
+            unsafe { error::write_out(out_count, count) }
+        })
+    }
+
+    /// Replaces every run of Unicode whitespace with one space, leading and trailing runs
+    /// included. `out_removed` gets how many whitespace characters were dropped, so a lone tab
+    /// turned into a space removes none. Free `*out` with `count_string_free`.
+    #[no_mangle]
+    pub extern "C" fn collapse_whitespace(text: *const c_char, out: *mut *mut c_char, out_removed: *mut u64) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            if out.is_null() || out_removed.is_null() {
+                return Err(CountErrorCode::NullPointer);
+            }
+            let (collapsed, removed) = super::collapse_whitespace(text);
+            unsafe {
+                out_removed.write(removed);
+                out.write(CString::new(collapsed).unwrap().into_raw());
+            }
+            Ok(())
+        })
+    }
+
+    /// Rewrites every line ending (`\r\n`, a lone `\r` or a lone `\n`) as LF for `style` 0,
+    /// CRLF for 1 or CR for 2, counting in `out_changed` the endings that were in another form.
+    /// Other styles fail with `CountErrorCode_InvalidArgument`. Free `*out` with
+    /// `count_string_free`.
+    #[no_mangle]
+    pub extern "C" fn normalize_newlines(
+        text: *const c_char,
+        style: u32,
+        out: *mut *mut c_char,
+        out_changed: *mut u64,
+    ) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            if out.is_null() || out_changed.is_null() {
+                return Err(CountErrorCode::NullPointer);
+            }
+            let ending = match super::NEWLINE_STYLES.get(style as usize) {
+                Some(ending) => ending,
+                None => return error::fail(CountErrorCode::InvalidArgument, format!("Unknown newline style: {style}")),
+            };
+            let (normalized, changed) = super::normalize_newlines(text, ending);
+            unsafe {
+                out_changed.write(changed);
+                out.write(CString::new(normalized).unwrap().into_raw());
+            }
+            Ok(())
+        })
+    }
+
+    /// Character-level Levenshtein distance between `a` and `b`. Either string being longer than
+    /// 4096 characters fails with `CountErrorCode_InvalidArgument`, since the cost grows with the
+    /// product of the lengths.
+    #[no_mangle]
+    pub extern "C" fn edit_distance(a: *const c_char, b: *const c_char, out: *mut u64) -> i32 {
+        error::status(|| {
+            let a = unsafe { error::str_from_ptr(a) }?;
+            let b = unsafe { error::str_from_ptr(b) }?;
+            for text in [a, b] {
+                if text.chars().count() > super::MAX_EDIT_DISTANCE_CHARS {
+                    let message = format!("Edit distance is limited to {} characters", super::MAX_EDIT_DISTANCE_CHARS);
+                    return error::fail(CountErrorCode::InvalidArgument, message);
+                }
+            }
+            unsafe { error::write_out(out, args::edit_distance(a, b) as u64) }
+        })
+    }
+
+    /// Whitespace-separated words of `text`, reading it in 4 KiB steps and stopping at the first
+    /// step that passes `limit`. Then `out` gets `limit` and `out_exceeded` true; otherwise the
+    /// full count and false. Input after the stopping point is never read, so invalid UTF-8 or
+    /// the configured text limit there are not reported.
+    #[no_mangle]
+    pub extern "C" fn count_words_capped(
+        text: *const c_char,
+        limit: u64,
+        out: *mut u64,
+        out_exceeded: *mut bool,
+    ) -> i32 {
+        error::status(|| {
+            if text.is_null() || out.is_null() || out_exceeded.is_null() {
+                return Err(CountErrorCode::NullPointer);
+            }
+            let mut counter = StreamCounter::default();
+            let mut offset = 0;
+            loop {
+                let chunk = unsafe { bytes_up_to(text.add(offset), super::CAPPED_CHUNK_BYTES) };
+                counter.feed(chunk)?;
+                offset += chunk.len();
+                if counter.words() > limit || chunk.len() < super::CAPPED_CHUNK_BYTES {
+                    break;
+                }
+            }
+            let exceeded = counter.words() > limit;
+            let words = if exceeded { limit } else { counter.finish()?.words };
+            unsafe {
+                out.write(words);
+                out_exceeded.write(exceeded);
+            }
+            Ok(())
+        })
+    }
+
+    /// Whitespace-separated tokens matching `pattern`, where `*` matches any run of characters and
+    /// `?` exactly one; everything else matches itself. The whole token must match.
+    #[no_mangle]
+    pub extern "C" fn count_glob_matches(text: *const c_char, pattern: *const c_char, out: *mut u64) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            let pattern: Vec<char> = unsafe { error::str_from_ptr(pattern) }?.chars().collect();
+            let count = text.split_whitespace().filter(|token| super::glob_matches(&pattern, token)).count();
+            unsafe { error::write_out(out, count as u64) }
+        })
+    }
+
+    /// Characters in `transform(text)` minus characters in `text`. The output is released with
+    /// `free_output`, so it may come from any allocator; a NULL output fails with
+    /// `CountErrorCode_InvalidArgument`.
+    #[no_mangle]
+    pub extern "C" fn count_transform_delta(
+        text: *const c_char,
+        transform: unsafe extern "C" fn(input: *const c_char, context: *const c_void) -> *mut c_char,
+        free_output: unsafe extern "C" fn(*mut c_char),
+        context: *const c_void,
+        out_delta: *mut i64,
+    ) -> i32 {
+        error::status(|| {
+            let before = unsafe { error::str_from_ptr(text) }?.chars().count();
+            let output = error::guard_callback(|| unsafe { transform(text, context) })?;
+            if output.is_null() {
+                return error::fail(CountErrorCode::InvalidArgument, "Transform returned NULL");
+            }
+            let after = unsafe { error::str_from_ptr(output) }.map(|output| output.chars().count());
+            error::guard_callback(|| unsafe { free_output(output) })?;
+            unsafe { error::write_out(out_delta, after? as i64 - before as i64) }
+        })
+    }
+
+    /// Blocks of non-blank lines separated by one or more blank lines, where a blank line is
+    /// empty or only whitespace. Blank lines at either end do not start a paragraph.
+    #[no_mangle]
+    pub extern "C" fn count_paragraphs(text: *const c_char, out: *mut u64) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            unsafe { error::write_out(out, super::count_paragraphs(text)) }
+        })
+    }
+
+    /// Line breaks before the first and after the last non-whitespace character, so
+    /// `"\n\na\n\n\n"` has 2 leading and 3 trailing blank lines and `"a\n"` has 1 trailing.
+    /// Text that is all whitespace counts every line break as leading.
+    #[no_mangle]
+    pub extern "C" fn count_edge_blank_lines(
+        text: *const c_char,
+        out_leading: *mut u64,
+        out_trailing: *mut u64,
+    ) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            let (leading, trailing) = super::edge_blank_lines(text);
+            unsafe { error::write_out(out_leading, leading) }?;
+            unsafe { error::write_out(out_trailing, trailing) }
+        })
+    }
+
+    /// Overlapping runs of `n` characters: the character count minus `n - 1`, or 0 for text
+    /// shorter than `n`. An `n` of 0 fails with `CountErrorCode_InvalidArgument`.
+    #[no_mangle]
+    pub extern "C" fn count_char_ngrams(text: *const c_char, n: u64, out: *mut u64) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            if n == 0 {
+                return error::fail(CountErrorCode::InvalidArgument, "N-gram length must be positive.");
+            }
+            let chars = text.chars().count() as u64;
+            unsafe { error::write_out(out, (chars + 1).saturating_sub(n)) }
+        })
+    }
+
+    /// Calls `cb` once per distinct n-gram counted by `count_char_ngrams`, in order of first
+    /// appearance, with how often it occurs.
+    #[no_mangle]
+    pub extern "C" fn char_ngram_frequencies(
+        text: *const c_char,
+        n: u64,
+        cb: unsafe extern "C" fn(ngram: *const c_char, count: u64, context: *const c_void),
+        context: *const c_void,
+    ) -> i32 {
+        error::status(|| {
+            let text = unsafe { error::str_from_ptr(text) }?;
+            if n == 0 {
+                return error::fail(CountErrorCode::InvalidArgument, "N-gram length must be positive.");
+            }
+            let frequencies = super::ngram_frequencies(text, n.try_into().unwrap_or(usize::MAX));
+            error::guard_callback(|| {
+                for (ngram, count) in frequencies {
+                    let ngram = CString::new(ngram).unwrap();
+                    unsafe { cb(ngram.as_ptr(), count, context) };
+                }
+            })
+        })
+    }
+
+    unsafe fn bytes_up_to<'a>(text: *const c_char, limit: usize) -> &'a [u8] {
+        let mut len = 0;
+        while len < limit && *text.add(len) != 0 {
+            len += 1;
+        }
+        slice::from_raw_parts(text as *const u8, len)
+    }
+}
+
+use crate::modules::error::{self, CountErrorCode};
+use crate::modules::stats::{self, TextEncoding, WordRule};
+use crate::modules::unicode;
+use crate::Command;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+fn weighted_count(text: &str, weight: impl Fn(char) -> u64) -> u64 {
+    text.chars().fold(0, |total, c| total.saturating_add(weight(c)))
+}
+
+fn first_distinct(text: &str, n: usize) -> String {
+    let mut seen = HashSet::new();
+    text.chars().filter(|c| seen.insert(*c)).take(n).collect()
+}
+
+fn window_counts(text: &str, window: usize, callback: impl Fn(u64, u64)) {
+    if window == 0 {
+        return;
+    }
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    for (index, block) in lines.chunks(window).enumerate() {
+        let count: usize = block.iter().map(|line| line.chars().count()).sum();
+        callback(index as u64, count as u64);
+    }
+}
+
+fn block_counts(text: &str) -> Vec<(&'static str, u64)> {
+    group_counts(text, unicode::block_name)
+}
+
+fn script_counts(text: &str) -> Vec<(&'static str, u64)> {
+    group_counts(text, unicode::script_name)
+}
+
+fn group_counts(text: &str, group: impl Fn(char) -> &'static str) -> Vec<(&'static str, u64)> {
+    let mut counts: Vec<(&'static str, u64)> = Vec::new();
+    for c in text.chars() {
+        let name = group(c);
+        match counts.iter_mut().find(|(existing, _)| *existing == name) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((name, 1)),
+        }
+    }
+    counts
+}
+
+fn word_length_counts(text: &str) -> BTreeMap<u64, u64> {
+    let mut counts = BTreeMap::new();
+    for word in text.split_whitespace() {
+        *counts.entry(word.chars().count() as u64).or_default() += 1;
+    }
+    counts
+}
+
+fn count_chars_lossless(bytes: &[u8]) -> u64 {
+    bytes.utf8_chunks().map(|chunk| (chunk.valid().chars().count() + chunk.invalid().len()) as u64).sum()
+}
+
+fn detect_encoding(bytes: &[u8]) -> (TextEncoding, u64) {
+    if bytes.is_ascii() {
+        return (TextEncoding::Utf8, 50);
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return (TextEncoding::Utf8, 100);
+    }
+    let high = bytes.iter().filter(|&&byte| byte >= 0x80).count();
+    let c1 = bytes.iter().filter(|&&byte| (0x80..=0x9f).contains(&byte)).count();
+    (TextEncoding::Latin1, ((high - c1) * 100 / high) as u64)
+}
+
+/// `is_ascii_whitespace` leaves out vertical tab, so it counts as a control byte here.
+fn binary_ratio_x1000(bytes: &[u8]) -> u64 {
+    if bytes.is_empty() {
+        return 0;
+    }
+    let control = bytes.iter().filter(|byte| byte.is_ascii_control() && !byte.is_ascii_whitespace()).count();
+    (control * 1000 / bytes.len()) as u64
+}
+
+fn edge_blank_lines(text: &str) -> (u64, u64) {
+    let newlines = |edge: &str| edge.matches('\n').count() as u64;
+    let start = text.len() - text.trim_start().len();
+    if start == text.len() {
+        return (newlines(text), 0);
+    }
+    (newlines(&text[..start]), newlines(&text[text.trim_end().len()..]))
+}
+
+fn count_paragraphs(text: &str) -> u64 {
+    let mut previous_blank = true;
+    let mut paragraphs = 0;
+    for line in text.lines() {
+        let blank = line.trim().is_empty();
+        if previous_blank && !blank {
+            paragraphs += 1;
+        }
+        previous_blank = blank;
+    }
+    paragraphs
+}
+
+fn count_chars_prefix(bytes: &[u8]) -> Result<u64, CountErrorCode> {
+    let valid = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(error) if error.error_len().is_none() => {
+            std::str::from_utf8(&bytes[..error.valid_up_to()]).unwrap()
+        }
+        Err(_) => return Err(CountErrorCode::InvalidUtf8),
+    };
+    Ok(valid.chars().count() as u64)
+}
+
+fn count_excluding_categories(text: &str, categories: &str) -> Result<u64, CountErrorCode> {
+    let categories: Vec<&str> = categories.split(',').map(str::trim).filter(|c| !c.is_empty()).collect();
+    if let Some(unknown) = categories.iter().find(|c| !unicode::is_category(c)) {
+        return error::fail(CountErrorCode::InvalidArgument, format!("Unknown general category: {unknown}"));
+    }
+    let count = text
+        .chars()
+        .filter(|&c| !categories.iter().any(|category| unicode::in_category(c, category)))
+        .count();
+    Ok(count as u64)
+}
+
+fn count_integers(text: &str) -> u64 {
+    text.split(|c: char| !c.is_ascii_digit())
+        .filter(|run| !run.is_empty())
+        .count() as u64
+}
+
+pub fn run_metric(metric: &Command, text: &str) -> Result<u64, CountErrorCode> {
+    match metric {
+        Command::Bytes => Ok(text.len() as u64),
+        Command::Characters => Ok(text.chars().count() as u64),
+        Command::Words => Ok(stats::count_words(text, WordRule::Whitespace)),
+        Command::Version => error::fail(CountErrorCode::InvalidArgument, "Version is not a metric."),
+        Command::Check => error::fail(CountErrorCode::InvalidArgument, "Check is not a metric."),
+    }
+}
+
+fn sections(text: &str, separator: &str) -> Vec<String> {
+    let mut sections = vec![String::new()];
+    for line in text.split_inclusive('\n') {
+        if line.trim_end_matches(['\r', '\n']) == separator {
+            sections.push(String::new());
+        } else {
+            sections.last_mut().unwrap().push_str(line);
+        }
+    }
+    sections
+}
+
+fn count_chars_in_set(text: &str, set: &str) -> u64 {
+    let set: HashSet<char> = set.chars().collect();
+    text.chars().filter(|c| set.contains(c)).count() as u64
+}
+
+fn modal_line_length(text: &str) -> u64 {
+    let mut frequencies: HashMap<usize, u64> = HashMap::new();
+    for line in text.lines() {
+        *frequencies.entry(line.chars().count()).or_default() += 1;
+    }
+    frequencies
+        .into_iter()
+        .max_by(|(len_a, count_a), (len_b, count_b)| count_a.cmp(count_b).then(len_b.cmp(len_a)))
+        .map_or(0, |(len, _)| len as u64)
+}
+
+fn long_lines(text: &str, max_len: u64, mut on_long_line: impl FnMut(u64, u64)) -> u64 {
+    let mut count = 0;
+    for (index, line) in text.lines().enumerate() {
+        let length = line.chars().count() as u64;
+        if length > max_len {
+            on_long_line(index as u64 + 1, length);
+            count += 1;
+        }
+    }
+    count
+}
+
+fn count_sentences(text: &str) -> u64 {
+    let mut sentences = 0;
+    let mut in_sentence = false;
+    for c in text.chars() {
+        if matches!(c, '.' | '!' | '?') {
+            if in_sentence {
+                sentences += 1;
+                in_sentence = false;
+            }
+        } else if !c.is_whitespace() {
+            in_sentence = true;
+        }
+    }
+    sentences + in_sentence as u64
+}
+
+fn urls(text: &str) -> impl Iterator<Item = &str> {
+    text.split_whitespace().filter_map(|token| {
+        let start = ["http://", "https://"]
+            .iter()
+            .filter_map(|scheme| token.find(scheme).map(|index| (index, scheme.len())))
+            .min()?;
+        let url = &token[start.0..];
+        (url.len() > start.1).then_some(url)
+    })
+}
+
+/// N-grams must not contain NUL, which `str_from_ptr` input never does.
+fn ngram_frequencies(text: &str, n: usize) -> Vec<(&str, u64)> {
+    let starts: Vec<usize> = text.char_indices().map(|(index, _)| index).chain([text.len()]).collect();
+    let mut order = Vec::new();
+    let mut counts: HashMap<&str, u64> = HashMap::new();
+    for window in starts.windows(n.saturating_add(1)) {
+        let ngram = &text[window[0]..window[n]];
+        let count = counts.entry(ngram).or_insert(0);
+        if *count == 0 {
+            order.push(ngram);
+        }
+        *count += 1;
+    }
+    order.into_iter().map(|ngram| (ngram, counts[ngram])).collect()
+}
+
+fn line_length_buckets(text: &str, bucket_size: u64) -> BTreeMap<u64, u64> {
+    let mut buckets = BTreeMap::new();
+    for line in text.lines() {
+        let length = line.chars().count() as u64;
+        *buckets.entry(length - length % bucket_size).or_default() += 1;
+    }
+    buckets
+}
+
+/// Greedy matching that backtracks only to the most recent `*`, which is enough because a later
+/// star can absorb anything an earlier one could.
+fn glob_matches(pattern: &[char], token: &str) -> bool {
+    let token: Vec<char> = token.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None;
+    while t < token.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                last_star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == token[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match last_star {
+                Some((star, matched)) => {
+                    last_star = Some((star, matched + 1));
+                    p = star + 1;
+                    t = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+const MAX_EDIT_DISTANCE_CHARS: usize = 4096;
+
+/// Bytes `count_words_capped` reads between checks against its limit.
+const CAPPED_CHUNK_BYTES: usize = 4096;
+
+/// Indexed by the `style` argument of `normalize_newlines`.
+const NEWLINE_STYLES: [&str; 3] = ["\n", "\r\n", "\r"];
+
+fn normalize_newlines(text: &str, ending: &str) -> (String, u64) {
+    let mut normalized = String::with_capacity(text.len());
+    let mut changed = 0;
+    let mut rest = text;
+    while let Some(index) = rest.find(['\r', '\n']) {
+        normalized.push_str(&rest[..index]);
+        let found = if rest[index..].starts_with("\r\n") { "\r\n" } else { &rest[index..index + 1] };
+        normalized.push_str(ending);
+        changed += u64::from(found != ending);
+        rest = &rest[index + found.len()..];
+    }
+    normalized.push_str(rest);
+    (normalized, changed)
+}
+
+fn collapse_whitespace(text: &str) -> (String, u64) {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut removed = 0;
+    let mut in_run = false;
+    for c in text.chars() {
+        if !c.is_whitespace() {
+            collapsed.push(c);
+            in_run = false;
+        } else if in_run {
+            removed += 1;
+        } else {
+            collapsed.push(' ');
+            in_run = true;
+        }
+    }
+    (collapsed, removed)
+}
+
+fn chars_by_frequency(text: &str) -> Vec<(char, u64)> {
+    let mut counts: HashMap<char, u64> = HashMap::new();
+    for c in text.chars() {
+        *counts.entry(c).or_default() += 1;
+    }
+    let mut ranked: Vec<(char, u64)> = counts.into_iter().collect();
+    ranked.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    ranked
+}
+
+fn line_diff(a: &str, b: &str) -> (u64, u64) {
+    let a: HashSet<&str> = a.lines().collect();
+    let b: HashSet<&str> = b.lines().collect();
+    (b.difference(&a).count() as u64, a.difference(&b).count() as u64)
+}
+
+fn consecutive_duplicate_lines(text: &str) -> u64 {
+    let lines: Vec<&str> = text.lines().collect();
+    lines.windows(2).filter(|pair| pair[0] == pair[1]).count() as u64
+}
+
+fn trailing_whitespace_bytes(text: &str) -> u64 {
+    text.lines()
+        .map(|line| (line.len() - line.trim_end_matches([' ', '\t']).len()) as u64)
+        .sum()
+}
+
+fn unescape(text: &str) -> Result<String, CountErrorCode> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        let escaped = match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('\\') => '\\',
+            Some('x') => {
+                let digits: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if digits.len() == 2 => char::from(byte),
+                    _ => {
+                        let message = format!("Malformed escape: \\x{digits}");
+                        return error::fail(CountErrorCode::InvalidArgument, message);
+                    }
+                }
+            }
+            Some(other) => {
+                return error::fail(CountErrorCode::InvalidArgument, format!("Unknown escape: \\{other}"));
+            }
+            None => return error::fail(CountErrorCode::InvalidArgument, "Trailing backslash."),
+        };
+        unescaped.push(escaped);
+    }
+    Ok(unescaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{c_void, CStr, CString};
+    use std::os::raw::c_char;
+
+    use super::ffi;
+    use crate::modules::error::CountErrorCode;
+    use crate::Command;
+
+    fn combining_marks(text: &str) -> u64 {
+        let text = CString::new(text).unwrap();
+        let mut marks = u64::MAX;
+        assert_eq!(ffi::count_combining_marks(text.as_ptr(), &mut marks), 0);
+        marks
+    }
+
+    #[test]
+    fn counts_a_decomposed_accent() {
+        assert_eq!(combining_marks("e\u{301}"), 1);
+        assert_eq!(combining_marks("\u{E9}"), 0);
+    }
+
+    #[test]
+    fn counts_spacing_marks() {
+        assert_eq!(combining_marks("\u{915}\u{93E}"), 1);
+        assert_eq!(combining_marks("\u{915}\u{903}\u{915}\u{93F}\u{915}\u{940}"), 3);
+    }
+
+    unsafe extern "C" fn collect_group(name: *const c_char, count: u64, context: *const c_void) {
+        let groups = unsafe { &mut *(context as *mut Vec<(String, u64)>) };
+        let name = unsafe { CStr::from_ptr(name) }.to_str().unwrap();
+        groups.push((name.to_owned(), count));
+    }
+
+    fn blocks(text: &str) -> Vec<(String, u64)> {
+        let text = CString::new(text).unwrap();
+        let mut groups: Vec<(String, u64)> = Vec::new();
+        ffi::for_each_block(text.as_ptr(), collect_group, &mut groups as *mut _ as *const c_void);
+        groups
+    }
+
+    #[test]
+    fn groups_latin_and_greek_into_two_blocks() {
+        assert_eq!(blocks("abcαβ"), [("Basic Latin".to_owned(), 3), ("Greek and Coptic".to_owned(), 2)]);
+    }
+
+    #[test]
+    fn reports_unlisted_blocks_as_unknown() {
+        assert_eq!(blocks("a\u{10900}"), [("Basic Latin".to_owned(), 1), ("Unknown".to_owned(), 1)]);
+    }
+
+    fn scripts(text: &str) -> Vec<(String, u64)> {
+        let text = CString::new(text).unwrap();
+        let mut groups: Vec<(String, u64)> = Vec::new();
+        ffi::for_each_script(text.as_ptr(), collect_group, &mut groups as *mut _ as *const c_void);
+        groups
+    }
+
+    #[test]
+    fn groups_latin_and_cyrillic_into_two_scripts() {
+        assert_eq!(scripts("helloмир"), [("Latin".to_owned(), 5), ("Cyrillic".to_owned(), 3)]);
+    }
+
+    #[test]
+    fn reports_marks_and_punctuation_as_inherited_and_common() {
+        assert_eq!(
+            scripts("e\u{301}!"),
+            [("Latin".to_owned(), 1), ("Inherited".to_owned(), 1), ("Common".to_owned(), 1)]
+        );
+    }
+
+    unsafe extern "C" fn vowel_weight(codepoint: u32, _: *const c_void) -> u64 {
+        if "aeiou".contains(char::from_u32(codepoint).unwrap()) {
+            2
+        } else {
+            1
+        }
+    }
+
+    #[test]
+    fn weighs_vowels_twice() {
+        let text = CString::new("banana split").unwrap();
+        let mut weight = 0;
+        assert_eq!(ffi::weighted_char_count(text.as_ptr(), vowel_weight, std::ptr::null(), &mut weight), 0);
+        assert_eq!(weight, 12 + 4);
+        let status = ffi::weighted_char_count(std::ptr::null(), vowel_weight, std::ptr::null(), &mut weight);
+        assert_eq!(status, CountErrorCode::NullPointer as i32);
+    }
+
+    fn first_distinct(text: &str, n: u64) -> String {
+        let text = CString::new(text).unwrap();
+        let distinct = ffi::first_distinct_chars(text.as_ptr(), n);
+        assert!(!distinct.is_null());
+        unsafe { CString::from_raw(distinct) }.into_string().unwrap()
+    }
+
+    #[test]
+    fn returns_the_first_distinct_chars() {
+        assert_eq!(first_distinct("banana", 3), "ban");
+        assert_eq!(first_distinct("banana", 10), "ban");
+        assert_eq!(first_distinct("naïve", 0), "");
+        assert!(ffi::first_distinct_chars(std::ptr::null(), 3).is_null());
+    }
+
+    unsafe extern "C" fn collect_pair(first: u64, second: u64, context: *const c_void) {
+        let pairs = unsafe { &mut *(context as *mut Vec<(u64, u64)>) };
+        pairs.push((first, second));
+    }
+
+    #[test]
+    fn windows_over_five_lines_fire_three_times() {
+        let text = CString::new("a\nbb\nccc\ndddd\neeeee").unwrap();
+        let mut windows: Vec<(u64, u64)> = Vec::new();
+        ffi::window_char_counts(text.as_ptr(), 2, collect_pair, &mut windows as *mut _ as *const c_void);
+        assert_eq!(windows, [(0, 5), (1, 9), (2, 5)]);
+    }
+
+    #[test]
+    fn a_zero_window_never_fires() {
+        let text = CString::new("a\nb\n").unwrap();
+        let mut windows: Vec<(u64, u64)> = Vec::new();
+        ffi::window_char_counts(text.as_ptr(), 0, collect_pair, &mut windows as *mut _ as *const c_void);
+        assert!(windows.is_empty());
+    }
+
+    fn capped(text: &str, max_bytes: usize) -> (u64, bool) {
+        let text = CString::new(text).unwrap();
+        let (mut chars, mut truncated) = (u64::MAX, false);
+        assert_eq!(ffi::count_characters_capped(text.as_ptr(), max_bytes, &mut chars, &mut truncated), 0);
+        (chars, truncated)
+    }
+
+    #[test]
+    fn a_char_straddling_the_cap_is_excluded() {
+        // "é" takes bytes 1 and 2, so a 2-byte cap keeps only the "a".
+        assert_eq!(capped("aéb", 2), (1, true));
+        assert_eq!(capped("aéb", 3), (2, true));
+        assert_eq!(capped("aéb", 4), (3, false));
+        assert_eq!(capped("", 0), (0, false));
+    }
+
+    /// Runs an export that writes a `u64` through its last argument, asserting that it succeeds.
+    fn counted(export: impl FnOnce(*mut u64) -> i32) -> u64 {
+        let mut out = u64::MAX;
+        assert_eq!(export(&mut out), CountErrorCode::Ok as i32);
+        out
+    }
+
+    #[test]
+    fn excludes_spaces_and_other_punctuation() {
+        let text = CString::new("Hello, world! Bye.").unwrap();
+        let categories = CString::new("Zs,Po").unwrap();
+        let count = counted(|out| ffi::count_excluding_categories(text.as_ptr(), categories.as_ptr(), out));
+        assert_eq!(count, 13);
+        let letters_only = CString::new("Zs, P").unwrap();
+        assert_eq!(counted(|out| ffi::count_excluding_categories(text.as_ptr(), letters_only.as_ptr(), out)), 13);
+        let unknown = CString::new("Zs,Qq").unwrap();
+        let status = ffi::count_excluding_categories(text.as_ptr(), unknown.as_ptr(), &mut 0);
+        assert_eq!(status, CountErrorCode::InvalidArgument as i32);
+    }
+
+    /// `(valid, error_offset)` for `data`, with the offset left at `usize::MAX` when valid.
+    fn validate(data: &[u8]) -> (bool, usize) {
+        let mut offset = usize::MAX;
+        let valid = ffi::validate_utf8(data.as_ptr(), data.len(), &mut offset);
+        (valid, offset)
+    }
+
+    #[test]
+    fn reports_the_offset_of_the_first_invalid_sequence() {
+        assert_eq!(validate("naïve".as_bytes()), (true, usize::MAX));
+        // Truncated two-, three- and four-byte sequences at the end.
+        assert_eq!(validate(b"ab\xC3"), (false, 2));
+        assert_eq!(validate(b"ab\xE2\x82"), (false, 2));
+        assert_eq!(validate(b"a\xF0\x9F\x98"), (false, 1));
+        // Invalid bytes in the middle.
+        assert_eq!(validate(b"abc\xFFdef"), (false, 3));
+        assert_eq!(validate(b"\xC3\xA9\x80z"), (false, 2));
+        assert_eq!(validate(b"ok\xED\xA0\x80"), (false, 2));
+    }
+
+    #[test]
+    fn validate_utf8_handles_empty_and_null_input() {
+        assert!(ffi::validate_utf8(std::ptr::null(), 0, std::ptr::null_mut()));
+        assert!(!ffi::validate_utf8(std::ptr::null(), 1, std::ptr::null_mut()));
+        assert!(!ffi::validate_utf8(b"\xFF".as_ptr(), 1, std::ptr::null_mut()));
+    }
+
+    fn integers(text: &str) -> u64 {
+        let text = CString::new(text).unwrap();
+        counted(|out| ffi::count_integers(text.as_ptr(), out))
+    }
+
+    #[test]
+    fn counts_integers_with_and_without_signs() {
+        assert_eq!(integers("a12 b3 -4"), 3);
+        assert_eq!(integers("a12 b3 4"), 3);
+        // Signs are ignored, so they never add or split an integer.
+        assert_eq!(integers("+7-8"), 2);
+        assert_eq!(integers("- + 1.5"), 2);
+        assert_eq!(integers("none"), 0);
+    }
+
+    #[test]
+    fn counts_three_sections_divided_by_dashes() {
+        let text = CString::new("one\n---\ntwo two\n---\nthree\n").unwrap();
+        let separator = CString::new("---").unwrap();
+        let mut sections: Vec<(u64, u64)> = Vec::new();
+        let context = &mut sections as *mut _ as *const c_void;
+        let status = ffi::count_sections(text.as_ptr(), separator.as_ptr(), Command::Words, collect_pair, context);
+        assert_eq!(status, CountErrorCode::Ok as i32);
+        assert_eq!(sections, [(0, 1), (1, 2), (2, 1)]);
+
+        sections.clear();
+        let context = &mut sections as *mut _ as *const c_void;
+        ffi::count_sections(text.as_ptr(), separator.as_ptr(), Command::Bytes, collect_pair, context);
+        assert_eq!(sections, [(0, 4), (1, 8), (2, 6)]);
+    }
+
+    fn chars_in_set(text: &str, set: &str) -> u64 {
+        let (text, set) = (CString::new(text).unwrap(), CString::new(set).unwrap());
+        counted(|out| ffi::count_chars_in_set(text.as_ptr(), set.as_ptr(), out))
+    }
+
+    #[test]
+    fn counts_vowels_from_a_set() {
+        assert_eq!(chars_in_set("education rocks", "aeiou"), 6);
+        assert_eq!(chars_in_set("AEIOU", "aeiou"), 0);
+        assert_eq!(chars_in_set("naïve", "ï"), 1);
+        assert_eq!(chars_in_set("anything", ""), 0);
+    }
+
+    fn modal_line_length(text: &str) -> u64 {
+        let text = CString::new(text).unwrap();
+        counted(|out| ffi::modal_line_length(text.as_ptr(), out))
+    }
+
+    #[test]
+    fn two_lines_of_one_length_win_the_mode() {
+        assert_eq!(modal_line_length("abc\nx\ndef\nlonger line\n"), 3);
+        // Ties go to the shorter length.
+        assert_eq!(modal_line_length("abcd\nab\n"), 2);
+        assert_eq!(modal_line_length(""), 0);
+    }
+
+    fn unescaped_chars(text: &str) -> Result<u64, i32> {
+        let text = CString::new(text).unwrap();
+        let mut out = u64::MAX;
+        match ffi::count_characters_unescaped(text.as_ptr(), &mut out) {
+            0 => Ok(out),
+            status => Err(status),
+        }
+    }
+
+    #[test]
+    fn counts_an_escape_as_one_char() {
+        assert_eq!(unescaped_chars("a\\nb"), Ok(3));
+        assert_eq!(unescaped_chars("tab\\there\\\\"), Ok(9));
+        assert_eq!(unescaped_chars("plain"), Ok(5));
+        assert_eq!(unescaped_chars("dangling\\"), Err(CountErrorCode::InvalidArgument as i32));
+    }
+
+    fn trailing_whitespace(text: &str) -> u64 {
+        let text = CString::new(text).unwrap();
+        counted(|out| ffi::count_trailing_whitespace_bytes(text.as_ptr(), out))
+    }
+
+    #[test]
+    fn counts_spaces_and_tabs_before_each_line_end() {
+        assert_eq!(trailing_whitespace("none\nhere"), 0);
+        assert_eq!(trailing_whitespace("one \ntwo  \nthree   \n"), 6);
+        assert_eq!(trailing_whitespace("tab\t \r\nlast \t"), 4);
+        assert_eq!(trailing_whitespace("  \n\n"), 2);
+    }
+
+    fn word_lengths(text: &str) -> Vec<(u64, u64)> {
+        let text = CString::new(text).unwrap();
+        let mut lengths: Vec<(u64, u64)> = Vec::new();
+        ffi::for_each_word_length(text.as_ptr(), collect_pair, &mut lengths as *mut _ as *const c_void);
+        lengths
+    }
+
+    #[test]
+    fn reports_word_lengths_in_ascending_order() {
+        assert_eq!(word_lengths("a bb cc ddd"), [(1, 1), (2, 2), (3, 1)]);
+        assert_eq!(word_lengths("ddd\ta\n  bb"), [(1, 1), (2, 1), (3, 1)]);
+        // Lengths are in characters, not bytes.
+        assert_eq!(word_lengths("naïve café"), [(4, 1), (5, 1)]);
+        assert_eq!(word_lengths("   "), []);
+    }
+
+    fn interior_nul(bytes: &[u8]) -> i32 {
+        ffi::contains_interior_nul(bytes.as_ptr() as *const c_char, bytes.len())
+    }
+
+    #[test]
+    fn finds_nul_bytes_before_the_last_one() {
+        assert_eq!(interior_nul(b"plain"), 0);
+        assert_eq!(interior_nul(b"terminated\0"), 0);
+        assert_eq!(interior_nul(b"cut\0short"), 1);
+        assert_eq!(interior_nul(b"\0\0"), 1);
+        assert_eq!(interior_nul(b""), 0);
+        assert_eq!(ffi::contains_interior_nul(std::ptr::null(), 0), 0);
+        assert_eq!(ffi::contains_interior_nul(std::ptr::null(), 1), -1);
+    }
+
+    fn long_lines(text: &str, max_len: u64) -> u64 {
+        let text = CString::new(text).unwrap();
+        counted(|out| ffi::count_long_lines(text.as_ptr(), max_len, out))
+    }
+
+    #[test]
+    fn counts_lines_past_80_characters() {
+        let text = ["a".repeat(79), "b".repeat(80), "c".repeat(81), "é".repeat(81), "d".repeat(200)].join("\n");
+        assert_eq!(long_lines(&text, 80), 3);
+        // The terminator does not count towards the length.
+        assert_eq!(long_lines(&format!("{}\r\n", "e".repeat(80)), 80), 0);
+        assert_eq!(long_lines(&text, 0), 5);
+        assert_eq!(long_lines("", 80), 0);
+    }
+
+    /// `(sentences, words, average words x100)`.
+    fn readability(text: &str) -> (u64, u64, u64) {
+        let text = CString::new(text).unwrap();
+        let (mut sentences, mut words, mut average) = (u64::MAX, u64::MAX, u64::MAX);
+        let status = ffi::readability_stats(text.as_ptr(), &mut sentences, &mut words, &mut average);
+        assert_eq!(status, CountErrorCode::Ok as i32);
+        (sentences, words, average)
+    }
+
+    #[test]
+    fn two_sentences_average_their_words() {
+        assert_eq!(readability("The cat sat. It purred loudly!"), (2, 6, 300));
+        assert_eq!(readability("One two three. Four?! Five"), (3, 5, 166));
+        assert_eq!(readability("Wait... what?"), (2, 2, 100));
+        assert_eq!(readability(""), (0, 0, 0));
+
+        let text = CString::new("Just one.").unwrap();
+        let mut words = 0;
+        let status = ffi::readability_stats(text.as_ptr(), std::ptr::null_mut(), &mut words, std::ptr::null_mut());
+        assert_eq!((status, words), (0, 2));
+    }
+
+    unsafe extern "C" fn collect_url(url: *const c_char, context: *mut c_void) {
+        let urls = unsafe { &mut *(context as *mut Vec<String>) };
+        urls.push(unsafe { CStr::from_ptr(url) }.to_str().unwrap().to_owned());
+    }
+
+    fn urls(text: &str) -> (u64, Vec<String>) {
+        let text = CString::new(text).unwrap();
+        let mut urls: Vec<String> = Vec::new();
+        let mut count = u64::MAX;
+        let status = ffi::for_each_url(text.as_ptr(), collect_url, &mut urls as *mut _ as *mut c_void, &mut count);
+        assert_eq!(status, CountErrorCode::Ok as i32);
+        assert_eq!(counted(|out| ffi::count_urls(text.as_ptr(), out)), count);
+        (count, urls)
+    }
+
+    #[test]
+    fn finds_http_and_https_urls() {
+        let (count, found) = urls("See https://example.com/a?b=1, or (http://x.org) and ftp://no.");
+        assert_eq!(count, 2);
+        assert_eq!(found, ["https://example.com/a?b=1,", "http://x.org)"]);
+        // A bare scheme does not count.
+        assert_eq!(urls("http:// https://"), (0, vec![]));
+        assert_eq!(urls("no links here"), (0, vec![]));
+    }
+
+    fn consecutive_duplicates(text: &str) -> u64 {
+        let text = CString::new(text).unwrap();
+        counted(|out| ffi::count_consecutive_duplicate_lines(text.as_ptr(), out))
+    }
+
+    #[test]
+    fn counts_lines_that_repeat_the_one_before() {
+        assert_eq!(consecutive_duplicates("a\na\nb\nb\nb\nc"), 3);
+        // Repeats must be adjacent.
+        assert_eq!(consecutive_duplicates("a\nb\na\nb"), 0);
+        assert_eq!(consecutive_duplicates("x\r\nx\n"), 1);
+        assert_eq!(consecutive_duplicates(""), 0);
+    }
+
+    fn histogram(text: &str, bucket_size: u64) -> Result<Vec<(u64, u64)>, i32> {
+        let text = CString::new(text).unwrap();
+        let mut buckets: Vec<(u64, u64)> = Vec::new();
+        let context = &mut buckets as *mut _ as *const c_void;
+        match ffi::line_length_histogram(text.as_ptr(), bucket_size, collect_pair, context) {
+            0 => Ok(buckets),
+            status => Err(status),
+        }
+    }
+
+    #[test]
+    fn buckets_line_lengths_by_ten() {
+        let text = ["a".repeat(3), "b".repeat(12), "c".repeat(15)].join("\n");
+        assert_eq!(histogram(&text, 10), Ok(vec![(0, 1), (10, 2)]));
+        assert_eq!(histogram(&text, 1), Ok(vec![(3, 1), (12, 1), (15, 1)]));
+        assert_eq!(histogram("", 10), Ok(vec![]));
+        assert_eq!(histogram(&text, 0), Err(CountErrorCode::InvalidArgument as i32));
+    }
+
+    fn trailing_newlines(text: &str) -> u64 {
+        let text = CString::new(text).unwrap();
+        counted(|out| ffi::count_trailing_newlines(text.as_ptr(), out))
+    }
+
+    #[test]
+    fn counts_the_newlines_ending_the_text() {
+        assert_eq!(trailing_newlines("a\n"), 1);
+        assert_eq!(trailing_newlines("a\n\n\n"), 3);
+        assert_eq!(trailing_newlines("a\nb"), 0);
+        // A `\r` ends the run, so CRLF endings count only their last `\n`.
+        assert_eq!(trailing_newlines("a\r\n\r\n"), 1);
+        assert_eq!(trailing_newlines(""), 0);
+    }
+
+    /// `(added, removed)` going from `a` to `b`.
+    fn line_diff(a: &str, b: &str) -> (u64, u64) {
+        let (a, b) = (CString::new(a).unwrap(), CString::new(b).unwrap());
+        let (mut added, mut removed) = (u64::MAX, u64::MAX);
+        assert_eq!(ffi::count_line_diff(a.as_ptr(), b.as_ptr(), &mut added, &mut removed), CountErrorCode::Ok as i32);
+        (added, removed)
+    }
+
+    #[test]
+    fn diffs_the_sets_of_distinct_lines() {
+        assert_eq!(line_diff("a\nb\nc", "b\nc\nd\ne"), (2, 1));
+        // Order and repetition are ignored.
+        assert_eq!(line_diff("a\nb\nb", "b\na"), (0, 0));
+        assert_eq!(line_diff("", "x\ny"), (2, 0));
+        assert_eq!(line_diff("x", ""), (0, 1));
+    }
+
+    fn nth_frequent(text: &str, rank: u64) -> Result<(char, u64), i32> {
+        let text = CString::new(text).unwrap();
+        let (mut codepoint, mut count) = (u32::MAX, u64::MAX);
+        match ffi::nth_frequent_char(text.as_ptr(), rank, &mut codepoint, &mut count) {
+            0 => Ok((char::from_u32(codepoint).unwrap(), count)),
+            status => Err(status),
+        }
+    }
+
+    #[test]
+    fn ranks_characters_by_count_then_code_point() {
+        assert_eq!(nth_frequent("aaabbc", 1), Ok(('a', 3)));
+        assert_eq!(nth_frequent("aaabbc", 2), Ok(('b', 2)));
+        assert_eq!(nth_frequent("aaabbc", 3), Ok(('c', 1)));
+        // Ties go to the lower code point.
+        assert_eq!(nth_frequent("yxéx y", 1), Ok(('x', 2)));
+        assert_eq!(nth_frequent("yxéx y", 2), Ok(('y', 2)));
+        assert_eq!(nth_frequent("yxéx y", 3), Ok((' ', 1)));
+        assert_eq!(nth_frequent("yxéx y", 4), Ok(('é', 1)));
+        let invalid = Err(CountErrorCode::InvalidArgument as i32);
+        assert_eq!(nth_frequent("aaabbc", 0), invalid);
+        assert_eq!(nth_frequent("aaabbc", 4), invalid);
+    }
+
+    fn collapsed(text: &str) -> (String, u64) {
+        let text = CString::new(text).unwrap();
+        let (mut out, mut removed) = (std::ptr::null_mut(), u64::MAX);
+        assert_eq!(ffi::collapse_whitespace(text.as_ptr(), &mut out, &mut removed), CountErrorCode::Ok as i32);
+        (unsafe { CString::from_raw(out) }.into_string().unwrap(), removed)
+    }
+
+    #[test]
+    fn collapses_each_whitespace_run_to_one_space() {
+        assert_eq!(collapsed("a   b\t\nc"), ("a b c".to_owned(), 3));
+        assert_eq!(collapsed("\ta\u{3000}b "), (" a b ".to_owned(), 0));
+        assert_eq!(collapsed("plain"), ("plain".to_owned(), 0));
+        let text = CString::new("a  b").unwrap();
+        let mut out = std::ptr::null_mut();
+        assert_eq!(ffi::collapse_whitespace(text.as_ptr(), &mut out, std::ptr::null_mut()), CountErrorCode::NullPointer as i32);
+    }
+
+    fn glob_matches(text: &str, pattern: &str) -> u64 {
+        let (text, pattern) = (CString::new(text).unwrap(), CString::new(pattern).unwrap());
+        counted(|out| ffi::count_glob_matches(text.as_ptr(), pattern.as_ptr(), out))
+    }
+
+    #[test]
+    fn counts_tokens_matching_a_glob() {
+        let text = "a.md readme.md notes.txt .md md b.mdx";
+        assert_eq!(glob_matches(text, "*.md"), 3);
+        assert_eq!(glob_matches(text, "?.md"), 1);
+        assert_eq!(glob_matches(text, "*"), 6);
+        assert_eq!(glob_matches(text, "*.*x*"), 2);
+        assert_eq!(glob_matches(text, "md"), 1);
+        assert_eq!(glob_matches("", "*"), 0);
+    }
+
+    fn os_chars(bytes: &[u8]) -> u64 {
+        counted(|out| ffi::count_characters_os(bytes.as_ptr() as *const c_char, bytes.len(), out))
+    }
+
+    #[test]
+    fn undecodable_bytes_count_one_unit_each() {
+        // A lone continuation byte is one unit between two characters.
+        assert_eq!(os_chars(b"a\x80b"), 3);
+        assert_eq!(os_chars("naïve".as_bytes()), 5);
+        // A truncated sequence counts per byte; NUL is an ordinary character.
+        assert_eq!(os_chars(b"\xE6\x97x\0"), 4);
+        assert_eq!(os_chars(b"\xFF\xFE"), 2);
+        assert_eq!(counted(|out| ffi::count_characters_os(std::ptr::null(), 0, out)), 0);
+        let mut out = 0;
+        assert_eq!(ffi::count_characters_os(std::ptr::null(), 1, &mut out), CountErrorCode::NullPointer as i32);
+    }
+
+    thread_local! {
+        static FREED_OUTPUTS: std::cell::Cell<u32> = const { std::cell::Cell::new(0) };
+    }
+
+    /// Repeats every character of `input` as many times as the `usize` behind `context`.
+    unsafe extern "C" fn repeat_chars(input: *const c_char, context: *const c_void) -> *mut c_char {
+        let times = *(context as *const usize);
+        let input = unsafe { CStr::from_ptr(input) }.to_str().unwrap();
+        let repeated: String = input.chars().flat_map(|c| std::iter::repeat_n(c, times)).collect();
+        CString::new(repeated).unwrap().into_raw()
+    }
+
+    unsafe extern "C" fn null_transform(_: *const c_char, _: *const c_void) -> *mut c_char {
+        std::ptr::null_mut()
+    }
+
+    unsafe extern "C" fn free_output(output: *mut c_char) {
+        drop(unsafe { CString::from_raw(output) });
+        FREED_OUTPUTS.with(|freed| freed.set(freed.get() + 1));
+    }
+
+    fn transform_delta(text: &str, transform: unsafe extern "C" fn(*const c_char, *const c_void) -> *mut c_char, times: usize) -> Result<i64, i32> {
+        let text = CString::new(text).unwrap();
+        let mut delta = i64::MAX;
+        let context = &times as *const usize as *const c_void;
+        match ffi::count_transform_delta(text.as_ptr(), transform, free_output, context, &mut delta) {
+            0 => Ok(delta),
+            status => Err(status),
+        }
+    }
+
+    #[test]
+    fn reports_how_many_characters_a_transform_adds() {
+        assert_eq!(transform_delta("naïve", repeat_chars, 2), Ok(5));
+        assert_eq!(transform_delta("naïve", repeat_chars, 0), Ok(-5));
+        assert_eq!(transform_delta("", repeat_chars, 2), Ok(0));
+        assert_eq!(FREED_OUTPUTS.with(|freed| freed.get()), 3);
+        assert_eq!(transform_delta("text", null_transform, 1), Err(CountErrorCode::InvalidArgument as i32));
+        assert_eq!(FREED_OUTPUTS.with(|freed| freed.get()), 3);
+    }
+
+    fn binary_ratio(bytes: &[u8]) -> u64 {
+        counted(|out| ffi::binary_ratio_x1000(bytes.as_ptr() as *const c_char, bytes.len(), out))
+    }
+
+    #[test]
+    fn scores_control_bytes_per_mille() {
+        assert_eq!(binary_ratio(b"plain text\twith\r\nwhitespace\x0c"), 0);
+        assert_eq!(binary_ratio("naïve café".as_bytes()), 0);
+        assert_eq!(binary_ratio(b"\0\x01\x7f\x0b"), 1000);
+        assert_eq!(binary_ratio(b"abc\0"), 250);
+        // Rounds down: one in three is 333.
+        assert_eq!(binary_ratio(b"a\x1bb"), 333);
+        assert_eq!(binary_ratio(b""), 0);
+    }
+
+    fn paragraphs(text: &str) -> u64 {
+        let text = CString::new(text).unwrap();
+        counted(|out| ffi::count_paragraphs(text.as_ptr(), out))
+    }
+
+    #[test]
+    fn counts_blocks_separated_by_blank_lines() {
+        assert_eq!(paragraphs("one\ntwo\n\nthree\n \t\n\n\nfour"), 3);
+        assert_eq!(paragraphs("\n\nonly one\nblock\n\n"), 1);
+        assert_eq!(paragraphs("a\r\n\r\nb\r\n"), 2);
+        assert_eq!(paragraphs(" \n\n"), 0);
+        assert_eq!(paragraphs(""), 0);
+    }
+
+    fn codepoints(text: *const c_char) -> Option<Vec<u32>> {
+        let mut len = usize::MAX;
+        let decoded = ffi::decode_codepoints(text, &mut len);
+        if decoded.is_null() {
+            return None;
+        }
+        let copy = unsafe { std::slice::from_raw_parts(decoded, len) }.to_vec();
+        ffi::decode_codepoints_free(decoded, len);
+        Some(copy)
+    }
+
+    #[test]
+    fn decodes_each_scalar_value() {
+        let text = CString::new("a😀").unwrap();
+        assert_eq!(codepoints(text.as_ptr()), Some(vec![0x61, 0x1F600]));
+        let text = CString::new("e\u{301}").unwrap();
+        assert_eq!(codepoints(text.as_ptr()), Some(vec![0x65, 0x301]));
+        let empty = CString::new("").unwrap();
+        assert_eq!(codepoints(empty.as_ptr()), Some(vec![]));
+        let invalid = CString::new(b"\xff".to_vec()).unwrap();
+        assert_eq!(codepoints(invalid.as_ptr()), None);
+        assert_eq!(codepoints(std::ptr::null()), None);
+    }
+
+    /// `(leading, trailing)`.
+    fn edge_blank_lines(text: &str) -> (u64, u64) {
+        let text = CString::new(text).unwrap();
+        let (mut leading, mut trailing) = (u64::MAX, u64::MAX);
+        assert_eq!(ffi::count_edge_blank_lines(text.as_ptr(), &mut leading, &mut trailing), CountErrorCode::Ok as i32);
+        (leading, trailing)
+    }
+
+    #[test]
+    fn counts_line_breaks_around_the_text() {
+        assert_eq!(edge_blank_lines("\n\na\n\n\n"), (2, 3));
+        assert_eq!(edge_blank_lines("a\n"), (0, 1));
+        assert_eq!(edge_blank_lines(" \n\t\nb\nc  \n \n"), (2, 2));
+        assert_eq!(edge_blank_lines("no breaks"), (0, 0));
+        assert_eq!(edge_blank_lines("\n \n\n"), (3, 0));
+        assert_eq!(edge_blank_lines(""), (0, 0));
+    }
+
+    fn char_ngrams(text: &str, n: u64) -> u64 {
+        let text = CString::new(text).unwrap();
+        counted(|out| ffi::count_char_ngrams(text.as_ptr(), n, out))
+    }
+
+    #[test]
+    fn counts_overlapping_character_runs() {
+        assert_eq!(char_ngrams("abcd", 2), 3);
+        assert_eq!(char_ngrams("abcd", 4), 1);
+        assert_eq!(char_ngrams("abcd", 5), 0);
+        assert_eq!(char_ngrams("héé", 2), 2);
+        assert_eq!(char_ngrams("", 1), 0);
+        let text = CString::new("abcd").unwrap();
+        let mut out = u64::MAX;
+        assert_eq!(ffi::count_char_ngrams(text.as_ptr(), 0, &mut out), CountErrorCode::InvalidArgument as i32);
+    }
+
+    /// `(name, confidence_x100)`.
+    fn encoding_guess(bytes: &[u8]) -> (String, u64) {
+        let (mut name, mut confidence) = (std::ptr::null_mut(), u64::MAX);
+        let status = ffi::detect_encoding(bytes.as_ptr() as *const c_char, bytes.len(), &mut name, &mut confidence);
+        assert_eq!(status, CountErrorCode::Ok as i32);
+        (unsafe { CString::from_raw(name) }.into_string().unwrap(), confidence)
+    }
+
+    #[test]
+    fn guesses_utf8_or_latin1_with_a_confidence() {
+        assert_eq!(encoding_guess("café".as_bytes()), ("utf8".to_string(), 100));
+        assert_eq!(encoding_guess(b"plain ascii"), ("utf8".to_string(), 50));
+        assert_eq!(encoding_guess(b""), ("utf8".to_string(), 50));
+        assert_eq!(encoding_guess(b"caf\xe9"), ("latin1".to_string(), 100));
+        assert_eq!(encoding_guess(b"\x85 caf\xe9"), ("latin1".to_string(), 50));
+        assert_eq!(encoding_guess(b"\x80\x9f"), ("latin1".to_string(), 0));
+        let mut confidence = 0;
+        let status = ffi::detect_encoding(b"a".as_ptr() as *const c_char, 1, std::ptr::null_mut(), &mut confidence);
+        assert_eq!(status, CountErrorCode::NullPointer as i32);
+    }
+
+    /// `(normalized, changed)`.
+    fn newlines_normalized(text: &str, style: u32) -> (String, u64) {
+        let text = CString::new(text).unwrap();
+        let (mut out, mut changed) = (std::ptr::null_mut(), u64::MAX);
+        assert_eq!(ffi::normalize_newlines(text.as_ptr(), style, &mut out, &mut changed), CountErrorCode::Ok as i32);
+        (unsafe { CString::from_raw(out) }.into_string().unwrap(), changed)
+    }
+
+    #[test]
+    fn rewrites_line_endings_in_each_style() {
+        let mixed = "a\r\nb\rc\nd";
+        assert_eq!(newlines_normalized(mixed, 0), ("a\nb\nc\nd".to_string(), 2));
+        assert_eq!(newlines_normalized(mixed, 1), ("a\r\nb\r\nc\r\nd".to_string(), 2));
+        assert_eq!(newlines_normalized(mixed, 2), ("a\rb\rc\rd".to_string(), 2));
+        assert_eq!(newlines_normalized("\n\r\r\n\n", 1), ("\r\n\r\n\r\n\r\n".to_string(), 3));
+        assert_eq!(newlines_normalized("no breaks", 2), ("no breaks".to_string(), 0));
+        let text = CString::new(mixed).unwrap();
+        let (mut out, mut changed) = (std::ptr::null_mut(), 0);
+        assert_eq!(ffi::normalize_newlines(text.as_ptr(), 3, &mut out, &mut changed), CountErrorCode::InvalidArgument as i32);
+        assert!(out.is_null());
+    }
+
+    fn distance(a: &str, b: &str) -> u64 {
+        let (a, b) = (CString::new(a).unwrap(), CString::new(b).unwrap());
+        counted(|out| ffi::edit_distance(a.as_ptr(), b.as_ptr(), out))
+    }
+
+    #[test]
+    fn measures_levenshtein_distance_in_characters() {
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("sitting", "kitten"), 3);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("same", "same"), 0);
+        assert_eq!(distance("café", "cafe"), 1);
+        let long = CString::new("a".repeat(4097)).unwrap();
+        let short = CString::new("a").unwrap();
+        let mut out = u64::MAX;
+        assert_eq!(ffi::edit_distance(long.as_ptr(), short.as_ptr(), &mut out), CountErrorCode::InvalidArgument as i32);
+        assert_eq!(out, u64::MAX);
+    }
+
+    /// `(words, exceeded)`.
+    fn words_capped(text: &[u8], limit: u64) -> (u64, bool) {
+        let text = CString::new(text).unwrap();
+        let (mut words, mut exceeded) = (u64::MAX, false);
+        assert_eq!(ffi::count_words_capped(text.as_ptr(), limit, &mut words, &mut exceeded), CountErrorCode::Ok as i32);
+        (words, exceeded)
+    }
+
+    #[test]
+    fn stops_counting_words_past_the_limit() {
+        let thousand = "word ".repeat(1000);
+        assert_eq!(words_capped(thousand.as_bytes(), 10), (10, true));
+        assert_eq!(words_capped(thousand.as_bytes(), 999), (999, true));
+        assert_eq!(words_capped(thousand.as_bytes(), 1000), (1000, false));
+        assert_eq!(words_capped(b"", 0), (0, false));
+        // The invalid byte lies past the first 4 KiB step, which already passes the limit.
+        let mut tail_invalid = thousand.into_bytes();
+        tail_invalid.push(0xFF);
+        assert_eq!(words_capped(&tail_invalid, 10), (10, true));
+        let text = CString::new(tail_invalid).unwrap();
+        let (mut words, mut exceeded) = (0, false);
+        let status = ffi::count_words_capped(text.as_ptr(), 1000, &mut words, &mut exceeded);
+        assert_eq!(status, CountErrorCode::InvalidUtf8 as i32);
+    }
+}
@@ -0,0 +1,353 @@
+const BLOCKS: &[(u32, u32, &str)] = &[
+    (0x0000, 0x007F, "Basic Latin"),
+    (0x0080, 0x00FF, "Latin-1 Supplement"),
+    (0x0100, 0x017F, "Latin Extended-A"),
+    (0x0180, 0x024F, "Latin Extended-B"),
+    (0x0250, 0x02AF, "IPA Extensions"),
+    (0x02B0, 0x02FF, "Spacing Modifier Letters"),
+    (0x0300, 0x036F, "Combining Diacritical Marks"),
+    (0x0370, 0x03FF, "Greek and Coptic"),
+    (0x0400, 0x04FF, "Cyrillic"),
+    (0x0500, 0x052F, "Cyrillic Supplement"),
+    (0x0530, 0x058F, "Armenian"),
+    (0x0590, 0x05FF, "Hebrew"),
+    (0x0600, 0x06FF, "Arabic"),
+    (0x0700, 0x074F, "Syriac"),
+    (0x0900, 0x097F, "Devanagari"),
+    (0x0980, 0x09FF, "Bengali"),
+    (0x0E00, 0x0E7F, "Thai"),
+    (0x10A0, 0x10FF, "Georgian"),
+    (0x1100, 0x11FF, "Hangul Jamo"),
+    (0x1E00, 0x1EFF, "Latin Extended Additional"),
+    (0x1F00, 0x1FFF, "Greek Extended"),
+    (0x2000, 0x206F, "General Punctuation"),
+    (0x2070, 0x209F, "Superscripts and Subscripts"),
+    (0x20A0, 0x20CF, "Currency Symbols"),
+    (0x2100, 0x214F, "Letterlike Symbols"),
+    (0x2150, 0x218F, "Number Forms"),
+    (0x2190, 0x21FF, "Arrows"),
+    (0x2200, 0x22FF, "Mathematical Operators"),
+    (0x2300, 0x23FF, "Miscellaneous Technical"),
+    (0x2500, 0x257F, "Box Drawing"),
+    (0x2580, 0x259F, "Block Elements"),
+    (0x25A0, 0x25FF, "Geometric Shapes"),
+    (0x2600, 0x26FF, "Miscellaneous Symbols"),
+    (0x2700, 0x27BF, "Dingbats"),
+    (0x3000, 0x303F, "CJK Symbols and Punctuation"),
+    (0x3040, 0x309F, "Hiragana"),
+    (0x30A0, 0x30FF, "Katakana"),
+    (0x4E00, 0x9FFF, "CJK Unified Ideographs"),
+    (0xAC00, 0xD7AF, "Hangul Syllables"),
+    (0xFB00, 0xFB4F, "Alphabetic Presentation Forms"),
+    (0xFE00, 0xFE0F, "Variation Selectors"),
+    (0xFF00, 0xFFEF, "Halfwidth and Fullwidth Forms"),
+    (0x1F300, 0x1F5FF, "Miscellaneous Symbols and Pictographs"),
+    (0x1F600, 0x1F64F, "Emoticons"),
+    (0x1F680, 0x1F6FF, "Transport and Map Symbols"),
+    (0x1F900, 0x1F9FF, "Supplemental Symbols and Pictographs"),
+    (0x20000, 0x2A6DF, "CJK Unified Ideographs Extension B"),
+];
+
+pub const UNKNOWN_BLOCK: &str = "Unknown";
+
+pub fn block_name(c: char) -> &'static str {
+    let codepoint = c as u32;
+    let index = BLOCKS.partition_point(|&(_, end, _)| end < codepoint);
+    match BLOCKS.get(index) {
+        Some(&(start, _, name)) if start <= codepoint => name,
+        _ => UNKNOWN_BLOCK,
+    }
+}
+
+const SCRIPTS: &[(u32, u32, &str)] = &[
+    (0x0041, 0x005A, "Latin"),
+    (0x0061, 0x007A, "Latin"),
+    (0x00AA, 0x00AA, "Latin"),
+    (0x00BA, 0x00BA, "Latin"),
+    (0x00C0, 0x02AF, "Latin"),
+    (0x0370, 0x03FF, "Greek"),
+    (0x0400, 0x052F, "Cyrillic"),
+    (0x0530, 0x058F, "Armenian"),
+    (0x0590, 0x05FF, "Hebrew"),
+    (0x0600, 0x06FF, "Arabic"),
+    (0x0700, 0x074F, "Syriac"),
+    (0x0900, 0x097F, "Devanagari"),
+    (0x0980, 0x09FF, "Bengali"),
+    (0x0E00, 0x0E7F, "Thai"),
+    (0x10A0, 0x10FF, "Georgian"),
+    (0x1100, 0x11FF, "Hangul"),
+    (0x1E00, 0x1EFF, "Latin"),
+    (0x1F00, 0x1FFF, "Greek"),
+    (0x3040, 0x309F, "Hiragana"),
+    (0x30A0, 0x30FF, "Katakana"),
+    (0x3400, 0x4DBF, "Han"),
+    (0x4E00, 0x9FFF, "Han"),
+    (0xAC00, 0xD7AF, "Hangul"),
+    (0xFB00, 0xFB06, "Latin"),
+    (0xFF21, 0xFF3A, "Latin"),
+    (0xFF41, 0xFF5A, "Latin"),
+    (0x20000, 0x2A6DF, "Han"),
+];
+
+/// Approximates the Unicode Script property: marks are "Inherited", other non-letters "Common",
+/// and letters outside the table "Unknown".
+pub fn script_name(c: char) -> &'static str {
+    if in_ranges(COMBINING_MARKS, c) || in_ranges(ENCLOSING_MARKS, c) {
+        return "Inherited";
+    }
+    if !c.is_alphabetic() {
+        return "Common";
+    }
+    let codepoint = c as u32;
+    let index = SCRIPTS.partition_point(|&(_, end, _)| end < codepoint);
+    match SCRIPTS.get(index) {
+        Some(&(start, _, name)) if start <= codepoint => name,
+        _ => "Unknown",
+    }
+}
+
+pub const GENERAL_CATEGORIES: &[&str] = &[
+    "Lu", "Ll", "Lt", "Lm", "Lo", "Mn", "Mc", "Me", "Nd", "Nl", "No", "Pc", "Pd", "Ps", "Pe", "Pi",
+    "Pf", "Po", "Sm", "Sc", "Sk", "So", "Zs", "Zl", "Zp", "Cc", "Cf", "Cs", "Co", "Cn",
+];
+
+const COMBINING_MARKS: &[(u32, u32)] = &[
+    (0x0300, 0x036F),
+    (0x0483, 0x0487),
+    (0x0591, 0x05BD),
+    (0x0610, 0x061A),
+    (0x064B, 0x065F),
+    (0x0900, 0x0902),
+    (0x093A, 0x093A),
+    (0x093C, 0x093C),
+    (0x0941, 0x0948),
+    (0x094D, 0x094D),
+    (0x0951, 0x0957),
+    (0x0962, 0x0963),
+    (0x0981, 0x0981),
+    (0x09BC, 0x09BC),
+    (0x09C1, 0x09C4),
+    (0x09CD, 0x09CD),
+    (0x09E2, 0x09E3),
+    (0x0E31, 0x0E31),
+    (0x0E34, 0x0E3A),
+    (0x0E47, 0x0E4E),
+    (0x1AB0, 0x1AFF),
+    (0x1DC0, 0x1DFF),
+    (0x20D0, 0x20DC),
+    (0x20E1, 0x20E1),
+    (0x20E5, 0x20F0),
+    (0x302A, 0x302D),
+    (0x3099, 0x309A),
+    (0xFE00, 0xFE0F),
+    (0xFE20, 0xFE2F),
+];
+
+// Spacing vowel signs and visarga in the Devanagari and Bengali blocks. Unlike the nonspacing
+// marks these keep the script of their block.
+const SPACING_MARKS: &[(u32, u32)] = &[
+    (0x0903, 0x0903),
+    (0x093B, 0x093B),
+    (0x093E, 0x0940),
+    (0x0949, 0x094C),
+    (0x094E, 0x094F),
+    (0x0982, 0x0983),
+    (0x09BE, 0x09C0),
+    (0x09C7, 0x09C8),
+    (0x09CB, 0x09CC),
+    (0x09D7, 0x09D7),
+];
+
+const ENCLOSING_MARKS: &[(u32, u32)] = &[(0x0488, 0x0489), (0x20DD, 0x20E0), (0x20E2, 0x20E4)];
+
+const OTHER_PUNCTUATION: &[(u32, u32)] = &[(0x00A1, 0x00BF), (0x2016, 0x205E), (0x3000, 0x303F)];
+
+fn in_ranges(ranges: &[(u32, u32)], c: char) -> bool {
+    let codepoint = c as u32;
+    ranges.iter().any(|&(start, end)| (start..=end).contains(&codepoint))
+}
+
+// Exact for ASCII, Latin-1 and the mark/punctuation ranges listed above; anything else is
+// approximated from the std predicates.
+pub fn general_category(c: char) -> &'static str {
+    match c {
+        '_' | '\u{203F}' | '\u{2040}' => return "Pc",
+        '-' | '\u{2010}'..='\u{2015}' => return "Pd",
+        '(' | '[' | '{' => return "Ps",
+        ')' | ']' | '}' => return "Pe",
+        '\u{AB}' | '\u{2018}' | '\u{201C}' => return "Pi",
+        '\u{BB}' | '\u{2019}' | '\u{201D}' => return "Pf",
+        '+' | '<' | '=' | '>' | '|' | '~' | '\u{AC}' | '\u{B1}' | '\u{D7}' | '\u{F7}' => return "Sm",
+        '\u{2200}'..='\u{22FF}' => return "Sm",
+        '$' | '\u{A2}'..='\u{A5}' | '\u{20A0}'..='\u{20CF}' => return "Sc",
+        '^' | '`' | '\u{A8}' | '\u{AF}' | '\u{B4}' | '\u{B8}' => return "Sk",
+        '\u{A6}' | '\u{A9}' | '\u{AE}' | '\u{B0}' => return "So",
+        '\u{2600}'..='\u{27BF}' | '\u{1F300}'..='\u{1F64F}' | '\u{1F680}'..='\u{1F6FF}' => return "So",
+        '\u{B2}' | '\u{B3}' | '\u{B9}' | '\u{BC}'..='\u{BE}' => return "No",
+        '\u{AA}' | '\u{BA}' => return "Lo",
+        '\u{AD}' | '\u{200B}'..='\u{200F}' | '\u{2060}'..='\u{2064}' | '\u{FEFF}' => return "Cf",
+        '\u{2028}' => return "Zl",
+        '\u{2029}' => return "Zp",
+        _ => {}
+    }
+    if in_ranges(ENCLOSING_MARKS, c) {
+        "Me"
+    } else if in_ranges(COMBINING_MARKS, c) {
+        "Mn"
+    } else if in_ranges(SPACING_MARKS, c) {
+        "Mc"
+    } else if c.is_control() {
+        "Cc"
+    } else if c.is_whitespace() {
+        "Zs"
+    } else if c.is_uppercase() {
+        "Lu"
+    } else if c.is_lowercase() {
+        "Ll"
+    } else if c.is_alphabetic() {
+        "Lo"
+    } else if c.is_numeric() {
+        "Nd"
+    } else if c.is_ascii_punctuation() || in_ranges(OTHER_PUNCTUATION, c) {
+        "Po"
+    } else if ('\u{E000}'..='\u{F8FF}').contains(&c) {
+        "Co"
+    } else {
+        "Cn"
+    }
+}
+
+/// Approximates extended grapheme clusters: a cluster continues through marks, variation
+/// selectors, emoji modifiers and tags, through a zero-width joiner and the character after it,
+/// across a pair of regional indicators, and through Hangul syllable sequences (leading,
+/// vowel and trailing jamo and the precomposed syllables), and CR LF is one cluster. Marks are
+/// only those `general_category` recognizes, and prepended characters are not joined.
+pub fn count_graphemes(text: &str) -> u64 {
+    let mut clusters = 0;
+    let mut previous: Option<char> = None;
+    let mut unpaired_indicator = false;
+    for c in text.chars() {
+        let joins = match previous {
+            None => false,
+            Some('\r') => c == '\n',
+            Some(p) if p.is_control() || c.is_control() => false,
+            Some('\u{200D}') => true,
+            Some(_) if is_regional_indicator(c) => unpaired_indicator,
+            Some(p) => joins_hangul(p, c) || extends_grapheme(c),
+        };
+        unpaired_indicator = is_regional_indicator(c) && !(joins && unpaired_indicator);
+        clusters += u64::from(!joins);
+        previous = Some(c);
+    }
+    clusters
+}
+
+fn extends_grapheme(c: char) -> bool {
+    in_category(c, "M")
+        || matches!(c, '\u{200D}' | '\u{FE00}'..='\u{FE0F}' | '\u{1F3FB}'..='\u{1F3FF}' | '\u{E0020}'..='\u{E007F}')
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Hangul {
+    Leading,
+    Vowel,
+    Trailing,
+    Lv,
+    Lvt,
+}
+
+fn hangul(c: char) -> Option<Hangul> {
+    match c {
+        '\u{1100}'..='\u{115F}' | '\u{A960}'..='\u{A97C}' => Some(Hangul::Leading),
+        '\u{1160}'..='\u{11A7}' | '\u{D7B0}'..='\u{D7C6}' => Some(Hangul::Vowel),
+        '\u{11A8}'..='\u{11FF}' | '\u{D7CB}'..='\u{D7FB}' => Some(Hangul::Trailing),
+        // Every 28th precomposed syllable has no trailing consonant.
+        '\u{AC00}'..='\u{D7A3}' if (c as u32 - 0xAC00).is_multiple_of(28) => Some(Hangul::Lv),
+        '\u{AC00}'..='\u{D7A3}' => Some(Hangul::Lvt),
+        _ => None,
+    }
+}
+
+fn joins_hangul(previous: char, c: char) -> bool {
+    use Hangul::*;
+    matches!(
+        (hangul(previous), hangul(c)),
+        (Some(Leading), Some(Leading | Vowel | Lv | Lvt))
+            | (Some(Lv | Vowel), Some(Vowel | Trailing))
+            | (Some(Lvt | Trailing), Some(Trailing))
+    )
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+pub fn is_category(category: &str) -> bool {
+    GENERAL_CATEGORIES.contains(&category)
+        || GENERAL_CATEGORIES.iter().any(|known| known[..1] == *category)
+}
+
+pub fn in_category(c: char, category: &str) -> bool {
+    let actual = general_category(c);
+    actual == category || (category.len() == 1 && actual.starts_with(category))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_graphemes, general_category, in_category, script_name};
+
+    #[test]
+    fn classifies_nonspacing_marks() {
+        assert_eq!(general_category('\u{301}'), "Mn");
+        assert_eq!(general_category('\u{941}'), "Mn");
+        assert_eq!(general_category('\u{9CD}'), "Mn");
+    }
+
+    #[test]
+    fn classifies_spacing_marks() {
+        for c in ['\u{903}', '\u{93E}', '\u{93F}', '\u{940}', '\u{94C}', '\u{9BE}', '\u{9D7}'] {
+            assert_eq!(general_category(c), "Mc", "U+{:04X}", c as u32);
+            assert!(in_category(c, "M"));
+        }
+        assert_eq!(general_category('\u{915}'), "Lo");
+    }
+
+    #[test]
+    fn spacing_marks_keep_their_script() {
+        assert_eq!(script_name('\u{93E}'), "Devanagari");
+        assert_eq!(script_name('\u{9BE}'), "Bengali");
+        assert_eq!(script_name('\u{301}'), "Inherited");
+    }
+
+    #[test]
+    fn joins_an_emoji_zwj_sequence() {
+        assert_eq!(count_graphemes("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"), 1);
+        assert_eq!(count_graphemes("\u{1F44D}\u{1F3FD}!"), 2);
+    }
+
+    #[test]
+    fn pairs_regional_indicators() {
+        assert_eq!(count_graphemes("\u{1F1FA}\u{1F1F8}"), 1);
+        assert_eq!(count_graphemes("\u{1F1FA}\u{1F1F8}\u{1F1EB}"), 2);
+        assert_eq!(count_graphemes("\u{1F1FA}\u{1F1F8}\u{1F1EB}\u{1F1F7}"), 2);
+    }
+
+    #[test]
+    fn joins_hangul_jamo_into_syllables() {
+        assert_eq!(count_graphemes("\u{1100}\u{1161}\u{11A8}"), 1);
+        assert_eq!(count_graphemes("\u{1100}\u{1161}\u{1100}\u{1161}"), 2);
+        // 가 (LV) takes a trailing jamo, 각 (LVT) does not take a vowel.
+        assert_eq!(count_graphemes("\u{AC00}\u{11A8}"), 1);
+        assert_eq!(count_graphemes("\u{AC01}\u{1161}"), 2);
+        assert_eq!(count_graphemes("\u{1100}\u{AC00}"), 1);
+        assert_eq!(count_graphemes("\u{D55C}\u{AD6D}\u{C5B4}"), 3);
+    }
+
+    #[test]
+    fn joins_marks_and_crlf() {
+        assert_eq!(count_graphemes("e\u{301}"), 1);
+        assert_eq!(count_graphemes("\u{915}\u{93E}"), 1);
+        assert_eq!(count_graphemes("a\r\nb"), 3);
+        assert_eq!(count_graphemes(""), 0);
+    }
+}
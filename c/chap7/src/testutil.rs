@@ -0,0 +1,101 @@
+//! Helpers for tests that call the C exports from Rust.
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+
+/// Owns the strings of an `argv` array. The pointer array handed out by `build` stays valid
+/// until the next `push` or `build`; the strings themselves stay put for the builder's lifetime.
+#[derive(Default)]
+pub struct ArgvBuilder {
+    arguments: Vec<CString>,
+    pointers: Vec<*const c_char>,
+}
+
+impl ArgvBuilder {
+    pub fn new(arguments: &[&str]) -> ArgvBuilder {
+        let mut builder = ArgvBuilder::default();
+        for argument in arguments {
+            builder.push(argument);
+        }
+        builder
+    }
+
+    pub fn push(&mut self, argument: &str) -> &mut ArgvBuilder {
+        self.arguments.push(CString::new(argument).unwrap());
+        self
+    }
+
+    /// `(argc, argv)`, with `argv[argc]` NULL as for `main`.
+    pub fn build(&mut self) -> (usize, *const *const c_char) {
+        self.pointers.clear();
+        self.pointers.extend(self.arguments.iter().map(|argument| argument.as_ptr()));
+        self.pointers.push(ptr::null());
+        (self.arguments.len(), self.pointers.as_ptr())
+    }
+}
+
+/// xorshift64, so every run of a generated-input test checks the same inputs.
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+    use std::os::raw::c_char;
+
+    use super::ArgvBuilder;
+
+    unsafe fn strings(argc: usize, argv: *const *const c_char) -> Vec<String> {
+        (0..argc).map(|index| CStr::from_ptr(*argv.add(index)).to_str().unwrap().to_owned()).collect()
+    }
+
+    #[test]
+    fn build_yields_every_argument_and_a_null_terminator() {
+        let mut builder = ArgvBuilder::new(&["count", "words", "notes.md"]);
+        let (argc, argv) = builder.build();
+        assert_eq!(argc, 3);
+        assert_eq!(unsafe { strings(argc, argv) }, ["count", "words", "notes.md"]);
+        assert!(unsafe { *argv.add(argc) }.is_null());
+    }
+
+    #[test]
+    fn argument_pointers_survive_later_pushes() {
+        let mut builder = ArgvBuilder::new(&["count", "words"]);
+        let (_, argv) = builder.build();
+        let first = unsafe { [*argv, *argv.add(1)] };
+        for index in 0..1000 {
+            builder.push(&format!("--flag-{index}"));
+        }
+        let (argc, argv) = builder.build();
+        assert_eq!(argc, 1002);
+        assert_eq!(unsafe { [*argv, *argv.add(1)] }, first);
+        assert_eq!(unsafe { CStr::from_ptr(first[1]) }.to_str().unwrap(), "words");
+        assert_eq!(unsafe { strings(argc, argv) }.last().unwrap(), "--flag-999");
+        assert!(unsafe { *argv.add(argc) }.is_null());
+    }
+
+    #[test]
+    fn an_empty_builder_has_argc_zero() {
+        let mut builder = ArgvBuilder::default();
+        let (argc, argv) = builder.build();
+        assert_eq!(argc, 0);
+        assert!(unsafe { *argv }.is_null());
+    }
+}
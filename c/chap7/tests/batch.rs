@@ -0,0 +1,99 @@
+mod common;
+
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::os::raw::c_char;
+use std::ptr;
+
+use common::Root;
+use count::api::CountErrorCode;
+
+#[repr(C)]
+struct FileCount {
+    filename: *mut c_char,
+    status: i32,
+    chars: u64,
+    bytes: u64,
+}
+
+#[repr(C)]
+struct BatchOptions {
+    threads: usize,
+}
+
+extern "C" {
+    fn count_files_batch_with_options(
+        csv: *const c_char,
+        options: *const BatchOptions,
+        out: *mut *mut FileCount,
+        out_len: *mut usize,
+    ) -> i32;
+    fn file_count_array_free(counts: *mut FileCount, len: usize);
+}
+
+/// `(filename, status, chars, bytes)` for each entry, copied out of the returned array.
+fn batch(csv: &str, threads: usize) -> Vec<(String, i32, u64, u64)> {
+    let csv = CString::new(csv).unwrap();
+    let options = BatchOptions { threads };
+    let (mut out, mut out_len) = (ptr::null_mut(), 0);
+    let status = unsafe { count_files_batch_with_options(csv.as_ptr(), &options, &mut out, &mut out_len) };
+    assert_eq!(status, CountErrorCode::Ok as i32);
+    let counts = unsafe { std::slice::from_raw_parts(out, out_len) }
+        .iter()
+        .map(|count| {
+            let filename = unsafe { CStr::from_ptr(count.filename) }.to_str().unwrap().to_owned();
+            (filename, count.status, count.chars, count.bytes)
+        })
+        .collect();
+    unsafe { file_count_array_free(out, out_len) };
+    counts
+}
+
+fn root_with_files(count: usize) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    for index in 0..count {
+        fs::write(dir.path().join(format!("{index}.md")), "é".repeat(index)).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn a_missing_file_only_fails_its_own_entry() {
+    let dir = root_with_files(3);
+    let _root = Root::set(Some(dir.path()));
+    let ok = CountErrorCode::Ok as i32;
+    assert_eq!(
+        batch("0.md, 1.md, missing.md, 2.md", 2),
+        [
+            ("0.md".to_owned(), ok, 0, 0),
+            ("1.md".to_owned(), ok, 1, 2),
+            ("missing.md".to_owned(), CountErrorCode::FileNotFound as i32, 0, 0),
+            ("2.md".to_owned(), ok, 2, 4),
+        ]
+    );
+}
+
+#[test]
+fn results_do_not_depend_on_scheduling() {
+    let dir = root_with_files(40);
+    let _root = Root::set(Some(dir.path()));
+    let mut names: Vec<String> = (0..40).rev().map(|index| format!("{index}.md")).collect();
+    names.insert(7, "missing.md".to_owned());
+    let csv = names.join(",");
+
+    let expected = batch(&csv, 1);
+    assert_eq!(expected.iter().map(|(filename, ..)| filename).collect::<Vec<_>>(), names.iter().collect::<Vec<_>>());
+    for threads in [0, 2, 3, 8, 64] {
+        for _ in 0..5 {
+            assert_eq!(batch(&csv, threads), expected, "with {threads} threads");
+        }
+    }
+}
+
+#[test]
+fn without_a_root_unknown_fixtures_fail_their_entry() {
+    let _root = Root::set(None);
+    let counts = batch("chapter1.md, missing.md", 0);
+    assert_eq!(counts[0], ("chapter1.md".to_owned(), CountErrorCode::Ok as i32, 18, 18));
+    assert_eq!(counts[1].1, CountErrorCode::FileNotFound as i32);
+}
@@ -0,0 +1,115 @@
+//! Fails when the generated C header differs from the checked-in `bindings.golden.h`, so every
+//! ABI change shows up in review as a diff to that file.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+include!("../bindings.rs");
+
+const GOLDEN: &str = "bindings.golden.h";
+const CONTEXT_LINES: usize = 3;
+
+#[test]
+fn header_matches_golden_file() {
+    let crate_dir = env!("CARGO_MANIFEST_DIR");
+    let mut generated = Vec::new();
+    bindings_builder(crate_dir).generate().expect("Unable to generate C bindings").write(&mut generated);
+    let generated = normalize(&String::from_utf8(generated).unwrap());
+
+    let golden_path = Path::new(crate_dir).join(GOLDEN);
+    if env::var_os("COUNT_UPDATE_GOLDEN").is_some() {
+        fs::write(&golden_path, &generated).unwrap();
+        return;
+    }
+    let golden = normalize(&fs::read_to_string(&golden_path).unwrap_or_default());
+    if golden != generated {
+        panic!(
+            "The generated header differs from {GOLDEN}:\n\n{}\nIf the ABI change is intended, regenerate it with \
+             `COUNT_UPDATE_GOLDEN=1 cargo test --test bindings` and commit the result.",
+            unified_diff(&golden, &generated),
+        );
+    }
+}
+
+/// Drops a leading comment banner (such as a generator notice or timestamp) and trailing
+/// whitespace, so only declarations are compared.
+fn normalize(header: &str) -> String {
+    let mut in_banner = false;
+    let lines = header.lines().skip_while(|line| {
+        let line = line.trim();
+        if in_banner {
+            in_banner = !line.ends_with("*/");
+            return true;
+        }
+        if line.starts_with("/*") {
+            in_banner = !line.ends_with("*/");
+            return true;
+        }
+        line.starts_with("//") || line.is_empty()
+    });
+    let mut normalized: String = lines.map(|line| format!("{}\n", line.trim_end())).collect();
+    normalized.truncate(normalized.trim_end().len());
+    normalized.push('\n');
+    normalized
+}
+
+enum Edit<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Line diff of `old` against `new` from a longest common subsequence, printed as unified diff
+/// hunks with a few lines of context.
+fn unified_diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // common[i][j] is the LCS length of old[i..] and new[j..].
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] { common[i + 1][j + 1] + 1 } else { common[i + 1][j].max(common[i][j + 1]) };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut edits = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push(Edit::Same(old[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            edits.push(Edit::Removed(old[i]));
+            i += 1;
+        } else {
+            edits.push(Edit::Added(new[j]));
+            j += 1;
+        }
+    }
+
+    let changed: Vec<usize> = (0..edits.len()).filter(|&index| !matches!(edits[index], Edit::Same(_))).collect();
+    let mut diff = format!("--- {GOLDEN}\n+++ generated\n");
+    let mut index = 0;
+    while index < changed.len() {
+        let start = changed[index].saturating_sub(CONTEXT_LINES);
+        let mut end = changed[index];
+        while index < changed.len() && changed[index] <= end + 2 * CONTEXT_LINES {
+            end = changed[index];
+            index += 1;
+        }
+        let end = (end + CONTEXT_LINES + 1).min(edits.len());
+        let old_start = edits[..start].iter().filter(|edit| !matches!(edit, Edit::Added(_))).count();
+        let new_start = edits[..start].iter().filter(|edit| !matches!(edit, Edit::Removed(_))).count();
+        let old_len = edits[start..end].iter().filter(|edit| !matches!(edit, Edit::Added(_))).count();
+        let new_len = edits[start..end].iter().filter(|edit| !matches!(edit, Edit::Removed(_))).count();
+        diff.push_str(&format!("@@ -{},{old_len} +{},{new_len} @@\n", old_start + 1, new_start + 1));
+        for edit in &edits[start..end] {
+            diff.push_str(&match edit {
+                Edit::Same(line) => format!(" {line}\n"),
+                Edit::Removed(line) => format!("-{line}\n"),
+                Edit::Added(line) => format!("+{line}\n"),
+            });
+        }
+    }
+    diff
+}
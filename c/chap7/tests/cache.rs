@@ -0,0 +1,88 @@
+//! The cache is process-wide, so the one test here owns it for the whole binary.
+
+use std::os::raw::c_char;
+
+use count::api::{CountErrorCode, CountOptions, TextEncoding, TextStats, WordRule};
+
+extern "C" {
+    fn count_cache_enable(max_entries: usize);
+    fn count_cache_stats(out_entries: *mut usize, out_hits: *mut u64) -> i32;
+    fn count_all(text: *const c_char, options: *const CountOptions, out: *mut TextStats) -> i32;
+}
+
+/// `count_all` over `text` with a NUL appended, as `(status, stats)`.
+fn counted(text: &[u8], options: &CountOptions) -> (i32, TextStats) {
+    let mut text = text.to_vec();
+    text.push(0);
+    let mut stats = TextStats::default();
+    let status = unsafe { count_all(text.as_ptr() as *const c_char, options, &mut stats) };
+    (status, stats)
+}
+
+/// `(entries, hits)`.
+fn cache_stats() -> (usize, u64) {
+    let (mut entries, mut hits) = (usize::MAX, u64::MAX);
+    assert_eq!(unsafe { count_cache_stats(&mut entries, &mut hits) }, CountErrorCode::Ok as i32);
+    (entries, hits)
+}
+
+/// Short and long texts, ASCII, UTF-8 and Latin-1, and two long ones that differ in one byte.
+fn texts() -> Vec<Vec<u8>> {
+    let mut texts = vec![
+        b"".to_vec(),
+        b"one two\nthree\n".to_vec(),
+        "naïve café, don't stop\n".as_bytes().to_vec(),
+        b"caf\xe9 \x85 cr\xe8me".to_vec(),
+    ];
+    let long: String = (0..2000).map(|i| format!("w{} ", i * 7 % 13)).collect();
+    let mut other = long.clone().into_bytes();
+    *other.last_mut().unwrap() = b'\n';
+    texts.push(long.into_bytes());
+    texts.push(other);
+    texts
+}
+
+fn all_options() -> Vec<CountOptions> {
+    let mut options = Vec::new();
+    for (word_rule, _) in WordRule::NAMES {
+        for (encoding, _) in TextEncoding::NAMES {
+            options.push(CountOptions { word_rule, encoding, ..CountOptions::default() });
+        }
+    }
+    options
+}
+
+#[test]
+fn cached_results_equal_uncached_ones_and_the_bound_holds() {
+    let inputs: Vec<(Vec<u8>, CountOptions)> =
+        texts().into_iter().flat_map(|text| all_options().into_iter().map(move |options| (text.clone(), options))).collect();
+    unsafe { count_cache_enable(0) };
+    let uncached: Vec<_> = inputs.iter().map(|(text, options)| counted(text, options)).collect();
+    assert_eq!(cache_stats(), (0, 0));
+    let ok = uncached.iter().filter(|(status, _)| *status == CountErrorCode::Ok as i32).count();
+    // The Latin-1 text is not UTF-8, so its two UTF-8 combinations fail and are never cached.
+    assert_eq!(ok, inputs.len() - 2);
+
+    unsafe { count_cache_enable(inputs.len()) };
+    for pass in 0..2 {
+        for ((text, options), expected) in inputs.iter().zip(&uncached) {
+            assert_eq!(counted(text, options), *expected, "pass {pass}");
+        }
+    }
+    assert_eq!(cache_stats(), (ok, ok as u64));
+
+    // Shrinking to two keeps the two most recently used: the last two inputs.
+    unsafe { count_cache_enable(2) };
+    assert_eq!(cache_stats(), (2, ok as u64));
+    let (first, last) = (&inputs[0], &inputs[inputs.len() - 1]);
+    assert_eq!(counted(&last.0, &last.1), uncached[inputs.len() - 1]);
+    assert_eq!(cache_stats(), (2, ok as u64 + 1));
+    // The first input was evicted, so it misses and in turn evicts the oldest of the two.
+    assert_eq!(counted(&first.0, &first.1), uncached[0]);
+    assert_eq!(cache_stats(), (2, ok as u64 + 1));
+    assert_eq!(counted(&first.0, &first.1), uncached[0]);
+    assert_eq!(cache_stats(), (2, ok as u64 + 2));
+
+    unsafe { count_cache_enable(0) };
+    assert_eq!(cache_stats(), (0, 0));
+}
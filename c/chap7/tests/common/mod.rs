@@ -0,0 +1,35 @@
+//! The content root is process-wide, so tests that set it hold `ROOT_LOCK` through `Root`.
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use count::api::CountErrorCode;
+
+extern "C" {
+    fn set_content_root(path: *const c_char) -> i32;
+}
+
+static ROOT_LOCK: Mutex<()> = Mutex::new(());
+
+/// Sets the content root for as long as it lives, then clears it again.
+pub struct Root {
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Root {
+    pub fn set(path: Option<&Path>) -> Root {
+        let lock = ROOT_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let path = path.map(|path| CString::new(path.to_str().unwrap()).unwrap());
+        let status = unsafe { set_content_root(path.as_ref().map_or(std::ptr::null(), |path| path.as_ptr())) };
+        assert_eq!(status, CountErrorCode::Ok as i32);
+        Root { _lock: lock }
+    }
+}
+
+impl Drop for Root {
+    fn drop(&mut self) {
+        unsafe { set_content_root(std::ptr::null()) };
+    }
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use std::ffi::{c_void, CStr, CString};
+use std::fs;
+use std::os::raw::c_char;
+
+use common::Root;
+use count::api::{self, CountErrorCode};
+
+extern "C" {
+    fn csv_validate_manifest(
+        csv: *const c_char,
+        cb: unsafe extern "C" fn(filename: *const c_char, exists: bool, context: *const c_void),
+        context: *const c_void,
+    ) -> i32;
+}
+
+/// A temp directory holding `root/inside.md` and, outside the root, `outside.md`.
+fn layout() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("root")).unwrap();
+    fs::write(dir.path().join("root/inside.md"), "inside\n").unwrap();
+    fs::write(dir.path().join("outside.md"), "outside\n").unwrap();
+    dir
+}
+
+fn merge_code(csv: &str) -> CountErrorCode {
+    api::merge_files(csv).unwrap_err().code
+}
+
+#[test]
+fn relative_values_resolve_against_the_root() {
+    let dir = layout();
+    let _root = Root::set(Some(&dir.path().join("root")));
+    assert_eq!(api::merge_files("inside.md, inside.md").unwrap(), "inside\ninside\n");
+}
+
+#[test]
+fn dot_dot_components_cannot_leave_the_root() {
+    let dir = layout();
+    let _root = Root::set(Some(&dir.path().join("root")));
+    assert_eq!(merge_code("inside.md, ../outside.md"), CountErrorCode::PathOutsideRoot);
+    assert_eq!(api::merge_files("../root/inside.md").unwrap(), "inside\n");
+}
+
+#[test]
+fn absolute_paths_outside_the_root_are_rejected() {
+    let dir = layout();
+    let _root = Root::set(Some(&dir.path().join("root")));
+    let outside = dir.path().join("outside.md");
+    assert_eq!(merge_code(outside.to_str().unwrap()), CountErrorCode::PathOutsideRoot);
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_out_of_the_root_are_rejected() {
+    let dir = layout();
+    std::os::unix::fs::symlink(dir.path().join("outside.md"), dir.path().join("root/link.md")).unwrap();
+    let _root = Root::set(Some(&dir.path().join("root")));
+    assert_eq!(merge_code("link.md"), CountErrorCode::PathOutsideRoot);
+}
+
+#[test]
+fn missing_files_under_the_root_are_not_found() {
+    let dir = layout();
+    let _root = Root::set(Some(&dir.path().join("root")));
+    assert_eq!(merge_code("inside.md, missing.md"), CountErrorCode::FileNotFound);
+}
+
+#[test]
+fn without_a_root_only_the_fixtures_exist() {
+    let _root = Root::set(None);
+    assert_eq!(api::merge_files("chapter1.md, chapter2.md").unwrap(), "# Getting started\n# Wrapping up\n");
+    assert_eq!(merge_code("chapter1.md, missing.md"), CountErrorCode::FileNotFound);
+}
+
+unsafe extern "C" fn collect_exists(filename: *const c_char, exists: bool, context: *const c_void) {
+    let reports = &mut *(context as *mut Vec<(String, bool)>);
+    reports.push((CStr::from_ptr(filename).to_str().unwrap().to_owned(), exists));
+}
+
+/// The return value of `csv_validate_manifest` and what it reported per file.
+fn validate(csv: &str) -> (i32, Vec<(String, bool)>) {
+    let csv = CString::new(csv).unwrap();
+    let mut reports = Vec::new();
+    let context = &mut reports as *mut Vec<(String, bool)> as *const c_void;
+    let missing = unsafe { csv_validate_manifest(csv.as_ptr(), collect_exists, context) };
+    (missing, reports)
+}
+
+#[test]
+fn validate_manifest_reports_existing_and_missing_files_under_the_root() {
+    let dir = layout();
+    fs::create_dir(dir.path().join("root/sub")).unwrap();
+    let _root = Root::set(Some(&dir.path().join("root")));
+    let (missing, reports) = validate("inside.md, missing.md, ../outside.md, sub, inside.md");
+    assert_eq!(missing, 3);
+    let expected = [("inside.md", true), ("missing.md", false), ("../outside.md", false), ("sub", false), ("inside.md", true)];
+    assert_eq!(reports, expected.map(|(filename, exists)| (filename.to_owned(), exists)));
+}
+
+#[test]
+fn validate_manifest_without_a_root_checks_paths() {
+    let dir = layout();
+    let _root = Root::set(None);
+    let outside = dir.path().join("outside.md");
+    let csv = format!("{}, {}, chapter1.md, missing.md", outside.display(), dir.path().join("gone.md").display());
+    let (missing, reports) = validate(&csv);
+    assert_eq!(missing, 2);
+    assert_eq!(reports.iter().map(|(_, exists)| *exists).collect::<Vec<_>>(), [true, false, true, false]);
+}
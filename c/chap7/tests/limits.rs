@@ -0,0 +1,95 @@
+//! Limits are process-wide, so the one test here owns them for the whole binary.
+
+mod common;
+
+use std::ffi::{c_void, CString};
+use std::fs;
+use std::os::raw::c_char;
+
+use common::Root;
+use count::api::{self, CountErrorCode};
+
+extern "C" {
+    fn set_limits(max_text_bytes: u64, max_merge_total_bytes: u64, max_csv_values: u64);
+    fn count_characters_os(bytes: *const c_char, len: usize, out: *mut u64) -> i32;
+    fn csv_for_each_value(
+        csv: *const c_char,
+        c_callback: unsafe extern "C" fn(*const c_char, *const c_void),
+        context: *const c_void,
+    ) -> i64;
+    fn csv_find_duplicates(
+        csv: *const c_char,
+        callback: unsafe extern "C" fn(value: *const c_char, first_index: usize, duplicate_index: usize, context: *mut c_void),
+        context: *mut c_void,
+    ) -> i64;
+}
+
+unsafe extern "C" fn count_call(_: *const c_char, context: *const c_void) {
+    *(context as *mut u64) += 1;
+}
+
+fn for_each_value(csv: &str) -> (i64, u64) {
+    let csv = CString::new(csv).unwrap();
+    let mut calls = 0u64;
+    let count = unsafe { csv_for_each_value(csv.as_ptr(), count_call, &mut calls as *mut u64 as *const c_void) };
+    (count, calls)
+}
+
+unsafe extern "C" fn ignore_duplicate(_: *const c_char, _: usize, _: usize, _: *mut c_void) {}
+
+fn duplicates(csv: &str) -> i64 {
+    let csv = CString::new(csv).unwrap();
+    unsafe { csv_find_duplicates(csv.as_ptr(), ignore_duplicate, std::ptr::null_mut()) }
+}
+
+fn characters(text: &str) -> Result<u64, i32> {
+    let mut out = 0;
+    match unsafe { count_characters_os(text.as_ptr() as *const c_char, text.len(), &mut out) } {
+        0 => Ok(out),
+        status => Err(status),
+    }
+}
+
+fn merge_code(csv: &str) -> CountErrorCode {
+    match api::merge_files(csv) {
+        Ok(_) => CountErrorCode::Ok,
+        Err(error) => error.code,
+    }
+}
+
+#[test]
+fn each_limit_allows_its_value_and_rejects_one_more() {
+    unsafe { set_limits(4, 0, 0) };
+    assert_eq!(characters("abcd"), Ok(4));
+    assert_eq!(characters("abcde"), Err(CountErrorCode::LimitExceeded as i32));
+
+    // The two chapter fixtures are 18 and 14 bytes.
+    unsafe { set_limits(0, 32, 0) };
+    assert_eq!(merge_code("chapter1.md, chapter2.md"), CountErrorCode::Ok);
+    unsafe { set_limits(0, 31, 0) };
+    assert_eq!(merge_code("chapter1.md, chapter2.md"), CountErrorCode::LimitExceeded);
+    assert_eq!(merge_code("chapter1.md"), CountErrorCode::Ok);
+
+    unsafe { set_limits(0, 0, 2) };
+    assert_eq!(for_each_value("a, b"), (2, 2));
+    assert_eq!(for_each_value("a, b, c"), (-(CountErrorCode::LimitExceeded as i64), 0));
+    assert_eq!(duplicates("a, a"), 1);
+    assert_eq!(duplicates("a, a, a"), -(CountErrorCode::LimitExceeded as i64));
+    // A sparse 8 GiB file: merging it would exhaust memory if it were read before the checks.
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("small.md"), "small\n").unwrap();
+    fs::File::create(dir.path().join("huge.md")).unwrap().set_len(8 << 30).unwrap();
+    let root = Root::set(Some(dir.path()));
+    unsafe { set_limits(0, 1 << 20, 0) };
+    assert_eq!(merge_code("small.md, huge.md"), CountErrorCode::LimitExceeded);
+    assert_eq!(merge_code("small.md, small.md"), CountErrorCode::Ok);
+    unsafe { set_limits(1 << 20, 0, 0) };
+    assert_eq!(merge_code("huge.md"), CountErrorCode::LimitExceeded);
+    drop(root);
+
+    unsafe { set_limits(0, 0, 0) };
+    assert_eq!(for_each_value("a, b, c"), (3, 3));
+    assert_eq!(duplicates("a, a, a"), 2);
+    assert_eq!(characters("abcde"), Ok(5));
+    assert_eq!(merge_code("chapter1.md, chapter2.md"), CountErrorCode::Ok);
+}
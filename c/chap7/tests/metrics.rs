@@ -0,0 +1,77 @@
+//! The metric registry is process-wide and has no way to unregister, so the one test here
+//! registers its plugins once and checks listing and running together.
+
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+
+use count::api::CountErrorCode;
+
+/// Mirrors `StringArray` from the header.
+#[repr(C)]
+struct StringArray {
+    items: *mut *mut c_char,
+    len: usize,
+}
+
+extern "C" {
+    fn register_metric(
+        name: *const c_char,
+        metric: unsafe extern "C" fn(text: *const c_char, context: *mut c_void) -> u64,
+        context: *mut c_void,
+    ) -> i32;
+    fn list_metrics(out: *mut StringArray) -> i32;
+    fn run_metric(name: *const c_char, text: *const c_char, out: *mut u64) -> i32;
+    fn string_array_free(array: *mut StringArray);
+}
+
+unsafe extern "C" fn count_vowels(text: *const c_char, _: *mut c_void) -> u64 {
+    CStr::from_ptr(text).to_bytes().iter().filter(|byte| b"aeiou".contains(byte)).count() as u64
+}
+
+/// Returns the `u64` behind `context`, whatever the text.
+unsafe extern "C" fn constant(_: *const c_char, context: *mut c_void) -> u64 {
+    *(context as *const u64)
+}
+
+fn register(name: &str, metric: unsafe extern "C" fn(*const c_char, *mut c_void) -> u64, context: *mut c_void) -> i32 {
+    let name = CString::new(name).unwrap();
+    unsafe { register_metric(name.as_ptr(), metric, context) }
+}
+
+fn names() -> Vec<String> {
+    let mut array = StringArray { items: ptr::null_mut(), len: 0 };
+    assert_eq!(unsafe { list_metrics(&mut array) }, CountErrorCode::Ok as i32);
+    let items = unsafe { std::slice::from_raw_parts(array.items, array.len) };
+    let names = items.iter().map(|&item| unsafe { CStr::from_ptr(item) }.to_str().unwrap().to_owned()).collect();
+    unsafe { string_array_free(&mut array) };
+    names
+}
+
+fn run(name: &str, text: &str) -> Result<u64, i32> {
+    let (name, text) = (CString::new(name).unwrap(), CString::new(text).unwrap());
+    let mut out = 0;
+    match unsafe { run_metric(name.as_ptr(), text.as_ptr(), &mut out) } {
+        0 => Ok(out),
+        status => Err(status),
+    }
+}
+
+#[test]
+fn plugins_are_listed_after_the_built_ins_and_run_by_name() {
+    assert_eq!(names(), ["bytes", "characters", "words", "lines"]);
+
+    static SEVEN: u64 = 7;
+    assert_eq!(register("vowels", count_vowels, ptr::null_mut()), CountErrorCode::Ok as i32);
+    assert_eq!(register("seven", constant, &SEVEN as *const u64 as *mut c_void), CountErrorCode::Ok as i32);
+    assert_eq!(names(), ["bytes", "characters", "words", "lines", "vowels", "seven"]);
+
+    assert_eq!(register("words", count_vowels, ptr::null_mut()), CountErrorCode::InvalidArgument as i32);
+    assert_eq!(register("vowels", constant, ptr::null_mut()), CountErrorCode::InvalidArgument as i32);
+    assert_eq!(names().len(), 6);
+
+    assert_eq!(run("words", "two words"), Ok(2));
+    assert_eq!(run("vowels", "education"), Ok(5));
+    assert_eq!(run("seven", "anything"), Ok(7));
+    assert_eq!(run("consonants", "education"), Err(CountErrorCode::UnknownMetric as i32));
+}
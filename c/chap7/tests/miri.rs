@@ -0,0 +1,242 @@
+//! Calls `print_version`, `count_characters`, `count_string_dup`/`count_string_free`,
+//! `csv_for_each_value`, `csv_merge_files`, `csv_merge_files_dedup_lines`,
+//! `csv_free_merged_file`, `weighted_char_count`, `window_char_counts`, `first_distinct_chars`,
+//! `parse_args` and `complete_args`/`string_array_free` the way a C caller would, with Rust mock
+//! callbacks and free functions, and frees everything they return, so
+//! `cargo miri test --test miri` can check the pointer handling. Only the built-in fixtures are
+//! read, so nothing touches the disk.
+//!
+//! `parse_args` panics on a NULL or empty argv, which aborts at the C boundary, so Miri walks that
+//! argv through `complete_args`, which converts it the same way, and a child process checks that
+//! the panic is the parser's "Missing command." rather than a fault.
+
+extern crate count;
+
+use std::cell::RefCell;
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+
+/// Mirrors `Arguments` from the header, with the enums as their `int` values.
+#[repr(C)]
+struct Arguments {
+    command: i32,
+    filename: *const c_char,
+    file_mode: i32,
+    word_rule: i32,
+    newline_fix: bool,
+    format: i32,
+    strict: bool,
+    encoding: i32,
+}
+
+/// Mirrors `StringArray` from the header.
+#[repr(C)]
+struct StringArray {
+    items: *mut *mut c_char,
+    len: usize,
+}
+
+extern "C" {
+    fn print_version();
+    fn count_characters(text: *const c_char) -> u64;
+    fn count_string_dup(string: *const c_char) -> *mut c_char;
+    fn count_string_free(string: *mut c_char);
+    fn csv_for_each_value(
+        csv: *const c_char,
+        c_callback: unsafe extern "C" fn(*const c_char, *const c_void),
+        context: *const c_void,
+    ) -> i64;
+    fn csv_merge_files(csv: *mut c_char, free_csv: unsafe extern "C" fn(*mut c_char)) -> *mut c_char;
+    fn csv_merge_files_dedup_lines(csv: *mut c_char, free_csv: unsafe extern "C" fn(*mut c_char)) -> *mut c_char;
+    fn csv_free_merged_file(merged: *mut c_char);
+    fn weighted_char_count(
+        text: *const c_char,
+        weight_cb: unsafe extern "C" fn(codepoint: u32, context: *const c_void) -> u64,
+        context: *const c_void,
+        out: *mut u64,
+    ) -> i32;
+    fn first_distinct_chars(text: *const c_char, n: u64) -> *mut c_char;
+    fn window_char_counts(
+        text: *const c_char,
+        window: u64,
+        cb: unsafe extern "C" fn(window_index: u64, count: u64, context: *const c_void),
+        context: *const c_void,
+    );
+    fn parse_args(argc: usize, argv: *const *const c_char) -> Arguments;
+    fn complete_args(argc: usize, argv: *const *const c_char, out: *mut StringArray) -> i32;
+    fn string_array_free(array: *mut StringArray);
+}
+
+thread_local! {
+    /// The strings `mock_free` was handed, in order.
+    static FREED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Stands in for the C allocator's `free` for strings made with `CString::into_raw`.
+unsafe extern "C" fn mock_free(string: *mut c_char) {
+    let string = CString::from_raw(string).into_string().unwrap();
+    FREED.with(|freed| freed.borrow_mut().push(string));
+}
+
+unsafe extern "C" fn collect_value(value: *const c_char, context: *const c_void) {
+    let values = &mut *(context as *mut Vec<String>);
+    values.push(CStr::from_ptr(value).to_str().unwrap().to_owned());
+}
+
+unsafe extern "C" fn weigh_by_context(_: u32, context: *const c_void) -> u64 {
+    *(context as *const u64)
+}
+
+unsafe extern "C" fn collect_window(index: u64, count: u64, context: *const c_void) {
+    let windows = &mut *(context as *mut Vec<(u64, u64)>);
+    windows.push((index, count));
+}
+
+/// Copies a string returned by the library and frees it with `free`.
+unsafe fn take(string: *mut c_char, free: unsafe extern "C" fn(*mut c_char)) -> String {
+    assert!(!string.is_null());
+    let copy = CStr::from_ptr(string).to_str().unwrap().to_owned();
+    free(string);
+    copy
+}
+
+#[test]
+fn print_version_writes_without_arguments() {
+    unsafe { print_version() };
+}
+
+#[test]
+fn count_characters_reads_up_to_the_terminator() {
+    let text = CString::new("naïve").unwrap();
+    assert_eq!(unsafe { count_characters(text.as_ptr()) }, 5);
+}
+
+#[test]
+fn dup_and_free_round_trip() {
+    let original = CString::new("copy me").unwrap();
+    let copy = unsafe { count_string_dup(original.as_ptr()) };
+    assert_eq!(unsafe { take(copy, count_string_free) }, "copy me");
+    assert!(unsafe { count_string_dup(ptr::null()) }.is_null());
+    unsafe { count_string_free(ptr::null_mut()) };
+}
+
+#[test]
+fn for_each_value_hands_out_borrowed_values() {
+    let csv = CString::new(" chapter1.md ,chapter2.md").unwrap();
+    let mut values: Vec<String> = Vec::new();
+    let count = unsafe { csv_for_each_value(csv.as_ptr(), collect_value, &mut values as *mut _ as *const c_void) };
+    assert_eq!(count, 2);
+    assert_eq!(values, ["chapter1.md", "chapter2.md"]);
+}
+
+#[test]
+fn merges_free_the_csv_with_the_callers_function() {
+    FREED.with(|freed| freed.borrow_mut().clear());
+    let csv = CString::new("chapter1.md, chapter2.md").unwrap().into_raw();
+    let merged = unsafe { take(csv_merge_files(csv, mock_free), csv_free_merged_file) };
+    assert_eq!(merged, "# Getting started\n# Wrapping up\n");
+
+    let csv = CString::new("chapter1.md, chapter1.md").unwrap().into_raw();
+    let merged = unsafe { take(csv_merge_files_dedup_lines(csv, mock_free), csv_free_merged_file) };
+    assert_eq!(merged, "# Getting started\n");
+
+    let csv = CString::new("missing.md").unwrap().into_raw();
+    assert!(unsafe { csv_merge_files(csv, mock_free) }.is_null());
+    unsafe { csv_free_merged_file(ptr::null_mut()) };
+
+    let freed = FREED.with(|freed| freed.take());
+    assert_eq!(freed, ["chapter1.md, chapter2.md", "chapter1.md, chapter1.md", "missing.md"]);
+}
+
+#[test]
+fn callbacks_receive_their_context() {
+    let text = CString::new("abc\nde\nf").unwrap();
+    let weight = 3u64;
+    let mut total = 0;
+    let status = unsafe { weighted_char_count(text.as_ptr(), weigh_by_context, &weight as *const u64 as *const c_void, &mut total) };
+    assert_eq!((status, total), (0, 24));
+
+    let mut windows: Vec<(u64, u64)> = Vec::new();
+    unsafe { window_char_counts(text.as_ptr(), 2, collect_window, &mut windows as *mut _ as *const c_void) };
+    assert_eq!(windows, [(0, 7), (1, 1)]);
+}
+
+#[test]
+fn first_distinct_chars_is_freed_with_count_string_free() {
+    let text = CString::new("banana").unwrap();
+    assert_eq!(unsafe { take(first_distinct_chars(text.as_ptr(), 3), count_string_free) }, "ban");
+    assert!(unsafe { first_distinct_chars(ptr::null(), 3) }.is_null());
+}
+
+/// `args` as owned strings plus the pointer array a C `main` would see.
+fn argv(args: &[&str]) -> (Vec<CString>, Vec<*const c_char>) {
+    let owned: Vec<CString> = args.iter().map(|arg| CString::new(*arg).unwrap()).collect();
+    let pointers = owned.iter().map(|arg| arg.as_ptr()).collect();
+    (owned, pointers)
+}
+
+#[test]
+fn parse_args_reads_a_cstring_argv_and_borrows_the_filename() {
+    let (owned, pointers) = argv(&["count", "words", "chapter1.md", "--csv-list"]);
+    let args = unsafe { parse_args(pointers.len(), pointers.as_ptr()) };
+    // `Command_Words` and `FileMode_CsvList`.
+    assert_eq!((args.command, args.file_mode), (3, 1));
+    assert_eq!(args.filename, owned[2].as_ptr());
+    assert_eq!(unsafe { CStr::from_ptr(args.filename) }.to_str(), Ok("chapter1.md"));
+    assert!(!args.newline_fix && !args.strict);
+    assert_eq!((args.word_rule, args.format, args.encoding), (0, 0, 0));
+
+    let (_owned, pointers) = argv(&["count", "version"]);
+    let args = unsafe { parse_args(pointers.len(), pointers.as_ptr()) };
+    assert_eq!((args.command, args.filename), (0, ptr::null()));
+}
+
+/// The completions `complete_args` offers for `argc` and `argv`, freed with `string_array_free`.
+fn completions(argc: usize, argv: *const *const c_char) -> Vec<String> {
+    let mut array = StringArray { items: ptr::null_mut(), len: 0 };
+    assert_eq!(unsafe { complete_args(argc, argv, &mut array) }, 0);
+    let items = unsafe { std::slice::from_raw_parts(array.items, array.len) };
+    let strings = items.iter().map(|&item| unsafe { CStr::from_ptr(item) }.to_str().unwrap().to_owned()).collect();
+    unsafe { string_array_free(&mut array) };
+    strings
+}
+
+#[test]
+fn a_null_or_empty_argv_is_read_as_no_arguments() {
+    let commands = completions(0, ptr::null());
+    assert!(commands.iter().any(|name| name == "words"), "{commands:?}");
+    let (_owned, pointers) = argv(&["count"]);
+    assert_eq!(completions(0, pointers.as_ptr()), commands);
+    assert_eq!(completions(1, pointers.as_ptr()), commands);
+}
+
+/// Tells a re-run of this binary which argv `parse_args_in_child` should pass.
+const CHILD_ARGV: &str = "COUNT_MIRI_TEST_CHILD_ARGV";
+
+/// Does nothing unless `parse_args_rejects_a_null_or_empty_argv` started this process.
+#[test]
+fn parse_args_in_child() {
+    let (_owned, pointers) = argv(&["count"]);
+    match std::env::var(CHILD_ARGV).as_deref() {
+        Ok("null") => unsafe { parse_args(0, ptr::null()) },
+        Ok("empty") => unsafe { parse_args(0, pointers.as_ptr()) },
+        _ => return,
+    };
+}
+
+#[cfg_attr(miri, ignore = "Miri cannot start processes")]
+#[test]
+fn parse_args_rejects_a_null_or_empty_argv() {
+    for argv in ["null", "empty"] {
+        // The abort skips the harness, so the panic message only survives uncaptured.
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "parse_args_in_child", "--test-threads=1", "--nocapture"])
+            .env(CHILD_ARGV, argv)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success(), "{argv} argv was accepted");
+        assert!(stderr.contains("Missing command."), "{argv} argv: {stderr}");
+    }
+}
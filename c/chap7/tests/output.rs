@@ -0,0 +1,103 @@
+//! The output mode and callback are process-wide, so one test here walks through every mode in
+//! process and another checks stdout by re-running this binary once per mode.
+
+extern crate count;
+
+use std::ffi::{c_void, CStr};
+use std::io::Write;
+use std::os::raw::c_char;
+use std::process::Command;
+use std::{env, io, ptr};
+
+/// Tells a re-run of this binary which mode `print_version_in_child` should print in.
+const CHILD_MODE: &str = "COUNT_OUTPUT_TEST_CHILD_MODE";
+
+/// Fences the library's stdout from the test harness's own output.
+const BEGIN: &str = "--- begin library output ---\n";
+const END: &str = "--- end library output ---\n";
+
+/// Mirrors `OutputMode` from the header.
+#[repr(C)]
+enum OutputMode {
+    Stdout,
+    Callback,
+    Both,
+    Discard,
+}
+
+extern "C" {
+    fn set_output_callback(callback: Option<unsafe extern "C" fn(*const c_char, *mut c_void)>, context: *mut c_void);
+    fn set_output_mode(mode: OutputMode);
+    fn print_version();
+}
+
+unsafe extern "C" fn capture(line: *const c_char, context: *mut c_void) {
+    let lines = &mut *(context as *mut Vec<String>);
+    lines.push(CStr::from_ptr(line).to_str().unwrap().to_owned());
+}
+
+/// What the callback saw of one `print_version` in `mode`.
+fn captured(mode: OutputMode) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    unsafe {
+        set_output_callback(Some(capture), &mut lines as *mut _ as *mut c_void);
+        set_output_mode(mode);
+        print_version();
+        set_output_callback(None, ptr::null_mut());
+    }
+    lines
+}
+
+#[test]
+fn the_mode_decides_whether_the_callback_sees_output() {
+    assert_eq!(captured(OutputMode::Stdout), Vec::<String>::new());
+    assert_eq!(captured(OutputMode::Callback), ["count version 1.0.0"]);
+    assert_eq!(captured(OutputMode::Both), ["count version 1.0.0"]);
+    assert_eq!(captured(OutputMode::Discard), Vec::<String>::new());
+
+    // Callback mode with no callback registered drops the line.
+    unsafe {
+        set_output_mode(OutputMode::Callback);
+        print_version();
+        set_output_mode(OutputMode::Stdout);
+    }
+}
+
+/// Does nothing unless `the_mode_decides_what_reaches_stdout` started this process.
+#[test]
+fn print_version_in_child() {
+    let mode = match env::var(CHILD_MODE).as_deref() {
+        Ok("stdout") => OutputMode::Stdout,
+        Ok("both") => OutputMode::Both,
+        Ok("discard") => OutputMode::Discard,
+        _ => return,
+    };
+    let mut stdout = io::stdout();
+    stdout.write_all(BEGIN.as_bytes()).and_then(|()| stdout.flush()).unwrap();
+    unsafe {
+        set_output_mode(mode);
+        print_version();
+    }
+    stdout.write_all(END.as_bytes()).and_then(|()| stdout.flush()).unwrap();
+}
+
+/// What a re-run of this binary printed between the fences with `mode` set.
+fn child_stdout(mode: &str) -> String {
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "print_version_in_child", "--test-threads=1"])
+        .env(CHILD_MODE, mode)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "child in {mode} mode failed: {output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let start = stdout.find(BEGIN).unwrap() + BEGIN.len();
+    let end = stdout.find(END).unwrap();
+    stdout[start..end].to_owned()
+}
+
+#[test]
+fn the_mode_decides_what_reaches_stdout() {
+    assert_eq!(child_stdout("stdout"), "count version 1.0.0\n");
+    assert_eq!(child_stdout("both"), "count version 1.0.0\n");
+    assert_eq!(child_stdout("discard"), "");
+}
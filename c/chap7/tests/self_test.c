@@ -0,0 +1,17 @@
+// Calls count_self_test through the C ABI, as a host would after loading the library. Exits 0
+// when every stage passes and prints the failing stage otherwise.
+
+#include "bindings.h"
+
+#include <stdio.h>
+
+int main(void) {
+    const int32_t stage = count_self_test();
+    if (stage == SelfTestStage_Passed) {
+        printf("count_self_test passed\n");
+        return 0;
+    }
+    const char* message = last_error_message();
+    fprintf(stderr, "count_self_test failed at stage %d: %s\n", stage, message ? message : "(no message)");
+    return 1;
+}
@@ -0,0 +1,64 @@
+//! Strict mode and the warning callback are process-wide, so the one test here toggles them
+//! around each warning.
+
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+
+use count::api::{CountErrorCode, ParseError};
+
+/// Mirrors `Arguments` from the header, with the enums as their `int` values.
+#[repr(C)]
+#[allow(dead_code)] // Only written by the library.
+struct Arguments {
+    command: i32,
+    filename: *const c_char,
+    file_mode: i32,
+    word_rule: i32,
+    newline_fix: bool,
+    format: i32,
+    strict: bool,
+    encoding: i32,
+}
+
+extern "C" {
+    fn set_strict_mode(enabled: bool);
+    fn set_warning_callback(callback: Option<unsafe extern "C" fn(*const c_char, *mut c_void)>, context: *mut c_void);
+    fn arguments_deserialize(s: *const c_char, out: *mut Arguments) -> ParseError;
+    fn last_error_code() -> i32;
+}
+
+unsafe extern "C" fn collect_warning(message: *const c_char, context: *mut c_void) {
+    let warnings = &mut *(context as *mut Vec<String>);
+    warnings.push(CStr::from_ptr(message).to_str().unwrap().to_owned());
+}
+
+/// Deserializes arguments without a filename, returning the result and the warnings raised.
+fn deserialize(s: &str, strict: bool) -> (ParseError, Vec<String>) {
+    let s = CString::new(s).unwrap();
+    let mut warnings: Vec<String> = Vec::new();
+    let mut out = std::mem::MaybeUninit::<Arguments>::uninit();
+    let result = unsafe {
+        set_strict_mode(strict);
+        set_warning_callback(Some(collect_warning), &mut warnings as *mut _ as *mut c_void);
+        let result = arguments_deserialize(s.as_ptr(), out.as_mut_ptr());
+        set_warning_callback(None, ptr::null_mut());
+        set_strict_mode(false);
+        result
+    };
+    (result, warnings)
+}
+
+#[test]
+fn strict_mode_turns_warnings_into_errors() {
+    let warning = vec!["Ignoring unknown argument key: colour".to_owned()];
+
+    assert_eq!(deserialize("command=version,colour=red", false), (ParseError::Ok, warning.clone()));
+    // The callback still hears about the warning that failed the call.
+    assert_eq!(deserialize("command=version,colour=red", true), (ParseError::UnknownKey, warning));
+    assert_eq!(unsafe { last_error_code() }, CountErrorCode::UnknownKey as i32);
+
+    // Without a warning, strict mode changes nothing.
+    assert_eq!(deserialize("command=version", true), (ParseError::Ok, vec![]));
+    assert_eq!(deserialize("command=version,colour=red", false).0, ParseError::Ok);
+}